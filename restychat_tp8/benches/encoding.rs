@@ -0,0 +1,77 @@
+// =============================
+// JSON vs MessagePack : débit d'encodage/décodage et taille des trames
+// cargo bench --bench encoding
+// Les tailles des trames sont affichées avant les mesures.
+// =============================
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use restychat_tp8::{Encoding, HistoryEntry, ServerMessage, DEFAULT_ROOM};
+
+const ENCODINGS: [(&str, Encoding); 2] = [("json", Encoding::Json), ("msgpack", Encoding::MessagePack)];
+
+// Messages représentatifs : un message de salon et une réponse /history pleine
+fn samples() -> Vec<(&'static str, ServerMessage)> {
+    let broadcast = ServerMessage::MessageBroadcast {
+        id: 4242,
+        timestamp: 1_700_000_000,
+        from: "alice".to_string(),
+        content: "Salut tout le monde, quelqu'un a testé la nouvelle version ?".to_string(),
+        room: DEFAULT_ROOM.to_string(),
+    };
+    let history = ServerMessage::History {
+        room: DEFAULT_ROOM.to_string(),
+        messages: (1..=200)
+            .map(|id| HistoryEntry {
+                id,
+                timestamp: 1_700_000_000 + id,
+                from: format!("bot_{}", id % 7),
+                content: format!("mesure n°{} : température 21.{}°C", id, id % 10),
+                room: DEFAULT_ROOM.to_string(),
+            })
+            .collect(),
+    };
+    vec![("broadcast", broadcast), ("history_200", history)]
+}
+
+fn print_frame_sizes() {
+    println!("📏 Taille des trames (octets, en-tête de 5 octets compris):");
+    for (nom, msg) in samples() {
+        let tailles: Vec<String> = ENCODINGS
+            .iter()
+            .map(|(label, encoding)| format!("{} = {}", label, msg.encode(*encoding).unwrap().serialize().len()))
+            .collect();
+        println!("  {:<12} {}", nom, tailles.join(", "));
+    }
+}
+
+fn bench_encode(c: &mut Criterion) {
+    print_frame_sizes();
+
+    let mut group = c.benchmark_group("encode");
+    for (nom, msg) in samples() {
+        group.throughput(Throughput::Elements(1));
+        for (label, encoding) in ENCODINGS {
+            group.bench_with_input(BenchmarkId::new(label, nom), &msg, |b, msg| {
+                b.iter(|| black_box(msg).encode(encoding).unwrap().serialize())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (nom, msg) in samples() {
+        for (label, encoding) in ENCODINGS {
+            let frame = msg.encode(encoding).unwrap();
+            group.throughput(Throughput::Bytes(frame.data.len() as u64));
+            group.bench_with_input(BenchmarkId::new(label, nom), &frame, |b, frame| {
+                b.iter(|| ServerMessage::decode(black_box(frame), encoding).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,218 @@
+// =============================
+// Comptes utilisateurs
+// Fichier JSON : { "alice": { "password_hash": "$argon2id$...", "created_at": 1700000000 } }
+// Les mots de passe sont hachés avec Argon2id et un sel aléatoire par compte.
+// Après MAX_FAILED_LOGINS échecs consécutifs, le compte est verrouillé LOCKOUT_DURATION.
+// =============================
+
+use crate::history::unix_timestamp;
+use anyhow::{anyhow, Result};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+pub const MAX_FAILED_LOGINS: u32 = 5;
+pub const LOCKOUT_DURATION: Duration = Duration::from_secs(5 * 60);
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub password_hash: String, // format PHC : algorithme, paramètres, sel et hash
+    pub created_at: u64,
+}
+
+// Échecs de connexion consécutifs d'un compte (gardés en mémoire seulement)
+#[derive(Default)]
+struct Failures {
+    count: u32,
+    locked_until: Option<Instant>,
+}
+
+pub struct UserStore {
+    path: Option<PathBuf>, // None : comptes perdus à l'arrêt du serveur
+    accounts: HashMap<String, Account>,
+    failures: HashMap<String, Failures>,
+}
+
+// Un nom d'utilisateur : 1 à 32 lettres, chiffres, '-' ou '_'
+pub fn is_valid_username(username: &str) -> bool {
+    (1..=32).contains(&username.len())
+        && username.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// Hache un mot de passe avec un sel aléatoire (coûteux : à appeler hors du runtime async)
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow!("Hachage impossible: {}", e))
+}
+
+// Hash d'un mot de passe que personne ne connaît, vérifié pour un nom sans compte :
+// la réponse prend le même temps et ne révèle pas quels noms sont enregistrés
+pub fn dummy_password_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_password("compte inexistant").unwrap_or_default())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+impl UserStore {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            accounts: HashMap::new(),
+            failures: HashMap::new(),
+        }
+    }
+
+    // Charge les comptes (le fichier est créé au premier enregistrement)
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let accounts = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(path)?)
+                .map_err(|e| anyhow!("Fichier de comptes {} illisible: {}", path.display(), e))?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            accounts,
+            failures: HashMap::new(),
+        })
+    }
+
+    // Écrit dans un fichier temporaire puis renomme : jamais de fichier à moitié écrit
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let temporaire = path.with_extension("tmp");
+        fs::write(&temporaire, serde_json::to_vec_pretty(&self.accounts)?)?;
+        fs::rename(&temporaire, path)?;
+        Ok(())
+    }
+
+    pub fn contains(&self, username: &str) -> bool {
+        self.accounts.contains_key(username)
+    }
+
+    pub fn register(&mut self, username: &str, password_hash: String) -> Result<()> {
+        if self.contains(username) {
+            return Err(anyhow!("Le nom '{}' est déjà enregistré", username));
+        }
+        self.accounts.insert(username.to_string(), Account {
+            password_hash,
+            created_at: unix_timestamp(),
+        });
+        if let Err(e) = self.save() {
+            self.accounts.remove(username);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn password_hash(&self, username: &str) -> Option<String> {
+        self.accounts.get(username).map(|account| account.password_hash.clone())
+    }
+
+    // Temps restant si le compte est verrouillé
+    pub fn locked_for(&self, username: &str) -> Option<Duration> {
+        let locked_until = self.failures.get(username)?.locked_until?;
+        locked_until.checked_duration_since(Instant::now())
+    }
+
+    // Compte un échec ; renvoie le nombre d'essais restants avant verrouillage (0 = verrouillé).
+    // Seuls les comptes existants sont suivis : la table reste bornée par leur nombre
+    pub fn record_failure(&mut self, username: &str) -> u32 {
+        if !self.contains(username) {
+            return MAX_FAILED_LOGINS;
+        }
+        let failures = self.failures.entry(username.to_string()).or_default();
+        // Un verrouillage expiré repart de zéro
+        if failures.locked_until.is_some_and(|fin| fin <= Instant::now()) {
+            *failures = Failures::default();
+        }
+
+        failures.count += 1;
+        if failures.count >= MAX_FAILED_LOGINS {
+            failures.locked_until = Some(Instant::now() + LOCKOUT_DURATION);
+            return 0;
+        }
+        MAX_FAILED_LOGINS - failures.count
+    }
+
+    pub fn record_success(&mut self, username: &str) {
+        self.failures.remove(username);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accounts_persist_with_hashed_passwords() {
+        let path = std::env::temp_dir().join(format!("restychat_users_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        {
+            let mut store = UserStore::open(&path).unwrap();
+            store.register("alice", hash_password("correct horse").unwrap()).unwrap();
+            assert!(store.register("alice", hash_password("autre").unwrap()).is_err());
+        }
+
+        let contenu = fs::read_to_string(&path).unwrap();
+        assert!(!contenu.contains("correct horse"));
+
+        let store = UserStore::open(&path).unwrap();
+        let hash = store.password_hash("alice").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("correct horsE", &hash));
+        assert!(store.password_hash("bob").is_none());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_lockout_after_repeated_failures() {
+        let mut store = UserStore::in_memory();
+        store.register("alice", "hash".to_string()).unwrap();
+        for restants in (1..MAX_FAILED_LOGINS).rev() {
+            assert_eq!(store.record_failure("alice"), restants);
+            assert!(store.locked_for("alice").is_none());
+        }
+        assert_eq!(store.record_failure("alice"), 0);
+        assert!(store.locked_for("alice").is_some_and(|reste| reste <= LOCKOUT_DURATION));
+
+        store.record_success("alice");
+        assert!(store.locked_for("alice").is_none());
+
+        // Un nom sans compte n'est ni suivi ni verrouillable
+        for _ in 0..MAX_FAILED_LOGINS {
+            assert_eq!(store.record_failure("personne"), MAX_FAILED_LOGINS);
+        }
+        assert!(store.locked_for("personne").is_none());
+        assert!(store.failures.is_empty());
+    }
+
+    #[test]
+    fn test_username_rules() {
+        assert!(is_valid_username("alice_42"));
+        assert!(!is_valid_username(""));
+        assert!(!is_valid_username("avec espace"));
+        assert!(!is_valid_username(&"x".repeat(33)));
+    }
+}
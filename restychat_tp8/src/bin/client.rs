@@ -1,24 +1,106 @@
+use clap::Parser;
 use restychat_tp8::client::ChatClient;
+use restychat_tp8::config::{ClientProfile, Theme};
+use restychat_tp8::tls::ClientTlsConfig;
+use restychat_tp8::HeartbeatConfig;
+use std::io::IsTerminal;
+use std::path::PathBuf;
 use tracing::Level;
-use tracing_subscriber;
+
+// Chaque réglage : option de la ligne de commande (ou variable d'environnement),
+// sinon profil, sinon valeur par défaut
+#[derive(Parser)]
+#[command(name = "client", version, about = "Client de chat RustChat")]
+struct Args {
+    /// Adresse du serveur [défaut : celle du profil, sinon 127.0.0.1:8080]
+    server: Option<String>,
+
+    /// Profil TOML (serveur, nom, thème) [défaut : ~/.config/rustchat/profil.toml]
+    #[arg(short, long, env = "RUSTCHAT_PROFILE")]
+    profile: Option<PathBuf>,
+
+    /// Nom proposé à l'identification
+    #[arg(short, long)]
+    username: Option<String>,
+
+    /// Couleurs de l'interface : dark, light ou mono
+    #[arg(long)]
+    theme: Option<Theme>,
+
+    /// Console simple plutôt que l'interface plein écran
+    #[arg(long, env = "RUSTCHAT_NO_TUI")]
+    no_tui: bool,
+
+    /// Dossier des fichiers reçus [défaut : fichiers_recus]
+    #[arg(long, env = "RUSTCHAT_DOWNLOAD_DIR")]
+    download_dir: Option<PathBuf>,
+
+    /// CA de confiance (PEM) ; active TLS
+    #[arg(long, env = "RUSTCHAT_TLS_CA")]
+    tls_ca: Option<PathBuf>,
+
+    /// Nom attendu dans le certificat du serveur [défaut : l'hôte de l'adresse]
+    #[arg(long, env = "RUSTCHAT_TLS_SERVER_NAME")]
+    tls_server_name: Option<String>,
+
+    /// Certificat client (TLS mutuel), avec --tls-key
+    #[arg(long, env = "RUSTCHAT_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// Clé privée du certificat client
+    #[arg(long, env = "RUSTCHAT_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Configuration des logs (niveau WARN pour moins de verbosité côté client)
-    tracing_subscriber::fmt()
-        .with_max_level(Level::WARN)
-        .init();
-
-    // Adresse du serveur par défaut
-    let server_addr = std::env::args()
-        .nth(1)
+    let args = Args::parse();
+    let profile = match args.profile.clone().or_else(ClientProfile::default_path) {
+        Some(path) => ClientProfile::load_or_default(path)?,
+        None => ClientProfile::default(),
+    };
+
+    // Interface plein écran dans un terminal, sauf avec --no-tui (ou une entrée redirigée)
+    let tui = std::io::stdin().is_terminal() && std::io::stdout().is_terminal() && !args.no_tui;
+
+    // Configuration des logs (niveau WARN pour moins de verbosité côté client) ;
+    // ils casseraient l'affichage de l'interface plein écran
+    let logs = tracing_subscriber::fmt().with_max_level(Level::WARN);
+    if tui {
+        logs.with_writer(std::io::sink).init();
+    } else {
+        logs.init();
+    }
+
+    let server_addr = args.server
+        .or(profile.server)
         .unwrap_or_else(|| "127.0.0.1:8080".to_string());
 
     println!("🌟 Client RustChat");
     println!("🔗 Connexion à: {}", server_addr);
     println!();
 
-    let mut client = ChatClient::new();
+    // TLS activé si une CA de confiance est fournie
+    let mut client = match args.tls_ca {
+        Some(ca_path) => {
+            println!("🔒 TLS (CA: {})", ca_path.display());
+            ChatClient::with_tls(ClientTlsConfig {
+                ca_path,
+                server_name: args.tls_server_name,
+                cert_path: args.tls_cert,
+                key_path: args.tls_key,
+            })
+        }
+        None => ChatClient::new(),
+    };
+    client.set_heartbeat(HeartbeatConfig::from_env());
+    client.set_tui(tui);
+    client.set_theme(args.theme.or(profile.theme).unwrap_or_default());
+    client.set_username(args.username.or(profile.username));
+    // Dossier des fichiers reçus (/accept)
+    if let Some(dir) = args.download_dir {
+        client.set_download_dir(dir);
+    }
 
     if let Err(e) = client.connect(&server_addr).await {
         eprintln!("❌ Erreur de connexion: {}", e);
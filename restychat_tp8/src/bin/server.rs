@@ -1,25 +1,173 @@
-use restychat_tp8::server::ChatServer;
+use clap::Parser;
+use restychat_tp8::config::ServerFile;
+use restychat_tp8::queue::QueueConfig;
+use restychat_tp8::ratelimit::RateLimitConfig;
+use restychat_tp8::server::{ChatServer, ServerConfig};
+use restychat_tp8::tls::ServerTlsConfig;
+use restychat_tp8::HeartbeatConfig;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use tracing::Level;
-use tracing_subscriber;
+
+// Chaque réglage : option de la ligne de commande (ou variable d'environnement),
+// sinon fichier de configuration, sinon valeur par défaut
+#[derive(Parser)]
+#[command(name = "server", version, about = "Serveur de chat RustChat")]
+struct Args {
+    /// Adresse d'écoute [défaut : 127.0.0.1:8080]
+    bind: Option<String>,
+
+    /// Fichier de configuration TOML
+    #[arg(short, long, env = "RUSTCHAT_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Connexions simultanées au plus [défaut : pas de limite]
+    #[arg(long)]
+    max_clients: Option<usize>,
+
+    /// Taille maximale d'un message de chat, en octets [défaut : pas de limite]
+    #[arg(long)]
+    max_message_size: Option<usize>,
+
+    /// Historique des messages publics [défaut : historique.jsonl]
+    #[arg(long)]
+    history: Option<PathBuf>,
+
+    /// Comptes utilisateurs (mots de passe hachés) [défaut : comptes.json]
+    #[arg(long)]
+    accounts: Option<PathBuf>,
+
+    /// Messages privés en attente des utilisateurs hors ligne [défaut : messages_en_attente.json]
+    #[arg(long)]
+    offline: Option<PathBuf>,
+
+    /// Bannissements par nom ou par adresse IP [défaut : bannis.json]
+    #[arg(long)]
+    bans: Option<PathBuf>,
+
+    /// Opérateurs, séparés par des virgules
+    #[arg(long, env = "RUSTCHAT_OPERATORS", value_delimiter = ',')]
+    operators: Option<Vec<String>>,
+
+    /// Certificat TLS (PEM) ; TLS activé avec --tls-key
+    #[arg(long, env = "RUSTCHAT_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// Clé privée TLS (PEM)
+    #[arg(long, env = "RUSTCHAT_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// CA des certificats clients exigés (TLS mutuel)
+    #[arg(long, env = "RUSTCHAT_TLS_CLIENT_CA")]
+    tls_client_ca: Option<PathBuf>,
+
+    /// Message du jour, envoyé après l'identification
+    #[arg(long)]
+    motd: Option<String>,
+
+    /// Niveau des logs : error, warn, info, debug ou trace [défaut : info]
+    #[arg(long, env = "RUSTCHAT_LOG")]
+    log_level: Option<Level>,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let fichier = match &args.config {
+        Some(path) => ServerFile::load(path)?,
+        None => ServerFile::default(),
+    };
+
     // Configuration des logs
+    let log_level = match (args.log_level, &fichier.log_level) {
+        (Some(level), _) => level,
+        (None, Some(niveau)) => niveau.parse().map_err(|_| format!("Niveau de logs inconnu '{}' (error, warn, info, debug ou trace)", niveau))?,
+        (None, None) => Level::INFO,
+    };
     tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
+        .with_max_level(log_level)
         .init();
 
-    // Adresse d'écoute par défaut
-    let addr = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let addr = args.bind.or(fichier.bind).unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let history_path = args.history.or(fichier.history_path).unwrap_or_else(|| "historique.jsonl".into());
+    let accounts_path = args.accounts.or(fichier.accounts_path).unwrap_or_else(|| "comptes.json".into());
+    let offline_path = args.offline.or(fichier.offline_path).unwrap_or_else(|| "messages_en_attente.json".into());
+    let bans_path = args.bans.or(fichier.bans_path).unwrap_or_else(|| "bannis.json".into());
+    let max_clients = args.max_clients.or(fichier.max_clients);
+    let max_message_size = args.max_message_size.or(fichier.max_message_size);
+    let motd = args.motd.or(fichier.motd).filter(|motd| !motd.trim().is_empty());
+
+    let operators: HashSet<String> = args.operators
+        .or(fichier.operators)
+        .unwrap_or_default()
+        .iter()
+        .map(|nom| nom.trim().to_string())
+        .filter(|nom| !nom.is_empty())
+        .collect();
+
+    // TLS activé si le certificat et la clé sont fournis
+    let tls = match (args.tls_cert, args.tls_key, fichier.tls) {
+        (Some(cert_path), Some(key_path), _) => Some(ServerTlsConfig { cert_path, key_path, client_ca_path: args.tls_client_ca }),
+        (_, _, Some(tls)) => Some(ServerTlsConfig {
+            cert_path: tls.cert,
+            key_path: tls.key,
+            client_ca_path: args.tls_client_ca.or(tls.client_ca),
+        }),
+        _ => None,
+    };
 
     println!("🚀 Démarrage du serveur RustChat...");
+    if let Some(config) = &args.config {
+        println!("⚙️ Configuration: {}", config.display());
+    }
     println!("📡 Écoute sur: {}", addr);
+    println!("📜 Historique: {}", history_path.display());
+    println!("👤 Comptes: {}", accounts_path.display());
+    println!("📭 Messages en attente: {}", offline_path.display());
+    println!("🚫 Bannissements: {}", bans_path.display());
+    if !operators.is_empty() {
+        let mut noms: Vec<&str> = operators.iter().map(String::as_str).collect();
+        noms.sort();
+        println!("🛡 Opérateurs: {}", noms.join(", "));
+    }
+    if let Some(tls) = &tls {
+        println!("🔒 TLS: {}", tls.cert_path.display());
+    }
+    if let Some(max) = max_clients {
+        println!("🈵 {} connexions au plus", max);
+    }
+    if let Some(max) = max_message_size {
+        println!("✂️ Messages de {} octets au plus", max);
+    }
+    if let Some(motd) = &motd {
+        println!("📢 Message du jour: {}", motd);
+    }
+    let heartbeat = HeartbeatConfig::from_env();
+    println!("💓 Ping toutes les {} s, déconnexion après {} sans réponse", heartbeat.interval.as_secs(), heartbeat.max_missed);
+    let queue = QueueConfig::from_env();
+    println!("📦 File d'envoi: {} messages par client, politique {:?}", queue.capacity, queue.policy);
+    let rate_limits = RateLimitConfig::default();
+    if let Some(rate) = rate_limits.messages_per_client {
+        println!("🚧 Anti-flood: rafale de {} messages puis {}/s par client", rate.burst, rate.per_second);
+    }
     println!("🔧 Appuyez sur Ctrl+C pour arrêter");
     println!();
 
-    let server = ChatServer::new();
+    let config = ServerConfig {
+        history_path: Some(history_path),
+        accounts_path: Some(accounts_path),
+        offline_path: Some(offline_path),
+        bans_path: Some(bans_path),
+        operators,
+        tls,
+        heartbeat,
+        queue,
+        rate_limits,
+        max_clients,
+        max_message_size,
+        motd,
+    };
+    let server = ChatServer::with_config(config)?;
     server.start(&addr).await?;
 
     Ok(())
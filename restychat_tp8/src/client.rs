@@ -1,186 +1,971 @@
-use crate::{ClientMessage, ClientState, ProtocolMessage, ServerMessage};
-use anyhow::Result;
-use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+use crate::config::Theme;
+use crate::tls::ClientTlsConfig;
+use crate::transfer::{
+    IncomingFile, crc32, is_valid_file_name, is_valid_sha256, read_chunk, sha256_file,
+};
+use crate::tui::{Tui, UiEvent, UiSender};
+use crate::{
+    CAP_COMPRESSION, CAP_HISTORY, CAP_MSGPACK, CAP_ROOMS, ClientMessage, ClientState, DEFAULT_ROOM,
+    DeliveryStatus, Encoding, HeartbeatConfig, HistoryEntry, ModerationAction, PROTOCOL_VERSION,
+    ProtocolMessage, ServerMessage, is_valid_room_name,
+};
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader, Stdin, stdin};
 use tokio::net::TcpStream;
-use tokio::sync::mpsc;
-use tracing::{error, info};
+use tokio::sync::{Notify, mpsc};
+use tracing::{error, info, warn};
 
+// Délai avant une tentative de reconnexion, doublé à chaque échec
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
+
+// Dossier des fichiers reçus par défaut
+pub const DEFAULT_DOWNLOAD_DIR: &str = "fichiers_recus";
+
+#[derive(Default)]
 pub struct ChatClient {
+    tls: Option<ClientTlsConfig>, // None : TCP en clair
+    heartbeat: HeartbeatConfig,
+    download_dir: PathBuf,
+    tui: bool, // interface plein écran plutôt que la console
+    theme: Theme,
+    username: Option<String>, // nom proposé à la première identification (profil)
+}
+
+// Fin d'une connexion
+enum Fin {
+    Quit, // demandée par l'utilisateur
+    Lost, // coupure : on tente de se reconnecter
+}
+
+// État partagé entre la saisie et la réception, conservé entre les connexions
+struct Session {
+    read_receipts: AtomicBool, // envoyer un accusé de lecture pour chaque message privé affiché
+    outbox: Mutex<Outbox>,
+    token: Mutex<Option<String>>, // jeton du dernier ConnectResponse réussi
+    resuming: AtomicBool,         // Resume envoyé, réponse attendue
+    last_seen_id: AtomicU64,      // plus grand id de message public reçu (0 : aucun)
+    username: Mutex<Option<String>>, // dernier nom saisi, proposé par défaut
+    relogin: Notify,              // le serveur a refusé l'identification : la saisie la redemande
+    kicked: AtomicBool,           // expulsé ou banni par un opérateur : pas de reconnexion
+    transfers: Mutex<Transfers>,
+    download_dir: PathBuf,
+    ui: Option<UiSender>,         // None : affichage dans la console
+    silent_user_list: AtomicBool, // UserList demandé pour la liste latérale, pas à afficher
+}
+
+impl Session {
+    // Une ligne pour l'utilisateur : dans la console, ou dans le panneau de messages
+    fn print(&self, line: impl Into<String>) {
+        match &self.ui {
+            Some(ui) => ui.send(UiEvent::Line(line.into())),
+            None => println!("{}", line.into()),
+        }
+    }
+
+    fn ui(&self, event: UiEvent) {
+        if let Some(ui) = &self.ui {
+            ui.send(event);
+        }
+    }
+}
+
+// D'où viennent les lignes tapées : l'entrée standard, ou la ligne de saisie de l'interface
+enum Input {
+    Stdin(BufReader<Stdin>),
+    Tui(mpsc::UnboundedReceiver<String>),
+}
+
+impl Input {
+    // None à la fin de la saisie
+    async fn read_line(&mut self) -> Option<String> {
+        match self {
+            Input::Stdin(reader) => {
+                let mut line = String::new();
+                match reader.read_line(&mut line).await {
+                    Ok(0) | Err(_) => None,
+                    Ok(_) => Some(line),
+                }
+            },
+            Input::Tui(lines) => lines.recv().await,
+        }
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            read_receipts: AtomicBool::new(true),
+            outbox: Mutex::new(Outbox::default()),
+            token: Mutex::new(None),
+            resuming: AtomicBool::new(false),
+            last_seen_id: AtomicU64::new(0),
+            username: Mutex::new(None),
+            relogin: Notify::new(),
+            kicked: AtomicBool::new(false),
+            transfers: Mutex::new(Transfers::default()),
+            download_dir: PathBuf::from(DEFAULT_DOWNLOAD_DIR),
+            ui: None,
+            silent_user_list: AtomicBool::new(false),
+        }
+    }
+}
+
+// Messages privés envoyés, suivis jusqu'à leur lecture
+#[derive(Default)]
+struct Outbox {
+    next_ref: u64,
+    pending: HashMap<u64, String>, // client_ref -> aperçu, en attente de l'Ack
+    sent: HashMap<u64, String>,    // id attribué par le serveur -> aperçu
+}
+
+impl Outbox {
+    fn track(&mut self, apercu: String) -> u64 {
+        self.next_ref += 1;
+        self.pending.insert(self.next_ref, apercu);
+        self.next_ref
+    }
+
+    fn acked(&mut self, client_ref: u64, id: u64) -> Option<String> {
+        let apercu = self.pending.remove(&client_ref)?;
+        self.sent.insert(id, apercu.clone());
+        Some(apercu)
+    }
+}
+
+// Fichier proposé par ce client
+struct Outgoing {
+    to: String,
+    path: PathBuf,
+    name: String,
+    size: u64,
+}
+
+// Fichier proposé par un autre utilisateur, en attente de /accept ou /reject
+struct IncomingOffer {
+    from: String,
+    name: String,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Default)]
+struct Transfers {
+    next_ref: u64,
+    offering: HashMap<u64, Outgoing>,     // client_ref -> fichier, en attente du numéro de transfert
+    outgoing: HashMap<u64, Outgoing>,     // transfer_id -> fichier proposé ou en cours d'envoi
+    offers: HashMap<u64, IncomingOffer>,  // transfer_id -> offre reçue
+    incoming: HashMap<u64, (String, IncomingFile)>, // transfer_id -> (expéditeur, fichier en cours de réception)
 }
 
 impl ChatClient {
     pub fn new() -> Self {
         Self {
+            tls: None,
+            heartbeat: HeartbeatConfig::default(),
+            download_dir: PathBuf::from(DEFAULT_DOWNLOAD_DIR),
+            tui: false,
+            theme: Theme::default(),
+            username: None,
+        }
+    }
+
+    pub fn with_tls(tls: ClientTlsConfig) -> Self {
+        Self {
+            tls: Some(tls),
+            ..Self::new()
         }
     }
 
-    async fn handle_server_message(msg: &ProtocolMessage, client_state: &mut ClientState, tx: &mpsc::Sender<ClientMessage>) -> Result<bool> {
-        let server_msg = ServerMessage::from_protocol_message(msg)?;
+    pub fn set_download_dir(&mut self, dir: PathBuf) {
+        self.download_dir = dir;
+    }
+
+    pub fn set_heartbeat(&mut self, heartbeat: HeartbeatConfig) {
+        self.heartbeat = heartbeat;
+    }
+
+    // Interface plein écran (le terminal doit en être un)
+    pub fn set_tui(&mut self, tui: bool) {
+        self.tui = tui;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn set_username(&mut self, username: Option<String>) {
+        self.username = username;
+    }
 
+    async fn handle_server_message(
+        server_msg: ServerMessage,
+        client_state: &mut ClientState,
+        tx: &mpsc::Sender<ClientMessage>,
+        session: &Session,
+    ) -> Result<bool> {
         match server_msg {
-            ServerMessage::ConnectResponse { success, message } => {
+            ServerMessage::Welcome { version, capabilities } => {
+                info!("🤝 Protocole v{} (capacités: {})", version, capabilities.join(", "));
+            },
+            ServerMessage::Ping => {
+                let _ = tx.send(ClientMessage::Pong).await;
+            },
+            ServerMessage::Pong => {},
+            ServerMessage::ConnectResponse { success, message, session_token } => {
+                let reprise = session.resuming.swap(false, Ordering::Relaxed);
                 if success {
-                    println!("✅ {}", message);
-                    println!("📋 Commandes disponibles:");
-                    println!("  /msg <utilisateur> <message> - Envoyer un message privé");
-                    println!("  /users - Lister les utilisateurs connectés");
-                    println!("  /quit - Quitter");
-                    println!("  Tapez simplement votre message pour l'envoyer à tous");
-                    println!();
+                    *session.token.lock().unwrap() = session_token;
+                    // La liste latérale part de la liste complète, tenue à jour ensuite par les arrivées et départs
+                    if session.ui.is_some() {
+                        session.ui(UiEvent::Identity(session.username.lock().unwrap().clone()));
+                        session.ui(UiEvent::RoomJoined(DEFAULT_ROOM.to_string()));
+                        session.silent_user_list.store(true, Ordering::Relaxed);
+                        let _ = tx.send(ClientMessage::ListUsers).await;
+                    }
+                    if reprise {
+                        session.print("🔁 Reconnecté, session reprise");
+                        return Ok(true);
+                    }
+                    session.print(format!("✅ {}", message));
+                    session.print("📋 Commandes disponibles:");
+                    session.print("  /msg <utilisateur> <message> - Envoyer un message privé");
+                    session.print("  /users - Lister les utilisateurs connectés");
+                    session.print("  /join #salon - Rejoindre un salon (et en faire le salon courant)");
+                    session.print("  /leave [#salon] - Quitter un salon");
+                    session.print("  /rooms - Lister les salons");
+                    session.print("  /history [n] - Afficher les derniers messages du salon courant");
+                    session.print("  /receipts on|off - Envoyer ou non les accusés de lecture");
+                    session.print("  /send <utilisateur> <chemin> - Proposer un fichier");
+                    session.print("  /accept <n> | /reject <n> | /cancel <n> - Répondre à une offre, interrompre un transfert");
+                    session.print("  /quit - Quitter");
+                    session.print(format!("  Tapez simplement votre message pour l'envoyer au salon courant ({})", DEFAULT_ROOM));
+                    if session.ui.is_some() {
+                        session.print("  Tab complète les noms, ↑/↓ rappellent les lignes tapées, PgUp/PgDn font défiler");
+                    }
+                    session.print("");
+                    // Rattrape ce qui a été dit avant notre arrivée
+                    let _ = tx.send(ClientMessage::History { limit: None, room: DEFAULT_ROOM.to_string() }).await;
                     return Ok(true);
                 } else {
-                    println!("❌ {}", message);
+                    session.print(format!("❌ {}", message));
+                    if reprise {
+                        *session.token.lock().unwrap() = None;
+                    }
+                    session.ui(UiEvent::Identity(None));
                     *client_state = ClientState::Disconnected;
-                    // Redemander le nom d'utilisateur
-                    Self::ask_username(tx, client_state).await;
+                    // Redemander les identifiants (seule la tâche de saisie lit stdin)
+                    session.relogin.notify_one();
                     return Ok(false);
                 }
             },
-            ServerMessage::MessageBroadcast { from, content } => {
-                println!("💬 {}: {}", from, content);
+            ServerMessage::MessageBroadcast { id, from, content, room, .. } => {
+                session.last_seen_id.fetch_max(id, Ordering::Relaxed);
+                session.print(format!("💬 [{}] {}: {}", room, from, content));
+            },
+            ServerMessage::PrivateMessageDelivery { id, timestamp, from, content, offline } => {
+                if offline {
+                    session.print(format!("📨 [Privé, reçu en votre absence, {}] {}: {}", Self::format_time(timestamp), from, content));
+                } else {
+                    session.print(format!("📨 [Privé] {}: {}", from, content));
+                }
+                if session.read_receipts.load(Ordering::Relaxed) {
+                    let _ = tx.send(ClientMessage::MarkRead { id }).await;
+                }
             },
-            ServerMessage::PrivateMessageDelivery { from, content } => {
-                println!("📨 [Privé] {}: {}", from, content);
+            ServerMessage::Ack { id, client_ref, .. } => {
+                // Les messages publics ne sont pas suivis : leur diffusion suffit
+                let apercu = client_ref.and_then(|client_ref| session.outbox.lock().unwrap().acked(client_ref, id));
+                if let Some(apercu) = apercu {
+                    session.print(format!("✓ Envoyé (#{}) → {}", id, apercu));
+                }
+            },
+            ServerMessage::PrivateMessageStatus { id, to, status } => {
+                let mut outbox = session.outbox.lock().unwrap();
+                match status {
+                    DeliveryStatus::Delivered => {
+                        let apercu = outbox.sent.get(&id).cloned().unwrap_or_else(|| to.clone());
+                        session.print(format!("✓✓ Remis (#{}) → {}", id, apercu));
+                    },
+                    DeliveryStatus::Queued => {
+                        session.print(format!("📭 En attente (#{}) : {} est hors ligne, remise à sa prochaine connexion", id, to));
+                    },
+                    DeliveryStatus::Read => {
+                        outbox.sent.remove(&id);
+                        session.print(format!("👁 Lu (#{}) par {}", id, to));
+                    },
+                }
             },
             ServerMessage::UserList { users } => {
-                println!("👥 Utilisateurs connectés ({}):", users.len());
+                session.ui(UiEvent::Users(users.clone()));
+                if session.silent_user_list.swap(false, Ordering::Relaxed) {
+                    return Ok(true);
+                }
+                session.print(format!("👥 Utilisateurs connectés ({}):", users.len()));
                 for user in users {
-                    println!("  - {}", user);
+                    session.print(format!("  - {}", user));
                 }
             },
             ServerMessage::UserJoined { username } => {
-                println!("👋 {} a rejoint le chat", username);
+                session.ui(UiEvent::UserJoined(username.clone()));
+                session.print(format!("👋 {} a rejoint le chat", username));
             },
             ServerMessage::UserLeft { username } => {
-                println!("👋 {} a quitté le chat", username);
+                session.ui(UiEvent::UserLeft(username.clone()));
+                session.print(format!("👋 {} a quitté le chat", username));
             },
             ServerMessage::Error { message } => {
-                println!("❌ Erreur: {}", message);
+                session.print(format!("❌ Erreur: {}", message));
+            },
+            ServerMessage::History { room, messages } => {
+                if let Some(dernier) = messages.iter().map(|entry| entry.id).max() {
+                    session.last_seen_id.fetch_max(dernier, Ordering::Relaxed);
+                }
+                if messages.is_empty() {
+                    session.print(format!("📜 Aucun message dans l'historique de {}", room));
+                } else {
+                    session.print(format!("📜 Historique de {} ({} messages):", room, messages.len()));
+                    for entry in &messages {
+                        session.print(format!("  {}", Self::format_history_entry(entry)));
+                    }
+                    session.print("📜 Fin de l'historique");
+                }
+            },
+            ServerMessage::RoomJoined { room, username } => {
+                if session.username.lock().unwrap().as_ref() == Some(&username) {
+                    session.ui(UiEvent::RoomJoined(room.clone()));
+                }
+                session.print(format!("🚪 {} a rejoint {}", username, room));
+            },
+            ServerMessage::RoomLeft { room, username } => {
+                if session.username.lock().unwrap().as_ref() == Some(&username) {
+                    session.ui(UiEvent::RoomLeft(room.clone()));
+                }
+                session.print(format!("🚪 {} a quitté {}", username, room));
+            },
+            ServerMessage::RoomList { rooms } => {
+                session.print(format!("🏠 Salons ({}):", rooms.len()));
+                for room in rooms {
+                    session.print(format!("  - {} ({} membres)", room.name, room.members));
+                }
+            },
+            ServerMessage::Moderation { action, target, by, reason, seconds } => {
+                let raison = reason.map(|reason| format!(" ({})", reason)).unwrap_or_default();
+                match action {
+                    ModerationAction::Kick => session.print(format!("👢 {} a été expulsé par {}{}", target, by, raison)),
+                    ModerationAction::Ban => session.print(format!("🚫 {} a été banni par {}{}", target, by, raison)),
+                    ModerationAction::Unban => session.print(format!("✅ {} n'est plus banni ({})", target, by)),
+                    ModerationAction::Mute => {
+                        let duree = Self::format_duration(seconds.unwrap_or(0));
+                        session.print(format!("🔇 {} est réduit au silence pour {} par {}{}", target, duree, by, raison));
+                    },
+                    ModerationAction::Unmute => session.print(format!("🔊 {} peut de nouveau parler ({})", target, by)),
+                }
+            },
+            ServerMessage::FileOfferSent { transfer_id, client_ref } => {
+                let mut transfers = session.transfers.lock().unwrap();
+                if let Some(outgoing) = transfers.offering.remove(&client_ref) {
+                    session.print(format!("📎 {} proposé à {} (transfert #{}), en attente de sa réponse", outgoing.name, outgoing.to, transfer_id));
+                    transfers.outgoing.insert(transfer_id, outgoing);
+                }
+            },
+            ServerMessage::FileOffered { transfer_id, from, name, size, sha256 } => {
+                if !is_valid_file_name(&name) || !is_valid_sha256(&sha256) {
+                    let _ = tx.send(ClientMessage::FileReject { transfer_id, reason: Some("Offre invalide".to_string()) }).await;
+                    return Ok(true);
+                }
+                session.print(format!("📎 {} vous propose {} ({}) : /accept {} ou /reject {}", from, name, Self::format_size(size), transfer_id, transfer_id));
+                session.transfers.lock().unwrap().offers.insert(transfer_id, IncomingOffer { from, name, size, sha256 });
+            },
+            ServerMessage::FileAccepted { transfer_id, offset } => {
+                let apercu = session.transfers.lock().unwrap().outgoing.get(&transfer_id).map(|outgoing| {
+                    (outgoing.name.clone(), outgoing.to.clone(), outgoing.size)
+                });
+                if let Some((name, to, size)) = apercu {
+                    if offset > 0 {
+                        session.print(format!("📤 Envoi de {} à {} : reprise à {}%", name, to, offset * 100 / size.max(1)));
+                    } else {
+                        session.print(format!("📤 Envoi de {} à {}...", name, to));
+                    }
+                    Self::send_chunk(tx, session, transfer_id, offset).await;
+                }
+            },
+            ServerMessage::FileAcked { transfer_id, next_offset } => {
+                Self::send_chunk(tx, session, transfer_id, next_offset).await;
+            },
+            ServerMessage::FileChunk { transfer_id, offset, data, crc32 } => {
+                Self::receive_chunk(tx, session, transfer_id, offset, &data, crc32).await;
+            },
+            ServerMessage::FileRejected { transfer_id, reason } => {
+                if let Some(outgoing) = session.transfers.lock().unwrap().outgoing.remove(&transfer_id) {
+                    let raison = reason.map(|reason| format!(" ({})", reason)).unwrap_or_default();
+                    session.print(format!("🚫 {} a refusé {}{}", outgoing.to, outgoing.name, raison));
+                }
+            },
+            ServerMessage::FileDone { transfer_id, ok } => {
+                if let Some(outgoing) = session.transfers.lock().unwrap().outgoing.remove(&transfer_id) {
+                    if ok {
+                        session.print(format!("✅ {} bien reçu par {}", outgoing.name, outgoing.to));
+                    } else {
+                        session.print(format!("❌ {} est arrivé abîmé chez {} (empreinte différente), renvoyez-le", outgoing.name, outgoing.to));
+                    }
+                }
+            },
+            ServerMessage::FileCancelled { transfer_id, reason } => {
+                let raison = reason.map(|reason| format!(" ({})", reason)).unwrap_or_default();
+                let mut transfers = session.transfers.lock().unwrap();
+                transfers.offers.remove(&transfer_id);
+                if let Some(outgoing) = transfers.outgoing.remove(&transfer_id) {
+                    session.print(format!("🚫 Envoi de {} annulé{} : un nouveau /send reprendra où il s'est arrêté", outgoing.name, raison));
+                } else if let Some((_, incoming)) = transfers.incoming.remove(&transfer_id) {
+                    session.print(format!("🚫 Réception de {} annulée{} : la partie reçue est gardée pour une reprise", incoming.name, raison));
+                } else {
+                    session.print(format!("🚫 Transfert #{} annulé{}", transfer_id, raison));
+                }
+            },
+            ServerMessage::Motd { message } => {
+                for ligne in message.lines() {
+                    session.print(format!("📢 {}", ligne));
+                }
+            },
+            ServerMessage::Kicked { by, reason, banned } => {
+                let raison = reason.map(|reason| format!(" ({})", reason)).unwrap_or_default();
+                if banned {
+                    session.print(format!("🚫 Vous avez été banni par {}{}", by, raison));
+                } else {
+                    session.print(format!("👢 Vous avez été expulsé par {}{}", by, raison));
+                }
+                // Le serveur a invalidé la session : rien à reprendre
+                *session.token.lock().unwrap() = None;
+                session.kicked.store(true, Ordering::Relaxed);
             },
         }
         Ok(true)
     }
 
-    async fn ask_username(tx: &mpsc::Sender<ClientMessage>, client_state: &mut ClientState) {
-        use tokio::io::{stdin, AsyncBufReadExt, BufReader};
-        let stdin = stdin();
-        let mut reader = BufReader::new(stdin);
-        let mut line = String::new();
-        loop {
-            println!("👤 Entrez votre nom d'utilisateur:");
-            line.clear();
-            if reader.read_line(&mut line).await.is_err() {
+    // "[14:03] alice: bonjour" (heure UTC)
+    fn format_history_entry(entry: &HistoryEntry) -> String {
+        format!("[{}] {}: {}", Self::format_time(entry.timestamp), entry.from, entry.content)
+    }
+
+    // "14:03" (heure UTC)
+    fn format_time(timestamp: u64) -> String {
+        let secondes_du_jour = timestamp % 86400;
+        format!("{:02}:{:02}", secondes_du_jour / 3600, secondes_du_jour % 3600 / 60)
+    }
+
+    // Envoie le bloc qui commence à `offset` ; rien une fois le fichier entier envoyé
+    async fn send_chunk(tx: &mpsc::Sender<ClientMessage>, session: &Session, transfer_id: u64, offset: u64) {
+        let Some((path, size)) = session.transfers.lock().unwrap().outgoing.get(&transfer_id).map(|outgoing| (outgoing.path.clone(), outgoing.size)) else {
+            return;
+        };
+        if offset >= size {
+            return;
+        }
+        let msg = match read_chunk(&path, offset) {
+            Ok(data) if !data.is_empty() => ClientMessage::FileChunk { transfer_id, offset, crc32: crc32(&data), data },
+            Ok(_) | Err(_) => {
+                session.print(format!("❌ Lecture de {} impossible, transfert annulé", path.display()));
+                session.transfers.lock().unwrap().outgoing.remove(&transfer_id);
+                ClientMessage::FileCancel { transfer_id, reason: Some("Fichier illisible chez l'expéditeur".to_string()) }
+            },
+        };
+        let _ = tx.send(msg).await;
+    }
+
+    // Écrit le bloc reçu et l'acquitte ; le dernier déclenche la vérification du fichier entier
+    async fn receive_chunk(tx: &mpsc::Sender<ClientMessage>, session: &Session, transfer_id: u64, offset: u64, data: &[u8], checksum: u32) {
+        let resultat = {
+            let mut transfers = session.transfers.lock().unwrap();
+            let Some((_, incoming)) = transfers.incoming.get_mut(&transfer_id) else {
+                return;
+            };
+            match incoming.write_chunk(offset, data, checksum) {
+                Ok(_) if incoming.is_complete() => Ok(transfers.incoming.remove(&transfer_id)),
+                Ok(next_offset) => Err(Ok(next_offset)),
+                Err(e) => {
+                    transfers.incoming.remove(&transfer_id);
+                    Err(Err(e))
+                },
+            }
+        };
+
+        let (from, incoming) = match resultat {
+            Ok(Some(termine)) => termine,
+            Ok(None) => return,
+            Err(Ok(next_offset)) => {
+                let _ = tx.send(ClientMessage::FileAck { transfer_id, next_offset }).await;
+                return;
+            },
+            Err(Err(e)) => {
+                session.print(format!("❌ Écriture du fichier reçu impossible: {}", e));
+                let _ = tx.send(ClientMessage::FileCancel { transfer_id, reason: Some("Écriture impossible chez le destinataire".to_string()) }).await;
                 return;
+            },
+        };
+
+        // Le SHA-256 relit tout le fichier : hors du runtime async
+        let name = incoming.name.clone();
+        let ok = match tokio::task::spawn_blocking(move || incoming.finish()).await {
+            Ok(Ok(chemin)) => {
+                session.print(format!("✅ {} reçu de {} : {}", name, from, chemin.display()));
+                true
+            },
+            Ok(Err(e)) => {
+                session.print(format!("❌ {} reçu de {} est abîmé ({}), il a été supprimé", name, from, e));
+                false
+            },
+            Err(_) => false,
+        };
+        let _ = tx.send(ClientMessage::FileDone { transfer_id, ok }).await;
+    }
+
+    // Prépare l'offre de /send <utilisateur> <chemin> : taille et empreinte du fichier
+    async fn prepare_file_offer(input: &str, session: &Session) -> Option<ClientMessage> {
+        let mut parts = input.splitn(3, ' ').skip(1);
+        let (Some(to), Some(path)) = (parts.next(), parts.next()) else {
+            session.print("❌ Usage: /send <utilisateur> <chemin du fichier>");
+            return None;
+        };
+        let path = PathBuf::from(path.trim());
+        let Some(name) = path.file_name().and_then(|name| name.to_str()).filter(|name| is_valid_file_name(name)) else {
+            session.print(format!("❌ {} : nom de fichier invalide", path.display()));
+            return None;
+        };
+        let name = name.to_string();
+
+        let chemin = path.clone();
+        let empreinte = tokio::task::spawn_blocking(move || {
+            let size = std::fs::metadata(&chemin)?.len();
+            sha256_file(&chemin).map(|sha256| (size, sha256))
+        })
+        .await;
+        let (size, sha256) = match empreinte {
+            Ok(Ok(empreinte)) => empreinte,
+            Ok(Err(e)) => {
+                session.print(format!("❌ {} : {}", path.display(), e));
+                return None;
+            },
+            Err(e) => {
+                session.print(format!("❌ {} : {}", path.display(), e));
+                return None;
+            },
+        };
+
+        let mut transfers = session.transfers.lock().unwrap();
+        transfers.next_ref += 1;
+        let client_ref = transfers.next_ref;
+        transfers.offering.insert(client_ref, Outgoing { to: to.to_string(), path, name: name.clone(), size });
+        Some(ClientMessage::FileOffer { to: to.to_string(), name, size, sha256, client_ref })
+    }
+
+    // /accept : reprend un fichier partiel de la même offre s'il y en a un
+    fn accept_file(transfer_id: u64, session: &Session, download_dir: &Path) -> Option<ClientMessage> {
+        let mut transfers = session.transfers.lock().unwrap();
+        let Some(offer) = transfers.offers.remove(&transfer_id) else {
+            session.print(format!("❌ Aucune offre de fichier #{}", transfer_id));
+            return None;
+        };
+        match IncomingFile::open(download_dir, &offer.name, offer.size, &offer.sha256) {
+            Ok(incoming) => {
+                let offset = incoming.received();
+                if offset > 0 {
+                    session.print(format!("📥 Réception de {} : reprise à {}%", offer.name, offset * 100 / offer.size.max(1)));
+                } else {
+                    session.print(format!("📥 Réception de {}...", offer.name));
+                }
+                transfers.incoming.insert(transfer_id, (offer.from, incoming));
+                Some(ClientMessage::FileAccept { transfer_id, offset })
+            },
+            Err(e) => {
+                session.print(format!("❌ Impossible de préparer {} dans {}: {}", offer.name, download_dir.display(), e));
+                Some(ClientMessage::FileReject { transfer_id, reason: Some("Écriture impossible chez le destinataire".to_string()) })
+            },
+        }
+    }
+
+    // "512 o", "12.3 Ko", "4.1 Mo"
+    fn format_size(size: u64) -> String {
+        match size {
+            s if s < 1024 => format!("{} o", s),
+            s if s < 1024 * 1024 => format!("{:.1} Ko", s as f64 / 1024.0),
+            s => format!("{:.1} Mo", s as f64 / (1024.0 * 1024.0)),
+        }
+    }
+
+    // "90 s", "10 min", "2 h"
+    fn format_duration(seconds: u64) -> String {
+        match seconds {
+            s if s >= 3600 && s % 3600 == 0 => format!("{} h", s / 3600),
+            s if s >= 60 && s % 60 == 0 => format!("{} min", s / 60),
+            s => format!("{} s", s),
+        }
+    }
+
+    // "30s", "10m", "2h", "1j" ou un nombre de secondes
+    fn parse_duration(duree: &str) -> Option<u64> {
+        let (nombre, unite) = match duree.find(|c: char| !c.is_ascii_digit()) {
+            Some(i) => duree.split_at(i),
+            None => (duree, "s"),
+        };
+        let facteur = match unite {
+            "s" => 1,
+            "m" | "min" => 60,
+            "h" => 3600,
+            "j" | "d" => 86400,
+            _ => return None,
+        };
+        nombre.parse::<u64>().ok().filter(|n| *n > 0)?.checked_mul(facteur)
+    }
+
+    // Faux si la saisie est terminée
+    async fn ask_credentials(
+        tx: &mpsc::Sender<ClientMessage>,
+        client_state: &mut ClientState,
+        input: &mut Input,
+        session: &Session,
+    ) -> bool {
+        let mut register = false;
+        loop {
+            let precedent = session.username.lock().unwrap().clone();
+            match (&precedent, register) {
+                (_, true) => session.print("🆕 Nom du nouveau compte:"),
+                (Some(nom), false) => session.print(format!("👤 Nom d'utilisateur [{}] (ou /register pour créer un compte):", nom)),
+                (None, false) => session.print("👤 Entrez votre nom d'utilisateur (ou /register pour créer un compte):"),
+            }
+            let Some(line) = input.read_line().await else {
+                return false;
+            };
+            let mut username = line.trim().to_string();
+            if username == "/register" {
+                register = true;
+                continue;
+            }
+            if username.is_empty() && !register
+                && let Some(precedent) = precedent
+            {
+                username = precedent;
             }
-            let username = line.trim().to_string();
             if username.is_empty() {
-                println!("❌ Le nom d'utilisateur ne peut pas être vide");
+                session.print("❌ Le nom d'utilisateur ne peut pas être vide");
                 continue;
             }
-            let connect_msg = ClientMessage::Connect { username: username.clone() };
-            if tx.send(connect_msg).await.is_err() {
-                return;
+
+            let Some(password) = Self::read_password(input, session, "🔑 Mot de passe: ").await else {
+                return false;
+            };
+            let msg = if register {
+                let Some(confirmation) = Self::read_password(input, session, "🔑 Confirmez le mot de passe: ").await else {
+                    return false;
+                };
+                if confirmation != password {
+                    session.print("❌ Les mots de passe ne correspondent pas");
+                    continue;
+                }
+                ClientMessage::Register { username: username.clone(), password }
+            } else {
+                ClientMessage::Connect { username: username.clone(), password }
+            };
+
+            if tx.send(msg).await.is_err() {
+                return false;
             }
+            *session.username.lock().unwrap() = Some(username.clone());
             *client_state = ClientState::Connected(username);
-            break;
+            return true;
         }
     }
 
+    // Saisie masquée si un terminal est disponible (ou dans l'interface), sinon simple lecture
+    // de ligne (entrée redirigée)
+    async fn read_password(input: &mut Input, session: &Session, prompt: &'static str) -> Option<String> {
+        if matches!(input, Input::Stdin(_))
+            && let Ok(Ok(password)) = tokio::task::spawn_blocking(move || rpassword::prompt_password(prompt)).await
+        {
+            return Some(password);
+        }
+
+        session.print(prompt);
+        session.ui(UiEvent::Password(true));
+        let password = input.read_line().await.map(|line| line.trim_end_matches(['\r', '\n']).to_string());
+        session.ui(UiEvent::Password(false));
+        password
+    }
+
+    // Se connecte puis, si la connexion tombe une fois identifié, se reconnecte avec un délai
+    // croissant et reprend la session (même nom, mêmes salons, messages manqués renvoyés).
+    // Les messages tapés pendant la coupure partent à la reconnexion.
     pub async fn connect(&mut self, server_addr: &str) -> Result<()> {
+        // Canal pour envoyer des messages au serveur, conservé d'une connexion à l'autre
+        let (tx, mut rx) = mpsc::channel::<ClientMessage>(100);
+        // Interface plein écran : les lignes tapées arrivent par un canal au lieu de stdin
+        let (mut input, tui) = if self.tui {
+            let (lines_tx, lines_rx) = mpsc::unbounded_channel();
+            (Input::Tui(lines_rx), Some(Tui::start(lines_tx, self.theme)?))
+        } else {
+            (Input::Stdin(BufReader::new(stdin())), None)
+        };
+        let session = Arc::new(Session {
+            download_dir: self.download_dir.clone(),
+            username: Mutex::new(self.username.clone()),
+            ui: tui.as_ref().map(Tui::sender),
+            ..Session::default()
+        });
+
+        // Task pour lire l'input utilisateur, pour toute la durée du client. C'est la seule
+        // à lire la saisie : identification, puis chat ; un refus du serveur ramène à l'identification.
+        let input_tx = tx.clone();
+        let input_session = session.clone();
+        let input_task = tokio::spawn(async move {
+            let mut client_state = ClientState::Disconnected;
+            while Self::ask_credentials(&input_tx, &mut client_state, &mut input, &input_session).await
+                && Self::run_user_interface(&input_tx, &input_session, &mut input).await
+            {}
+            // Fin de l'entrée standard sans /quit : on se déconnecte proprement
+            let _ = input_tx.send(ClientMessage::Disconnect).await;
+        });
+
+        let resultat = self.keep_connected(server_addr, &mut rx, &tx, &session, &input_task).await;
+        input_task.abort();
+        if let Some(tui) = tui {
+            tui.close().await;
+        }
+        resultat
+    }
+
+    // Boucle de reconnexion, jusqu'à la fin de la saisie ou une coupure avant l'identification
+    async fn keep_connected(
+        &self,
+        server_addr: &str,
+        rx: &mut mpsc::Receiver<ClientMessage>,
+        tx: &mpsc::Sender<ClientMessage>,
+        session: &Session,
+        input_task: &tokio::task::JoinHandle<()>,
+    ) -> Result<()> {
+        let mut delai = RECONNECT_DELAY_MIN;
+        loop {
+            let resultat = self.open_session(server_addr, rx, tx, session).await;
+            if matches!(resultat, Ok(Fin::Quit)) || input_task.is_finished() || session.kicked.load(Ordering::Relaxed) {
+                break;
+            }
+            // Rien à reprendre tant que l'utilisateur ne s'est pas identifié
+            if session.token.lock().unwrap().is_none() {
+                return match resultat {
+                    Err(e) => Err(e),
+                    Ok(_) => Err(anyhow!("Connexion perdue avant l'identification")),
+                };
+            }
+
+            match resultat {
+                Ok(_) => delai = RECONNECT_DELAY_MIN, // la connexion avait tenu : on repart du début
+                Err(e) => warn!("⚠️ Reconnexion impossible: {}", e),
+            }
+            session.print(format!("🔌 Connexion perdue, nouvelle tentative dans {} s...", delai.as_secs()));
+            tokio::time::sleep(delai).await;
+            delai = (delai * 2).min(RECONNECT_DELAY_MAX);
+        }
+        Ok(())
+    }
+
+    // Une connexion : TCP, TLS éventuel, puis la session de chat
+    async fn open_session(
+        &self,
+        server_addr: &str,
+        rx: &mut mpsc::Receiver<ClientMessage>,
+        tx: &mpsc::Sender<ClientMessage>,
+        session: &Session,
+    ) -> Result<Fin> {
         let stream = TcpStream::connect(server_addr).await?;
-        info!("🔗 Connecté au serveur {}", server_addr);
 
-        let (mut reader, mut writer) = stream.into_split();
+        match &self.tls {
+            Some(tls) => {
+                let (connector, server_name) = tls.connector(server_addr)?;
+                let stream = connector.connect(server_name, stream).await?;
+                info!("🔒 Connecté au serveur {} (TLS)", server_addr);
+                Self::run(stream, rx, tx, session, self.heartbeat).await
+            }
+            None => {
+                info!("🔗 Connecté au serveur {}", server_addr);
+                Self::run(stream, rx, tx, session, self.heartbeat).await
+            }
+        }
+    }
+
+    // Session de chat sur un flux déjà établi (TCP en clair ou TLS)
+    async fn run<S>(
+        stream: S,
+        rx: &mut mpsc::Receiver<ClientMessage>,
+        tx: &mpsc::Sender<ClientMessage>,
+        session: &Session,
+        heartbeat: HeartbeatConfig,
+    ) -> Result<Fin>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (mut reader, mut writer) = tokio::io::split(stream);
 
-        // Canal pour envoyer des messages au serveur
-        let (tx, mut rx) = mpsc::channel::<ClientMessage>(100);
-        let tx_clone = tx.clone();
-        // On ne partage plus client_state entre tâches, il reste local à l'input utilisateur
+        // Poignée de main, toujours en JSON : le Welcome fixe l'encodage de la suite
+        let capabilities = [CAP_HISTORY, CAP_ROOMS, CAP_MSGPACK, CAP_COMPRESSION].map(String::from).to_vec();
+        ClientMessage::Hello { version: PROTOCOL_VERSION, capabilities }.to_protocol_message()?.write_to(&mut writer).await?;
+        let welcome = ServerMessage::from_protocol_message(&ProtocolMessage::read_from(&mut reader).await?)?;
+        let ServerMessage::Welcome { capabilities, .. } = &welcome else {
+            Self::handle_server_message(welcome, &mut ClientState::Disconnected, tx, session).await?;
+            return Err(anyhow!("Poignée de main refusée par le serveur"));
+        };
+        let encoding = Encoding::negotiate(capabilities);
+        let compression = capabilities.iter().any(|cap| cap == CAP_COMPRESSION);
+        Self::handle_server_message(welcome, &mut ClientState::Disconnected, tx, session).await?;
+
+        // Après une coupure : reprise de la session plutôt que nouvelle identification
+        let token = session.token.lock().unwrap().clone();
+        if let Some(token) = token {
+            session.resuming.store(true, Ordering::Relaxed);
+            let last_seen_id = Some(session.last_seen_id.load(Ordering::Relaxed)).filter(|id| *id > 0);
+            let resume = ClientMessage::Resume { token, last_seen_id }.encode(encoding)?;
+            resume.compressed(compression).write_to(&mut writer).await?;
+        }
+
+        // Battements manqués d'affilée, remis à zéro par tout message reçu
+        let missed = AtomicU32::new(0);
 
-        // Task pour lire les messages du serveur
-        let receive_task = tokio::spawn(async move {
+        // Lecture des messages du serveur
+        let reception = async {
             loop {
-                match ProtocolMessage::read_from(&mut reader).await {
+                let msg = match ProtocolMessage::read_from(&mut reader).await {
                     Ok(msg) => {
-                        // On ne gère plus client_state ici
-                        let _ = Self::handle_server_message(&msg, &mut ClientState::Disconnected, &tx_clone).await;
+                        missed.store(0, Ordering::Relaxed);
+                        msg
                     },
                     Err(e) => {
                         error!("❌ Erreur lecture message: {}", e);
-                        break;
+                        return Fin::Lost;
                     }
+                };
+                let msg = match msg.decompress() {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        error!("❌ Message illisible: {}", e);
+                        continue;
+                    }
+                };
+                match ServerMessage::decode(&msg, encoding) {
+                    // On ne gère plus client_state ici
+                    Ok(server_msg) => {
+                        let _ = Self::handle_server_message(server_msg, &mut ClientState::Disconnected, tx, session).await;
+                    },
+                    Err(e) => error!("❌ Message illisible: {}", e),
                 }
             }
-        });
+        };
 
-        // Task pour envoyer des messages au serveur
-        let send_task = tokio::spawn(async move {
+        // Envoi des messages au serveur
+        let envoi = async {
             while let Some(msg) = rx.recv().await {
-                if let Ok(protocol_msg) = msg.to_protocol_message() {
-                    if protocol_msg.write_to(&mut writer).await.is_err() {
-                        break;
-                    }
+                let quitter = matches!(msg, ClientMessage::Disconnect);
+                if let Ok(protocol_msg) = msg.encode(encoding)
+                    && protocol_msg.compressed(compression).write_to(&mut writer).await.is_err()
+                {
+                    return Fin::Lost;
+                }
+                if quitter {
+                    return Fin::Quit;
                 }
             }
-        });
-
-        // Task pour lire l'input utilisateur
-        let input_task = tokio::spawn(async move {
-            let mut client_state = ClientState::Disconnected;
-            Self::ask_username(&tx, &mut client_state).await;
-            Self::run_user_interface(tx).await;
-        });
+            // La saisie est terminée
+            Fin::Quit
+        };
 
-        // Attendre qu'une des tâches se termine
-        tokio::select! {
-            _ = receive_task => {},
-            _ = send_task => {},
-            _ = input_task => {},
-        }
+        // Ping régulier : sur un Wi-Fi coupé, la lecture seule ne verrait jamais la coupure
+        let veille = async {
+            if heartbeat.interval.is_zero() {
+                return std::future::pending().await;
+            }
+            let mut intervalle = tokio::time::interval(heartbeat.interval);
+            intervalle.tick().await; // le premier tick est immédiat
+            loop {
+                intervalle.tick().await;
+                if missed.fetch_add(1, Ordering::Relaxed) >= heartbeat.max_missed {
+                    warn!("💔 Le serveur ne répond plus");
+                    return Fin::Lost;
+                }
+                let _ = tx.send(ClientMessage::Ping).await;
+            }
+        };
 
-        Ok(())
+        // Attendre que l'un des côtés se termine
+        Ok(tokio::select! {
+            fin = reception => fin,
+            fin = envoi => fin,
+            fin = veille => fin,
+        })
     }
 
-    async fn run_user_interface(tx: mpsc::Sender<ClientMessage>) {
-        let stdin = stdin();
-        let mut reader = BufReader::new(stdin);
-        let mut line = String::new();
+    // Vrai si le serveur redemande l'identification, faux à la fin de la saisie
+    async fn run_user_interface(tx: &mpsc::Sender<ClientMessage>, session: &Session, input: &mut Input) -> bool {
+        let mut current_room = DEFAULT_ROOM.to_string();
 
         // Boucle principale de chat
         loop {
-            line.clear();
-            if reader.read_line(&mut line).await.is_err() {
-                break;
-            }
+            let line = tokio::select! {
+                line = input.read_line() => line,
+                _ = session.relogin.notified() => return true,
+            };
+            let Some(line) = line else {
+                return false;
+            };
 
             let input = line.trim();
             if input.is_empty() {
                 continue;
             }
 
-            let message = if input.starts_with('/') {
-                Self::parse_command(input)
+            let message = if input == "/send" || input.starts_with("/send ") {
+                Self::prepare_file_offer(input, session).await
+            } else if input.starts_with('/') {
+                Self::parse_command(input, &mut current_room, session)
             } else {
                 Some(ClientMessage::PublicMessage {
                     content: input.to_string(),
+                    room: current_room.clone(),
+                    client_ref: None,
                 })
             };
 
-            if let Some(msg) = message {
+            if let Some(mut msg) = message {
+                if let ClientMessage::PrivateMessage { to, content, client_ref } = &mut msg {
+                    *client_ref = Some(session.outbox.lock().unwrap().track(format!("{}: {}", to, content)));
+                }
+
                 // Vérifier si c'est une commande de déconnexion
                 if matches!(msg, ClientMessage::Disconnect) {
                     let _ = tx.send(msg).await;
-                    break;
+                    return false;
                 }
 
+                // En entrant dans un salon, on affiche ce qui s'y est dit
+                let suite = match &msg {
+                    ClientMessage::JoinRoom { room } => Some(ClientMessage::History { limit: None, room: room.clone() }),
+                    _ => None,
+                };
+
                 if tx.send(msg).await.is_err() {
-                    break;
+                    return false;
+                }
+                if let Some(suite) = suite
+                    && tx.send(suite).await.is_err()
+                {
+                    return false;
                 }
             }
         }
     }
 
-    fn parse_command(input: &str) -> Option<ClientMessage> {
+    fn parse_command(input: &str, current_room: &mut String, session: &Session) -> Option<ClientMessage> {
         let parts: Vec<&str> = input.splitn(3, ' ').collect();
 
         match parts[0] {
@@ -188,30 +973,176 @@ impl ChatClient {
                 if parts.len() >= 3 {
                     let to = parts[1].to_string();
                     let content = parts[2].to_string();
-                    Some(ClientMessage::PrivateMessage { to, content })
+                    Some(ClientMessage::PrivateMessage { to, content, client_ref: None })
                 } else {
-                    println!("❌ Usage: /msg <utilisateur> <message>");
+                    session.print("❌ Usage: /msg <utilisateur> <message>");
                     None
                 }
             },
             "/users" | "/list" => {
                 Some(ClientMessage::ListUsers)
             },
+            "/history" => {
+                match parts.get(1).map(|n| n.parse::<usize>()) {
+                    None => Some(ClientMessage::History { limit: None, room: current_room.clone() }),
+                    Some(Ok(n)) if n > 0 => Some(ClientMessage::History { limit: Some(n), room: current_room.clone() }),
+                    Some(_) => {
+                        session.print("❌ Usage: /history [nombre de messages]");
+                        None
+                    }
+                }
+            },
+            "/join" => {
+                match parts.get(1) {
+                    Some(room) if is_valid_room_name(room) => {
+                        *current_room = room.to_string();
+                        session.print(format!("📍 Salon courant: {}", current_room));
+                        session.ui(UiEvent::CurrentRoom(current_room.clone()));
+                        Some(ClientMessage::JoinRoom { room: room.to_string() })
+                    },
+                    _ => {
+                        session.print("❌ Usage: /join #salon (lettres, chiffres, '-' ou '_')");
+                        None
+                    }
+                }
+            },
+            "/leave" => {
+                let room = parts.get(1).map(|room| room.to_string()).unwrap_or_else(|| current_room.clone());
+                if &room == current_room {
+                    *current_room = DEFAULT_ROOM.to_string();
+                    session.print(format!("📍 Salon courant: {}", current_room));
+                    session.ui(UiEvent::CurrentRoom(current_room.clone()));
+                }
+                Some(ClientMessage::LeaveRoom { room })
+            },
+            "/rooms" => {
+                Some(ClientMessage::ListRooms)
+            },
+            "/receipts" => {
+                match parts.get(1).copied() {
+                    Some("on") => session.read_receipts.store(true, Ordering::Relaxed),
+                    Some("off") => session.read_receipts.store(false, Ordering::Relaxed),
+                    _ => session.print("❌ Usage: /receipts on|off"),
+                }
+                let etat = if session.read_receipts.load(Ordering::Relaxed) { "activés" } else { "désactivés" };
+                session.print(format!("👁 Accusés de lecture {}", etat));
+                None
+            },
+            "/accept" => {
+                match parts.get(1).and_then(|id| id.parse::<u64>().ok()) {
+                    Some(transfer_id) => Self::accept_file(transfer_id, session, &session.download_dir),
+                    None => {
+                        session.print("❌ Usage: /accept <numéro du transfert>");
+                        None
+                    }
+                }
+            },
+            "/reject" => {
+                match parts.get(1).and_then(|id| id.parse::<u64>().ok()) {
+                    Some(transfer_id) if session.transfers.lock().unwrap().offers.remove(&transfer_id).is_some() => {
+                        Some(ClientMessage::FileReject { transfer_id, reason: None })
+                    },
+                    _ => {
+                        session.print("❌ Usage: /reject <numéro d'une offre reçue>");
+                        None
+                    }
+                }
+            },
+            "/cancel" => {
+                match parts.get(1).and_then(|id| id.parse::<u64>().ok()) {
+                    Some(transfer_id) => {
+                        let mut transfers = session.transfers.lock().unwrap();
+                        transfers.outgoing.remove(&transfer_id);
+                        transfers.incoming.remove(&transfer_id);
+                        Some(ClientMessage::FileCancel { transfer_id, reason: None })
+                    },
+                    None => {
+                        session.print("❌ Usage: /cancel <numéro du transfert>");
+                        None
+                    }
+                }
+            },
+            "/kick" => {
+                match parts.get(1) {
+                    Some(username) => Some(ClientMessage::Kick {
+                        username: username.to_string(),
+                        reason: parts.get(2).map(|reason| reason.to_string()),
+                    }),
+                    None => {
+                        session.print("❌ Usage: /kick <utilisateur> [raison]");
+                        None
+                    }
+                }
+            },
+            "/ban" => {
+                match parts.get(1) {
+                    Some(target) => Some(ClientMessage::Ban {
+                        target: target.to_string(),
+                        reason: parts.get(2).map(|reason| reason.to_string()),
+                    }),
+                    None => {
+                        session.print("❌ Usage: /ban <utilisateur|adresse IP> [raison]");
+                        None
+                    }
+                }
+            },
+            "/unban" => {
+                match parts.get(1) {
+                    Some(target) => Some(ClientMessage::Unban { target: target.to_string() }),
+                    None => {
+                        session.print("❌ Usage: /unban <utilisateur|adresse IP>");
+                        None
+                    }
+                }
+            },
+            "/mute" => {
+                // La raison suit la durée : "/mute bob 10m flood"
+                let (duree, reason) = match parts.get(2).map(|reste| reste.split_once(' ')) {
+                    Some(Some((duree, reason))) => (Some(duree), Some(reason.to_string())),
+                    Some(None) => (parts.get(2).copied(), None),
+                    None => (None, None),
+                };
+                match (parts.get(1), duree.and_then(Self::parse_duration)) {
+                    (Some(username), Some(seconds)) => Some(ClientMessage::Mute { username: username.to_string(), seconds, reason }),
+                    _ => {
+                        session.print("❌ Usage: /mute <utilisateur> <durée: 30s, 10m, 2h, 1j> [raison]");
+                        None
+                    }
+                }
+            },
+            "/unmute" => {
+                match parts.get(1) {
+                    Some(username) => Some(ClientMessage::Mute { username: username.to_string(), seconds: 0, reason: None }),
+                    None => {
+                        session.print("❌ Usage: /unmute <utilisateur>");
+                        None
+                    }
+                }
+            },
             "/quit" | "/exit" => {
-                println!("👋 Au revoir!");
+                session.print("👋 Au revoir!");
                 Some(ClientMessage::Disconnect)
             },
             "/help" => {
-                println!("📋 Commandes disponibles:");
-                println!("  /msg <utilisateur> <message> - Envoyer un message privé");
-                println!("  /users - Lister les utilisateurs connectés");
-                println!("  /quit - Quitter");
-                println!("  /help - Afficher cette aide");
-                println!("  Tapez simplement votre message pour l'envoyer à tous");
+                session.print("📋 Commandes disponibles:");
+                session.print("  /msg <utilisateur> <message> - Envoyer un message privé");
+                session.print("  /users - Lister les utilisateurs connectés");
+                session.print("  /join #salon - Rejoindre un salon (et en faire le salon courant)");
+                session.print("  /leave [#salon] - Quitter un salon");
+                session.print("  /rooms - Lister les salons");
+                session.print("  /history [n] - Afficher les derniers messages du salon courant");
+                session.print("  /receipts on|off - Envoyer ou non les accusés de lecture");
+                session.print("  /send <utilisateur> <chemin> - Proposer un fichier");
+                session.print("  /accept <n> | /reject <n> | /cancel <n> - Répondre à une offre, interrompre un transfert");
+                session.print("  /quit - Quitter");
+                session.print("  /help - Afficher cette aide");
+                session.print("  Opérateurs : /kick <utilisateur> [raison], /ban <utilisateur|IP> [raison], /unban <utilisateur|IP>,");
+                session.print("               /mute <utilisateur> <durée> [raison], /unmute <utilisateur>");
+                session.print(format!("  Tapez simplement votre message pour l'envoyer au salon courant ({})", current_room));
                 None
             },
             _ => {
-                println!("❌ Commande inconnue: {}. Tapez /help pour l'aide", parts[0]);
+                session.print(format!("❌ Commande inconnue: {}. Tapez /help pour l'aide", parts[0]));
                 None
             }
         }
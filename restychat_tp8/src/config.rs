@@ -0,0 +1,153 @@
+// =============================
+// Fichiers de configuration (TOML)
+// Serveur, par exemple rustchat.toml :
+//   bind = "0.0.0.0:8080"
+//   max_clients = 200
+//   max_message_size = 4096      # octets par message de chat
+//   history_path = "historique.jsonl"
+//   motd = "Bienvenue ! Soyez courtois."
+//   log_level = "info"           # error, warn, info, debug ou trace
+//   operators = ["alice"]
+//   [tls]
+//   cert = "serveur.pem"
+//   key = "serveur.key"
+//   client_ca = "ca.pem"         # facultatif : certificats clients exigés
+// Client, profil ~/.config/rustchat/profil.toml :
+//   server = "chat.example.org:8080"
+//   username = "alice"
+//   theme = "dark"               # dark, light ou mono
+// Toutes les clés sont facultatives ; les options de la ligne de commande l'emportent.
+// =============================
+
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerFile {
+    pub bind: Option<String>,
+    pub max_clients: Option<usize>,
+    pub max_message_size: Option<usize>,
+    pub history_path: Option<PathBuf>,
+    pub accounts_path: Option<PathBuf>,
+    pub offline_path: Option<PathBuf>,
+    pub bans_path: Option<PathBuf>,
+    pub operators: Option<Vec<String>>,
+    pub motd: Option<String>,
+    pub log_level: Option<String>,
+    pub tls: Option<TlsFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsFile {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub client_ca: Option<PathBuf>,
+}
+
+impl ServerFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        load(path.as_ref())
+    }
+}
+
+// Couleurs de l'interface plein écran
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,  // terminal sombre
+    Light, // terminal clair
+    Mono,  // sans couleur
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dark" => Ok(Self::Dark),
+            "light" => Ok(Self::Light),
+            "mono" => Ok(Self::Mono),
+            autre => Err(format!("Thème inconnu '{}' (dark, light ou mono)", autre)),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Theme::Dark => write!(f, "dark"),
+            Theme::Light => write!(f, "light"),
+            Theme::Mono => write!(f, "mono"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientProfile {
+    pub server: Option<String>,
+    pub username: Option<String>, // proposé par défaut à l'identification
+    pub theme: Option<Theme>,
+}
+
+impl ClientProfile {
+    // Un profil absent n'est pas une erreur : valeurs par défaut
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        load(path)
+    }
+
+    // $XDG_CONFIG_HOME/rustchat/profil.toml, sinon ~/.config/rustchat/profil.toml
+    pub fn default_path() -> Option<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config.join("rustchat").join("profil.toml"))
+    }
+}
+
+fn load<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let contenu = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Fichier de configuration {} illisible: {}", path.display(), e))?;
+    toml::from_str(&contenu).map_err(|e| anyhow!("Fichier de configuration {} invalide: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_file_and_profile_parse() {
+        let fichier: ServerFile = toml::from_str(r#"
+            bind = "0.0.0.0:9000"
+            max_clients = 2
+            motd = "Bienvenue"
+            [tls]
+            cert = "serveur.pem"
+            key = "serveur.key"
+        "#).unwrap();
+        assert_eq!(fichier.bind.as_deref(), Some("0.0.0.0:9000"));
+        assert_eq!(fichier.max_clients, Some(2));
+        assert_eq!(fichier.tls.map(|tls| (tls.key, tls.client_ca)), Some((PathBuf::from("serveur.key"), None)));
+        assert_eq!(fichier.history_path, None);
+
+        // Une faute de frappe ne doit pas passer inaperçue
+        assert!(toml::from_str::<ServerFile>("max_client = 2").is_err());
+
+        let profil: ClientProfile = toml::from_str("username = \"alice\"\ntheme = \"light\"").unwrap();
+        assert_eq!(profil.theme, Some(Theme::Light));
+        assert!(toml::from_str::<ClientProfile>("theme = \"rose\"").is_err());
+        assert_eq!(ClientProfile::load_or_default("/nonexistent/profil.toml").unwrap(), ClientProfile::default());
+    }
+}
@@ -0,0 +1,166 @@
+// =============================
+// Historique des messages publics
+// Chaque message est ajouté en fin de fichier, une ligne JSON par message :
+// {"id":1,"timestamp":1700000000,"from":"alice","content":"salut","room":"#general"}
+// Les derniers messages restent en mémoire pour répondre vite à /history.
+// =============================
+
+use crate::HistoryEntry;
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+// Nombre de messages gardés en mémoire
+const MAX_RECENT: usize = 1000;
+// Nombre de messages renvoyés quand le client ne précise rien
+pub const DEFAULT_HISTORY_LEN: usize = 20;
+// Plafond d'une requête /history
+pub const MAX_HISTORY_LEN: usize = 200;
+
+// Secondes écoulées depuis l'epoch Unix
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub struct MessageHistory {
+    file: Option<File>, // None : historique uniquement en mémoire
+    recent: VecDeque<HistoryEntry>,
+    next_id: u64,
+}
+
+impl MessageHistory {
+    // Historique perdu à l'arrêt du serveur
+    pub fn in_memory() -> Self {
+        Self {
+            file: None,
+            recent: VecDeque::new(),
+            next_id: 1,
+        }
+    }
+
+    // Ouvre (ou crée) le fichier et recharge les derniers messages
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut history = Self::in_memory();
+
+        if path.exists() {
+            let reader = BufReader::new(File::open(path)?);
+            for (numero, ligne) in reader.lines().enumerate() {
+                let ligne = ligne?;
+                if ligne.trim().is_empty() {
+                    continue;
+                }
+                // Une ligne abîmée (arrêt brutal pendant l'écriture) ne bloque pas le démarrage
+                match serde_json::from_str::<HistoryEntry>(&ligne) {
+                    Ok(entry) => history.remember(entry),
+                    Err(e) => warn!("⚠️ Ligne {} de {} ignorée: {}", numero + 1, path.display(), e),
+                }
+            }
+        }
+
+        history.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        Ok(history)
+    }
+
+    fn remember(&mut self, entry: HistoryEntry) {
+        self.next_id = self.next_id.max(entry.id + 1);
+        if self.recent.len() == MAX_RECENT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(entry);
+    }
+
+    // Réserve un identifiant pour un message non archivé (messages privés) :
+    // publics et privés partagent la même numérotation
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    // Écarte les identifiants déjà attribués hors de l'historique (messages privés
+    // encore en attente), pour qu'un redémarrage ne les redonne pas
+    pub fn reserve_through(&mut self, id: u64) {
+        self.next_id = self.next_id.max(id + 1);
+    }
+
+    // Archive un message et lui attribue un identifiant
+    pub fn append(&mut self, room: &str, from: &str, content: &str) -> Result<HistoryEntry> {
+        let entry = HistoryEntry {
+            id: self.next_id,
+            timestamp: unix_timestamp(),
+            from: from.to_string(),
+            content: content.to_string(),
+            room: room.to_string(),
+        };
+
+        if let Some(file) = &mut self.file {
+            let mut ligne = serde_json::to_vec(&entry)?;
+            ligne.push(b'\n');
+            file.write_all(&ligne)?;
+        }
+
+        self.remember(entry.clone());
+        Ok(entry)
+    }
+
+    // Messages d'un salon postérieurs à `after_id` (au plus `n`, les plus anciens d'abord)
+    pub fn since(&self, room: &str, after_id: u64, n: usize) -> Vec<HistoryEntry> {
+        self.recent.iter()
+            .filter(|entry| entry.room == room && entry.id > after_id)
+            .take(n)
+            .cloned()
+            .collect()
+    }
+
+    // Les `n` derniers messages d'un salon, du plus ancien au plus récent
+    pub fn last(&self, room: &str, n: usize) -> Vec<HistoryEntry> {
+        let mut messages: Vec<HistoryEntry> = self.recent.iter()
+            .rev()
+            .filter(|entry| entry.room == room)
+            .take(n)
+            .cloned()
+            .collect();
+        messages.reverse();
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_history_survives_reopen() {
+        let path = std::env::temp_dir().join(format!("restychat_history_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        {
+            let mut history = MessageHistory::open(&path).unwrap();
+            assert_eq!(history.append("#general", "alice", "bonjour").unwrap().id, 1);
+            assert_eq!(history.append("#general", "bob", "salut").unwrap().id, 2);
+        }
+
+        // Une ligne tronquée en fin de fichier est ignorée ; une ligne sans salon va dans #general
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"id\":3,\"timestamp\":0,\"from\":\"dave\",\"content\":\"ancien\"}\n").unwrap();
+        file.write_all(b"{\"id\":4,\"timest").unwrap();
+        file.write_all(b"\n").unwrap();
+
+        let mut history = MessageHistory::open(&path).unwrap();
+        let derniers = history.last("#general", 10);
+        assert_eq!(derniers.len(), 3);
+        assert_eq!((derniers[0].from.as_str(), derniers[2].content.as_str()), ("alice", "ancien"));
+        assert_eq!(history.append("#rust", "carol", "re").unwrap().id, 4);
+        assert_eq!(history.last("#general", 1)[0].from, "dave");
+        assert_eq!(history.last("#rust", 5).len(), 1);
+        assert_eq!(history.since("#general", 1, 10).iter().map(|e| e.id).collect::<Vec<_>>(), [2, 3]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
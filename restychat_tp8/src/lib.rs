@@ -1,22 +1,111 @@
 // =============================
 // Protocole RustChat
 // Format d'un message :
-// [4 octets: longueur totale (u32, big-endian)] [1 octet: type de message (u8)] [données sérialisées JSON]
+// [4 octets: longueur totale (u32, big-endian)] [1 octet: type de message (u8)] [données sérialisées]
+// Le premier message d'un client doit être Hello (version + capacités) ;
+// le serveur répond Welcome ou Error puis ferme la connexion.
+// Hello et Welcome sont toujours en JSON. Si les deux côtés annoncent "msgpack",
+// tous les messages suivants sont encodés en MessagePack. Si les deux annoncent
+// "compression", les données des messages suivants assez longs sont compressées
+// (deflate) et le bit de poids fort du type le signale.
 // =============================
 
+pub mod accounts;
 pub mod client;
+pub mod config;
+pub mod history;
+pub mod moderation;
+pub mod offline;
+pub mod queue;
+pub mod ratelimit;
 pub mod server;
+pub mod session;
+pub mod tls;
+pub mod transfer;
+pub mod tui;
 
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::io;
+use std::io::{self, Read, Write};
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+// Version parlée par ce code. La v1 (sans poignée de main) n'est plus acceptée.
+pub const PROTOCOL_VERSION: u16 = 2;
+pub const MIN_PROTOCOL_VERSION: u16 = 2;
+
+// Capacités échangées pendant la poignée de main
+pub const CAP_HISTORY: &str = "history";
+pub const CAP_ROOMS: &str = "rooms";
+pub const CAP_MSGPACK: &str = "msgpack";
+pub const CAP_COMPRESSION: &str = "compression";
+
+// Encodage des données d'un message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl Encoding {
+    // Encodage à utiliser après le Welcome, d'après les capacités retenues par le serveur
+    pub fn negotiate(capabilities: &[String]) -> Self {
+        if capabilities.iter().any(|cap| cap == CAP_MSGPACK) {
+            Encoding::MessagePack
+        } else {
+            Encoding::Json
+        }
+    }
+
+    pub fn to_vec<T: Serialize>(self, value: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            Encoding::Json => serde_json::to_vec(value)?,
+            // Les noms de champs sont gardés : le tag "type" des enums en a besoin
+            Encoding::MessagePack => rmp_serde::to_vec_named(value)?,
+        })
+    }
+
+    pub fn from_slice<T: DeserializeOwned>(self, data: &[u8]) -> anyhow::Result<T> {
+        Ok(match self {
+            Encoding::Json => serde_json::from_slice(data)?,
+            Encoding::MessagePack => rmp_serde::from_slice(data)?,
+        })
+    }
+}
+
 // Types de messages du protocole
 pub const MSG_CONNECT: u8 = 0x01;
 pub const MSG_PUBLIC_MESSAGE: u8 = 0x02;
 pub const MSG_PRIVATE_MESSAGE: u8 = 0x03;
 pub const MSG_LIST_USERS: u8 = 0x04;
 pub const MSG_DISCONNECT: u8 = 0x05;
+pub const MSG_HISTORY: u8 = 0x06;
+pub const MSG_JOIN_ROOM: u8 = 0x07;
+pub const MSG_LEAVE_ROOM: u8 = 0x08;
+pub const MSG_LIST_ROOMS: u8 = 0x09;
+pub const MSG_REGISTER: u8 = 0x0A;
+pub const MSG_HELLO: u8 = 0x0B;
+pub const MSG_MARK_READ: u8 = 0x0C;
+pub const MSG_RESUME: u8 = 0x0D;
+pub const MSG_PING: u8 = 0x0E;
+pub const MSG_PONG: u8 = 0x0F;
+// Commandes d'opérateur
+pub const MSG_KICK: u8 = 0x20;
+pub const MSG_BAN: u8 = 0x21;
+pub const MSG_UNBAN: u8 = 0x22;
+pub const MSG_MUTE: u8 = 0x23;
+// Transfert de fichiers (voir transfer.rs)
+pub const MSG_FILE_OFFER: u8 = 0x24;
+pub const MSG_FILE_ACCEPT: u8 = 0x25;
+pub const MSG_FILE_REJECT: u8 = 0x26;
+pub const MSG_FILE_CHUNK: u8 = 0x27;
+pub const MSG_FILE_ACK: u8 = 0x28;
+pub const MSG_FILE_DONE: u8 = 0x29;
+pub const MSG_FILE_CANCEL: u8 = 0x2A;
 
 pub const MSG_CONNECT_RESPONSE: u8 = 0x10;
 pub const MSG_MESSAGE_BROADCAST: u8 = 0x11;
@@ -25,31 +114,237 @@ pub const MSG_USER_LIST: u8 = 0x13;
 pub const MSG_USER_JOINED: u8 = 0x14;
 pub const MSG_USER_LEFT: u8 = 0x15;
 pub const MSG_ERROR: u8 = 0x16;
+pub const MSG_HISTORY_RESPONSE: u8 = 0x17;
+pub const MSG_ROOM_JOINED: u8 = 0x18;
+pub const MSG_ROOM_LEFT: u8 = 0x19;
+pub const MSG_ROOM_LIST: u8 = 0x1A;
+pub const MSG_WELCOME: u8 = 0x1B;
+pub const MSG_PRIVATE_MESSAGE_STATUS: u8 = 0x1C;
+pub const MSG_ACK: u8 = 0x1D;
+pub const MSG_SERVER_PING: u8 = 0x1E;
+pub const MSG_SERVER_PONG: u8 = 0x1F;
+pub const MSG_MODERATION: u8 = 0x30;
+pub const MSG_KICKED: u8 = 0x31;
+pub const MSG_FILE_OFFERED: u8 = 0x32;
+pub const MSG_FILE_OFFER_SENT: u8 = 0x33;
+pub const MSG_FILE_ACCEPTED: u8 = 0x34;
+pub const MSG_FILE_REJECTED: u8 = 0x35;
+pub const MSG_FILE_CHUNK_DELIVERY: u8 = 0x36;
+pub const MSG_FILE_ACKED: u8 = 0x37;
+pub const MSG_FILE_DONE_STATUS: u8 = 0x38;
+pub const MSG_FILE_CANCELLED: u8 = 0x39;
+pub const MSG_MOTD: u8 = 0x3A;
+
+// Battement de cœur : chaque côté envoie Ping toutes les `interval` et coupe la
+// connexion si l'autre reste muet `max_missed` intervalles de suite.
+// Tout message reçu compte comme signe de vie. Un intervalle nul désactive le mécanisme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    pub interval: Duration,
+    pub max_missed: u32,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            max_missed: 3,
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    // RUSTCHAT_HEARTBEAT_SECS et RUSTCHAT_HEARTBEAT_MISSED remplacent les valeurs par défaut
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(secs) = std::env::var("RUSTCHAT_HEARTBEAT_SECS").ok().and_then(|v| v.parse().ok()) {
+            config.interval = Duration::from_secs(secs);
+        }
+        if let Some(missed) = std::env::var("RUSTCHAT_HEARTBEAT_MISSED").ok().and_then(|v| v.parse().ok()) {
+            config.max_missed = missed;
+        }
+        config
+    }
+}
+
+// Salon rejoint automatiquement à la connexion
+pub const DEFAULT_ROOM: &str = "#general";
+
+fn default_room() -> String {
+    DEFAULT_ROOM.to_string()
+}
+
+// Un nom de salon : '#' suivi de 1 à 31 lettres, chiffres, '-' ou '_'
+pub fn is_valid_room_name(room: &str) -> bool {
+    room.strip_prefix('#').is_some_and(|nom| {
+        (1..=31).contains(&nom.len()) && nom.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    })
+}
 
 // Messages Client -> Serveur
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ClientMessage {
-    Connect { username: String },
-    PublicMessage { content: String },
-    PrivateMessage { to: String, content: String },
+    Hello { version: u16, capabilities: Vec<String> },
+    Connect { username: String, password: String },  // connexion à un compte existant
+    Register { username: String, password: String }, // création du compte puis connexion
+    Resume {
+        token: String,             // reçu dans le dernier ConnectResponse
+        last_seen_id: Option<u64>, // les messages publics suivants sont renvoyés
+    },
+    PublicMessage {
+        content: String,
+        #[serde(default = "default_room")]
+        room: String,
+        #[serde(default)]
+        client_ref: Option<u64>, // renvoyé tel quel dans l'Ack
+    },
+    PrivateMessage {
+        to: String,
+        content: String,
+        #[serde(default)]
+        client_ref: Option<u64>,
+    },
+    MarkRead { id: u64 }, // accusé de lecture d'un message privé reçu (facultatif)
+    Ping,
+    Pong,
     ListUsers,
     Disconnect,
+    History {
+        limit: Option<usize>, // None : nombre par défaut du serveur
+        #[serde(default = "default_room")]
+        room: String,
+    },
+    JoinRoom { room: String },
+    LeaveRoom { room: String },
+    ListRooms,
+    // Réservés aux opérateurs du serveur
+    Kick { username: String, reason: Option<String> },
+    Ban { target: String, reason: Option<String> }, // nom d'utilisateur ou adresse IP
+    Unban { target: String },
+    Mute { username: String, seconds: u64, reason: Option<String> }, // 0 : rend la parole
+    // Transfert de fichiers : expéditeur
+    FileOffer { to: String, name: String, size: u64, sha256: String, client_ref: u64 },
+    FileChunk {
+        transfer_id: u64,
+        offset: u64,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>, // au plus transfer::CHUNK_SIZE octets
+        crc32: u32,
+    },
+    // Transfert de fichiers : destinataire
+    FileAccept { transfer_id: u64, offset: u64 }, // offset : octets déjà reçus (reprise)
+    FileReject { transfer_id: u64, reason: Option<String> },
+    FileAck { transfer_id: u64, next_offset: u64 },
+    FileDone { transfer_id: u64, ok: bool }, // empreinte SHA-256 vérifiée ou non
+    // L'un ou l'autre
+    FileCancel { transfer_id: u64, reason: Option<String> },
 }
 
 // Messages Serveur -> Client
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type")]
 pub enum ServerMessage {
-    ConnectResponse { success: bool, message: String },
-    MessageBroadcast { from: String, content: String },
-    PrivateMessageDelivery { from: String, content: String },
+    Welcome { version: u16, capabilities: Vec<String> }, // capacités communes au client et au serveur
+    ConnectResponse {
+        success: bool,
+        message: String,
+        #[serde(default)]
+        session_token: Option<String>, // pour reprendre la session après une coupure
+    },
+    MessageBroadcast { id: u64, timestamp: u64, from: String, content: String, room: String },
+    PrivateMessageDelivery {
+        id: u64,
+        timestamp: u64,
+        from: String,
+        content: String,
+        #[serde(default)]
+        offline: bool, // envoyé pendant que le destinataire était hors ligne
+    },
+    Ack { id: u64, timestamp: u64, client_ref: Option<u64> }, // message accepté par le serveur
+    PrivateMessageStatus { id: u64, to: String, status: DeliveryStatus }, // pour l'expéditeur d'un message privé
+    Ping,
+    Pong,
     UserList { users: Vec<String> },
     UserJoined { username: String },
     UserLeft { username: String },
     Error { message: String },
+    History { room: String, messages: Vec<HistoryEntry> },
+    RoomJoined { room: String, username: String },
+    RoomLeft { room: String, username: String },
+    RoomList { rooms: Vec<RoomInfo> },
+    Moderation {
+        action: ModerationAction,
+        target: String, // nom d'utilisateur, ou adresse IP (annoncée à l'opérateur seulement)
+        by: String,
+        reason: Option<String>,
+        #[serde(default)]
+        seconds: Option<u64>, // durée d'un silence
+    },
+    Kicked { by: String, reason: Option<String>, banned: bool }, // juste avant la fermeture de la connexion
+    // Transfert de fichiers, relayé entre l'expéditeur et le destinataire
+    FileOffered { transfer_id: u64, from: String, name: String, size: u64, sha256: String },
+    FileOfferSent { transfer_id: u64, client_ref: u64 },
+    FileAccepted { transfer_id: u64, offset: u64 },
+    FileRejected { transfer_id: u64, reason: Option<String> },
+    FileChunk {
+        transfer_id: u64,
+        offset: u64,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+        crc32: u32,
+    },
+    FileAcked { transfer_id: u64, next_offset: u64 },
+    FileDone { transfer_id: u64, ok: bool },
+    FileCancelled { transfer_id: u64, reason: Option<String> },
+    Motd { message: String }, // message du jour, après une identification réussie
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    Kick,
+    Ban,
+    Unban,
+    Mute,
+    Unmute,
+}
+
+// Sort d'un message privé
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Delivered, // remis au destinataire connecté
+    Queued,    // gardé jusqu'à la prochaine connexion du destinataire
+    Read,      // accusé de lecture envoyé par le destinataire
+}
+
+// Un salon et son nombre de membres connectés
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RoomInfo {
+    pub name: String,
+    pub members: usize,
+}
+
+// Message public archivé par le serveur
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub timestamp: u64, // secondes depuis l'epoch Unix
+    pub from: String,
+    pub content: String,
+    #[serde(default = "default_room")] // historiques écrits avant l'arrivée des salons
+    pub room: String,
+}
+
+// Taille maximale d'une trame, en-tête compris
+pub const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+// Bit du type de message signalant des données compressées
+pub const FLAG_COMPRESSED: u8 = 0x80;
+// En dessous, compresser coûte plus que cela ne rapporte
+pub const COMPRESSION_THRESHOLD: usize = 256;
+
 // Structure d'un message protocolaire
 #[derive(Debug)]
 pub struct ProtocolMessage {
@@ -85,7 +380,7 @@ impl ProtocolMessage {
         reader.read_exact(&mut len_buf).await?;
         let total_len = u32::from_be_bytes(len_buf) as usize;
 
-        if total_len < 5 || total_len > 1024 * 1024 { // Max 1MB
+        if !(5..=MAX_FRAME_SIZE).contains(&total_len) {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "Message size invalid"
@@ -103,6 +398,38 @@ impl ProtocolMessage {
         Ok(ProtocolMessage::new(msg_type, data))
     }
 
+    pub fn is_compressed(&self) -> bool {
+        self.msg_type & FLAG_COMPRESSED != 0
+    }
+
+    // Compresse les données assez longues si la compression est négociée ; le
+    // message reste tel quel si cela ne le raccourcit pas
+    pub fn compressed(self, negociee: bool) -> Self {
+        if !negociee || self.data.len() < COMPRESSION_THRESHOLD || self.is_compressed() {
+            return self;
+        }
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        match encoder.write_all(&self.data).and_then(|_| encoder.finish()) {
+            Ok(data) if data.len() < self.data.len() => Self::new(self.msg_type | FLAG_COMPRESSED, data),
+            _ => self,
+        }
+    }
+
+    // Décompresse un message marqué compressé, sans dépasser la taille d'une trame
+    pub fn decompress(&self) -> io::Result<Self> {
+        if !self.is_compressed() {
+            return Ok(Self::new(self.msg_type, self.data.clone()));
+        }
+        let mut data = Vec::new();
+        DeflateDecoder::new(self.data.as_slice())
+            .take(MAX_FRAME_SIZE as u64 + 1)
+            .read_to_end(&mut data)?;
+        if data.len() > MAX_FRAME_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Decompressed message too large"));
+        }
+        Ok(Self::new(self.msg_type & !FLAG_COMPRESSED, data))
+    }
+
     // Écrit un message sur un stream TCP
     pub async fn write_to<W: AsyncWriteExt + Unpin>(&self, writer: &mut W) -> io::Result<()> {
         let serialized = self.serialize();
@@ -115,40 +442,97 @@ impl ProtocolMessage {
 // Utilitaires pour les messages client
 impl ClientMessage {
     pub fn to_protocol_message(&self) -> anyhow::Result<ProtocolMessage> {
-        let data = serde_json::to_vec(self)?;
+        self.encode(Encoding::Json)
+    }
+
+    pub fn from_protocol_message(msg: &ProtocolMessage) -> anyhow::Result<Self> {
+        Self::decode(msg, Encoding::Json)
+    }
+
+    pub fn encode(&self, encoding: Encoding) -> anyhow::Result<ProtocolMessage> {
+        let data = encoding.to_vec(self)?;
         let msg_type = match self {
+            ClientMessage::Hello { .. } => MSG_HELLO,
             ClientMessage::Connect { .. } => MSG_CONNECT,
+            ClientMessage::Register { .. } => MSG_REGISTER,
+            ClientMessage::Resume { .. } => MSG_RESUME,
             ClientMessage::PublicMessage { .. } => MSG_PUBLIC_MESSAGE,
             ClientMessage::PrivateMessage { .. } => MSG_PRIVATE_MESSAGE,
+            ClientMessage::MarkRead { .. } => MSG_MARK_READ,
+            ClientMessage::Ping => MSG_PING,
+            ClientMessage::Pong => MSG_PONG,
             ClientMessage::ListUsers => MSG_LIST_USERS,
             ClientMessage::Disconnect => MSG_DISCONNECT,
+            ClientMessage::History { .. } => MSG_HISTORY,
+            ClientMessage::JoinRoom { .. } => MSG_JOIN_ROOM,
+            ClientMessage::LeaveRoom { .. } => MSG_LEAVE_ROOM,
+            ClientMessage::ListRooms => MSG_LIST_ROOMS,
+            ClientMessage::Kick { .. } => MSG_KICK,
+            ClientMessage::Ban { .. } => MSG_BAN,
+            ClientMessage::Unban { .. } => MSG_UNBAN,
+            ClientMessage::Mute { .. } => MSG_MUTE,
+            ClientMessage::FileOffer { .. } => MSG_FILE_OFFER,
+            ClientMessage::FileAccept { .. } => MSG_FILE_ACCEPT,
+            ClientMessage::FileReject { .. } => MSG_FILE_REJECT,
+            ClientMessage::FileChunk { .. } => MSG_FILE_CHUNK,
+            ClientMessage::FileAck { .. } => MSG_FILE_ACK,
+            ClientMessage::FileDone { .. } => MSG_FILE_DONE,
+            ClientMessage::FileCancel { .. } => MSG_FILE_CANCEL,
         };
         Ok(ProtocolMessage::new(msg_type, data))
     }
 
-    pub fn from_protocol_message(msg: &ProtocolMessage) -> anyhow::Result<Self> {
-        Ok(serde_json::from_slice(&msg.data)?)
+    pub fn decode(msg: &ProtocolMessage, encoding: Encoding) -> anyhow::Result<Self> {
+        encoding.from_slice(&msg.data)
     }
 }
 
 // Utilitaires pour les messages serveur
 impl ServerMessage {
     pub fn to_protocol_message(&self) -> anyhow::Result<ProtocolMessage> {
-        let data = serde_json::to_vec(self)?;
+        self.encode(Encoding::Json)
+    }
+
+    pub fn from_protocol_message(msg: &ProtocolMessage) -> anyhow::Result<Self> {
+        Self::decode(msg, Encoding::Json)
+    }
+
+    pub fn encode(&self, encoding: Encoding) -> anyhow::Result<ProtocolMessage> {
+        let data = encoding.to_vec(self)?;
         let msg_type = match self {
+            ServerMessage::Welcome { .. } => MSG_WELCOME,
             ServerMessage::ConnectResponse { .. } => MSG_CONNECT_RESPONSE,
             ServerMessage::MessageBroadcast { .. } => MSG_MESSAGE_BROADCAST,
             ServerMessage::PrivateMessageDelivery { .. } => MSG_PRIVATE_MESSAGE_DELIVERY,
+            ServerMessage::PrivateMessageStatus { .. } => MSG_PRIVATE_MESSAGE_STATUS,
+            ServerMessage::Ack { .. } => MSG_ACK,
+            ServerMessage::Ping => MSG_SERVER_PING,
+            ServerMessage::Pong => MSG_SERVER_PONG,
             ServerMessage::UserList { .. } => MSG_USER_LIST,
             ServerMessage::UserJoined { .. } => MSG_USER_JOINED,
             ServerMessage::UserLeft { .. } => MSG_USER_LEFT,
             ServerMessage::Error { .. } => MSG_ERROR,
+            ServerMessage::History { .. } => MSG_HISTORY_RESPONSE,
+            ServerMessage::RoomJoined { .. } => MSG_ROOM_JOINED,
+            ServerMessage::RoomLeft { .. } => MSG_ROOM_LEFT,
+            ServerMessage::RoomList { .. } => MSG_ROOM_LIST,
+            ServerMessage::Moderation { .. } => MSG_MODERATION,
+            ServerMessage::Kicked { .. } => MSG_KICKED,
+            ServerMessage::FileOffered { .. } => MSG_FILE_OFFERED,
+            ServerMessage::FileOfferSent { .. } => MSG_FILE_OFFER_SENT,
+            ServerMessage::FileAccepted { .. } => MSG_FILE_ACCEPTED,
+            ServerMessage::FileRejected { .. } => MSG_FILE_REJECTED,
+            ServerMessage::FileChunk { .. } => MSG_FILE_CHUNK_DELIVERY,
+            ServerMessage::FileAcked { .. } => MSG_FILE_ACKED,
+            ServerMessage::FileDone { .. } => MSG_FILE_DONE_STATUS,
+            ServerMessage::FileCancelled { .. } => MSG_FILE_CANCELLED,
+            ServerMessage::Motd { .. } => MSG_MOTD,
         };
         Ok(ProtocolMessage::new(msg_type, data))
     }
 
-    pub fn from_protocol_message(msg: &ProtocolMessage) -> anyhow::Result<Self> {
-        Ok(serde_json::from_slice(&msg.data)?)
+    pub fn decode(msg: &ProtocolMessage, encoding: Encoding) -> anyhow::Result<Self> {
+        encoding.from_slice(&msg.data)
     }
 }
 
@@ -161,6 +545,73 @@ pub enum ClientState {
 
 #[derive(Debug, Clone)]
 pub enum ServerClientState {
+    Handshake, // en attente du Hello
     WaitingAuth,
     Authenticated(String), // username
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_roundtrip_and_limits() {
+        let court = ProtocolMessage::new(MSG_PUBLIC_MESSAGE, b"salut".to_vec());
+        assert!(!court.compressed(true).is_compressed());
+
+        let data = "bonjour à tous ".repeat(100).into_bytes();
+        let compresse = ProtocolMessage::new(MSG_PUBLIC_MESSAGE, data.clone()).compressed(true);
+        assert!(compresse.is_compressed() && compresse.data.len() < data.len());
+        let lu = compresse.decompress().unwrap();
+        assert_eq!((lu.msg_type, lu.data), (MSG_PUBLIC_MESSAGE, data));
+
+        // Une bombe de décompression est refusée
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0u8; MAX_FRAME_SIZE + 1]).unwrap();
+        let bombe = ProtocolMessage::new(MSG_PUBLIC_MESSAGE | FLAG_COMPRESSED, encoder.finish().unwrap());
+        assert!(bombe.decompress().is_err());
+    }
+
+    #[test]
+    fn test_encodings_roundtrip() {
+        let messages = vec![
+            ServerMessage::MessageBroadcast {
+                id: 3,
+                timestamp: 1_700_000_000,
+                from: "alice".to_string(),
+                content: "é💬".to_string(),
+                room: DEFAULT_ROOM.to_string(),
+            },
+            ServerMessage::UserList { users: vec!["alice".to_string(), "bob".to_string()] },
+            ServerMessage::History {
+                room: DEFAULT_ROOM.to_string(),
+                messages: vec![HistoryEntry { id: 7, timestamp: 1_700_000_000, from: "bob".to_string(), content: "re".to_string(), room: DEFAULT_ROOM.to_string() }],
+            },
+        ];
+        for msg in messages {
+            let json = msg.encode(Encoding::Json).unwrap();
+            let msgpack = msg.encode(Encoding::MessagePack).unwrap();
+            assert_eq!(json.msg_type, msgpack.msg_type);
+            assert!(msgpack.data.len() < json.data.len());
+            let relu = ServerMessage::decode(&msgpack, Encoding::MessagePack).unwrap();
+            assert_eq!(format!("{:?}", relu), format!("{:?}", msg));
+        }
+
+        let list = ClientMessage::ListUsers.encode(Encoding::MessagePack).unwrap();
+        assert!(matches!(ClientMessage::decode(&list, Encoding::MessagePack).unwrap(), ClientMessage::ListUsers));
+        assert_eq!(Encoding::negotiate(&[CAP_ROOMS.to_string()]), Encoding::Json);
+    }
+
+    #[test]
+    fn test_full_file_chunk_fits_in_a_frame() {
+        // 0xFF : le pire cas en JSON (tableau de nombres à trois chiffres)
+        let data = vec![0xFF; transfer::CHUNK_SIZE];
+        let chunk = ServerMessage::FileChunk { transfer_id: u64::MAX, offset: u64::MAX, crc32: transfer::crc32(&data), data };
+        for encoding in [Encoding::Json, Encoding::MessagePack] {
+            let frame = chunk.encode(encoding).unwrap();
+            assert!(frame.serialize().len() <= MAX_FRAME_SIZE);
+            let relu = ServerMessage::decode(&frame, encoding).unwrap();
+            assert!(matches!(relu, ServerMessage::FileChunk { data, .. } if data.len() == transfer::CHUNK_SIZE));
+        }
+    }
+}
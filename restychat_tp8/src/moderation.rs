@@ -0,0 +1,225 @@
+// =============================
+// Modération
+// Bannissements par nom d'utilisateur ou par adresse IP, gardés dans un fichier :
+// { "users": { "mallory": { "by": "alice", "reason": "spam", "timestamp": 1700000000 } },
+//   "ips": { "203.0.113.7": { "by": "alice", "reason": null, "timestamp": 1700000000 } } }
+// Les silences (mute) sont temporaires et restent en mémoire.
+// =============================
+
+use crate::accounts::is_valid_username;
+use crate::history::unix_timestamp;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// Durée maximale d'un silence
+pub const MAX_MUTE_DURATION: Duration = Duration::from_secs(30 * 24 * 3600);
+
+// Ce que vise un bannissement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BanTarget {
+    User(String),
+    Ip(IpAddr),
+}
+
+impl BanTarget {
+    // "203.0.113.7" ou "::1" : une adresse ; sinon un nom d'utilisateur valide
+    pub fn parse(target: &str) -> Option<Self> {
+        if let Ok(ip) = target.parse() {
+            return Some(BanTarget::Ip(ip));
+        }
+        is_valid_username(target).then(|| BanTarget::User(target.to_string()))
+    }
+}
+
+impl fmt::Display for BanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BanTarget::User(username) => write!(f, "{}", username),
+            BanTarget::Ip(ip) => write!(f, "{}", ip),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub by: String, // opérateur à l'origine du bannissement
+    pub reason: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Bans {
+    #[serde(default)]
+    users: BTreeMap<String, Ban>,
+    #[serde(default)]
+    ips: BTreeMap<IpAddr, Ban>,
+}
+
+pub struct BanList {
+    path: Option<PathBuf>, // None : bannissements perdus à l'arrêt du serveur
+    bans: Bans,
+}
+
+impl BanList {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            bans: Bans::default(),
+        }
+    }
+
+    // Charge la liste (le fichier est créé au premier bannissement)
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bans = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(path)?)
+                .map_err(|e| anyhow!("Fichier des bannissements {} illisible: {}", path.display(), e))?
+        } else {
+            Bans::default()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            bans,
+        })
+    }
+
+    // Même principe que les comptes : fichier temporaire puis renommage
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let temporaire = path.with_extension("tmp");
+        fs::write(&temporaire, serde_json::to_vec_pretty(&self.bans)?)?;
+        fs::rename(&temporaire, path)?;
+        Ok(())
+    }
+
+    // Bannit (ou remplace le bannissement existant)
+    pub fn ban(&mut self, target: &BanTarget, by: &str, reason: Option<String>) -> Result<()> {
+        let ban = Ban { by: by.to_string(), reason, timestamp: unix_timestamp() };
+        let precedent = match target {
+            BanTarget::User(username) => self.bans.users.insert(username.clone(), ban),
+            BanTarget::Ip(ip) => self.bans.ips.insert(*ip, ban),
+        };
+        if let Err(e) = self.save() {
+            self.restore(target, precedent);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // Faux si la cible n'était pas bannie
+    pub fn unban(&mut self, target: &BanTarget) -> Result<bool> {
+        let precedent = match target {
+            BanTarget::User(username) => self.bans.users.remove(username),
+            BanTarget::Ip(ip) => self.bans.ips.remove(ip),
+        };
+        if precedent.is_none() {
+            return Ok(false);
+        }
+        if let Err(e) = self.save() {
+            self.restore(target, precedent);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn restore(&mut self, target: &BanTarget, precedent: Option<Ban>) {
+        match (target, precedent) {
+            (BanTarget::User(username), Some(ban)) => {
+                self.bans.users.insert(username.clone(), ban);
+            },
+            (BanTarget::User(username), None) => {
+                self.bans.users.remove(username);
+            },
+            (BanTarget::Ip(ip), Some(ban)) => {
+                self.bans.ips.insert(*ip, ban);
+            },
+            (BanTarget::Ip(ip), None) => {
+                self.bans.ips.remove(ip);
+            },
+        }
+    }
+
+    // Bannissement qui s'applique à cette connexion : l'adresse d'abord, puis le nom
+    pub fn find(&self, username: Option<&str>, ip: IpAddr) -> Option<&Ban> {
+        self.bans.ips.get(&ip).or_else(|| username.and_then(|username| self.bans.users.get(username)))
+    }
+}
+
+// Utilisateurs réduits au silence, jusqu'à une échéance
+#[derive(Default)]
+pub struct MuteList {
+    until: HashMap<String, Instant>,
+}
+
+impl MuteList {
+    pub fn mute(&mut self, username: &str, duration: Duration) {
+        let duration = duration.min(MAX_MUTE_DURATION);
+        self.until.insert(username.to_string(), Instant::now() + duration);
+    }
+
+    // Faux si l'utilisateur pouvait déjà parler
+    pub fn unmute(&mut self, username: &str) -> bool {
+        self.remaining(username).is_some() && self.until.remove(username).is_some()
+    }
+
+    // Temps de silence restant
+    pub fn remaining(&mut self, username: &str) -> Option<Duration> {
+        let maintenant = Instant::now();
+        self.until.retain(|_, fin| *fin > maintenant);
+        self.until.get(username).map(|fin| *fin - maintenant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bans_persist_by_name_and_address() {
+        let path = std::env::temp_dir().join(format!("restychat_bans_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        assert_eq!(BanTarget::parse("203.0.113.7"), Some(BanTarget::Ip(ip)));
+        assert_eq!(BanTarget::parse("mallory"), Some(BanTarget::User("mallory".to_string())));
+        assert_eq!(BanTarget::parse("pas un nom"), None);
+
+        {
+            let mut bans = BanList::open(&path).unwrap();
+            bans.ban(&BanTarget::User("mallory".to_string()), "alice", Some("spam".to_string())).unwrap();
+            bans.ban(&BanTarget::Ip(ip), "alice", None).unwrap();
+        }
+
+        let mut bans = BanList::open(&path).unwrap();
+        let autre: IpAddr = "198.51.100.1".parse().unwrap();
+        assert_eq!(bans.find(Some("mallory"), autre).and_then(|ban| ban.reason.as_deref()), Some("spam"));
+        assert!(bans.find(Some("bob"), ip).is_some());
+        assert!(bans.find(Some("bob"), autre).is_none());
+
+        assert!(bans.unban(&BanTarget::User("mallory".to_string())).unwrap());
+        assert!(!bans.unban(&BanTarget::User("mallory".to_string())).unwrap());
+        assert!(BanList::open(&path).unwrap().find(Some("mallory"), autre).is_none());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_mutes_expire() {
+        let mut mutes = MuteList::default();
+        mutes.mute("mallory", Duration::from_secs(60));
+        mutes.mute("eve", Duration::ZERO);
+        assert!(mutes.remaining("mallory").is_some());
+        assert!(mutes.remaining("eve").is_none());
+        assert!(mutes.unmute("mallory"));
+        assert!(!mutes.unmute("mallory"));
+    }
+}
@@ -0,0 +1,196 @@
+// =============================
+// Messages privés en attente
+// Un message privé destiné à un compte existant mais hors ligne est gardé ici
+// jusqu'à la prochaine connexion de son destinataire.
+// Fichier JSON : { "alice": [ { "id": 12, "from": "bob", "content": "salut", "timestamp": 1700000000 } ] }
+// =============================
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Au-delà, les nouveaux messages pour ce destinataire sont refusés
+pub const MAX_QUEUED_PER_USER: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    #[serde(default)] // fichiers écrits avant la numérotation des messages
+    pub id: u64,
+    pub from: String,
+    pub content: String,
+    pub timestamp: u64,
+}
+
+pub struct OfflineQueue {
+    path: Option<PathBuf>, // None : messages perdus à l'arrêt du serveur
+    pending: HashMap<String, Vec<QueuedMessage>>,
+    generation: u64,
+    written: Arc<Mutex<u64>>, // génération de la dernière photo écrite
+}
+
+// Contenu de la file préparé sous le verrou, écrit sur disque après l'avoir relâché
+pub struct OfflineSnapshot {
+    path: PathBuf,
+    contenu: Vec<u8>,
+    generation: u64,
+    written: Arc<Mutex<u64>>,
+}
+
+impl OfflineSnapshot {
+    // Écriture bloquante (à lancer avec spawn_blocking) : fichier temporaire puis
+    // renommage, comme les comptes. Une photo plus ancienne que la dernière écrite
+    // est ignorée, pour que le fichier ne revienne jamais en arrière
+    pub fn write(self) -> Result<()> {
+        let mut written = self.written.lock().map_err(|_| anyhow!("Verrou d'écriture empoisonné"))?;
+        if *written >= self.generation {
+            return Ok(());
+        }
+        let temporaire = self.path.with_extension("tmp");
+        fs::write(&temporaire, &self.contenu)?;
+        fs::rename(&temporaire, &self.path)?;
+        *written = self.generation;
+        Ok(())
+    }
+}
+
+impl OfflineQueue {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            pending: HashMap::new(),
+            generation: 0,
+            written: Arc::default(),
+        }
+    }
+
+    // Charge les messages en attente (le fichier est créé au premier message)
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let pending = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(path)?)
+                .map_err(|e| anyhow!("Fichier de messages en attente {} illisible: {}", path.display(), e))?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            pending,
+            generation: 0,
+            written: Arc::default(),
+        })
+    }
+
+    // Plus grand identifiant en attente, que la numérotation ne doit pas redonner
+    pub fn max_id(&self) -> Option<u64> {
+        self.pending.values().flatten().map(|message| message.id).max()
+    }
+
+    fn snapshot(&mut self) -> Result<Option<OfflineSnapshot>> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        let contenu = serde_json::to_vec_pretty(&self.pending)?;
+        self.generation += 1;
+        Ok(Some(OfflineSnapshot {
+            path: path.clone(),
+            contenu,
+            generation: self.generation,
+            written: self.written.clone(),
+        }))
+    }
+
+    // Le message est en attente dès le retour ; la photo renvoyée reste à écrire
+    pub fn push(&mut self, to: &str, message: QueuedMessage) -> Result<Option<OfflineSnapshot>> {
+        let file = self.pending.entry(to.to_string()).or_default();
+        if file.len() >= MAX_QUEUED_PER_USER {
+            return Err(anyhow!("La boîte de {} est pleine", to));
+        }
+        file.push(message);
+        match self.snapshot() {
+            Ok(snapshot) => Ok(snapshot),
+            Err(e) => {
+                if let Some(file) = self.pending.get_mut(to) {
+                    file.pop();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Retire et renvoie les messages en attente, du plus ancien au plus récent,
+    // avec la photo de la file sans eux
+    pub fn take(&mut self, username: &str) -> Result<(Vec<QueuedMessage>, Option<OfflineSnapshot>)> {
+        let Some(messages) = self.pending.remove(username) else {
+            return Ok((Vec::new(), None));
+        };
+        match self.snapshot() {
+            Ok(snapshot) => Ok((messages, snapshot)),
+            Err(e) => {
+                self.pending.insert(username.to_string(), messages);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: u64, from: &str) -> QueuedMessage {
+        QueuedMessage { id, from: from.to_string(), content: "salut".to_string(), timestamp: 1_700_000_000 }
+    }
+
+    fn save(snapshot: Option<OfflineSnapshot>) {
+        if let Some(snapshot) = snapshot {
+            snapshot.write().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_queue_survives_reopen_and_is_emptied_once() {
+        let path = std::env::temp_dir().join(format!("restychat_offline_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        {
+            let mut queue = OfflineQueue::open(&path).unwrap();
+            save(queue.push("alice", message(1, "bob")).unwrap());
+            save(queue.push("alice", message(2, "carol")).unwrap());
+        }
+
+        let mut queue = OfflineQueue::open(&path).unwrap();
+        let (messages, snapshot) = queue.take("alice").unwrap();
+        save(snapshot);
+        assert_eq!(messages.iter().map(|m| m.from.as_str()).collect::<Vec<_>>(), ["bob", "carol"]);
+        assert!(queue.take("alice").unwrap().0.is_empty());
+        assert!(OfflineQueue::open(&path).unwrap().take("alice").unwrap().0.is_empty());
+
+        for id in 0..MAX_QUEUED_PER_USER as u64 {
+            queue.push("dave", message(id, "bob")).unwrap();
+        }
+        assert!(queue.push("dave", message(0, "bob")).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_older_snapshot_never_overwrites_newer() {
+        let path = std::env::temp_dir().join(format!("restychat_offline_ordre_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut queue = OfflineQueue::open(&path).unwrap();
+        let ancienne = queue.push("alice", message(1, "bob")).unwrap();
+        let recente = queue.push("alice", message(2, "carol")).unwrap();
+        save(recente);
+        save(ancienne);
+
+        let (messages, _) = OfflineQueue::open(&path).unwrap().take("alice").unwrap();
+        assert_eq!(messages.iter().map(|m| m.id).collect::<Vec<_>>(), [1, 2]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,310 @@
+// =============================
+// Files d'envoi par client
+// Chaque connexion a sa propre file bornée, vidée par sa tâche d'écriture.
+// Quand un lecteur lent la laisse se remplir, la politique choisie décide :
+// - DropOldest : le plus ancien message est jeté (et compté)
+// - Disconnect : la file est vidée, une erreur explique la coupure, puis la connexion est fermée
+// - Block      : l'expéditeur attend une place, au plus block_timeout, puis le client est coupé
+// =============================
+
+use crate::ServerMessage;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+pub const DEFAULT_QUEUE_CAPACITY: usize = 100;
+pub const DEFAULT_BLOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    #[default]
+    DropOldest,
+    Disconnect,
+    Block,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop-oldest" => Ok(Self::DropOldest),
+            "disconnect" => Ok(Self::Disconnect),
+            "block" => Ok(Self::Block),
+            autre => Err(format!("Politique de file inconnue '{}' (drop-oldest, disconnect ou block)", autre)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueConfig {
+    pub capacity: usize,
+    pub policy: OverflowPolicy,
+    pub block_timeout: Duration, // attente maximale avec OverflowPolicy::Block
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            policy: OverflowPolicy::default(),
+            block_timeout: DEFAULT_BLOCK_TIMEOUT,
+        }
+    }
+}
+
+impl QueueConfig {
+    // RUSTCHAT_QUEUE_CAPACITY et RUSTCHAT_QUEUE_POLICY remplacent les valeurs par défaut
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(capacity) = std::env::var("RUSTCHAT_QUEUE_CAPACITY").ok().and_then(|v| v.parse().ok()) {
+            config.capacity = capacity;
+        }
+        if let Some(policy) = std::env::var("RUSTCHAT_QUEUE_POLICY").ok().and_then(|v| v.parse().ok()) {
+            config.policy = policy;
+        }
+        config
+    }
+}
+
+// Compteurs partagés par toutes les files du serveur
+#[derive(Debug, Default)]
+pub struct QueueMetrics {
+    dropped: AtomicU64,
+    disconnected: AtomicU64,
+    blocked: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueueStats {
+    pub dropped: u64,      // messages jetés, toutes causes confondues
+    pub disconnected: u64, // clients coupés pour file pleine
+    pub blocked: u64,      // envois qui ont dû attendre une place
+}
+
+impl QueueMetrics {
+    pub fn snapshot(&self) -> QueueStats {
+        QueueStats {
+            dropped: self.dropped.load(Ordering::Relaxed),
+            disconnected: self.disconnected.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    messages: VecDeque<ServerMessage>,
+    closed: bool,
+}
+
+#[derive(Debug)]
+pub struct ClientQueue {
+    config: QueueConfig,
+    state: Mutex<QueueState>,
+    readable: Notify, // un message est arrivé, ou la file est fermée
+    writable: Notify, // une place s'est libérée, ou la file est fermée
+    dropped: AtomicU64,
+    metrics: Arc<QueueMetrics>,
+}
+
+impl ClientQueue {
+    pub fn new(config: QueueConfig, metrics: Arc<QueueMetrics>) -> Self {
+        Self {
+            config,
+            state: Mutex::new(QueueState::default()),
+            readable: Notify::new(),
+            writable: Notify::new(),
+            dropped: AtomicU64::new(0),
+            metrics,
+        }
+    }
+
+    // Messages perdus par ce client
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    fn count_dropped(&self, n: u64) {
+        self.dropped.fetch_add(n, Ordering::Relaxed);
+        self.metrics.dropped.fetch_add(n, Ordering::Relaxed);
+    }
+
+    // Met le message en file ; false s'il a été refusé (file fermée ou client coupé)
+    pub async fn push(&self, message: ServerMessage) -> bool {
+        let mut a_attendu = false;
+        let mut message = Some(message);
+        loop {
+            let place_libre = self.writable.notified();
+            tokio::pin!(place_libre);
+            // Inscrit avant de regarder la file : un pop entre-temps ne sera pas manqué
+            place_libre.as_mut().enable();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                // Client en cours de départ : rien à compter
+                if state.closed {
+                    return false;
+                }
+                if state.messages.len() < self.config.capacity {
+                    state.messages.push_back(message.take().unwrap());
+                    drop(state);
+                    self.readable.notify_one();
+                    return true;
+                }
+
+                match self.config.policy {
+                    OverflowPolicy::DropOldest => {
+                        state.messages.pop_front();
+                        state.messages.push_back(message.take().unwrap());
+                        drop(state);
+                        self.count_dropped(1);
+                        self.readable.notify_one();
+                        return true;
+                    },
+                    OverflowPolicy::Disconnect => {
+                        drop(state);
+                        self.disconnect("File d'envoi pleine : connexion trop lente, déconnexion");
+                        self.count_dropped(1);
+                        return false;
+                    },
+                    OverflowPolicy::Block => {},
+                }
+            }
+
+            if !a_attendu {
+                a_attendu = true;
+                self.metrics.blocked.fetch_add(1, Ordering::Relaxed);
+            }
+            if tokio::time::timeout(self.config.block_timeout, place_libre).await.is_err() {
+                self.disconnect("File d'envoi bloquée trop longtemps : connexion trop lente, déconnexion");
+                self.count_dropped(1);
+                return false;
+            }
+        }
+    }
+
+    // Prochain message à écrire ; None une fois la file fermée et vidée
+    pub async fn pop(&self) -> Option<ServerMessage> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(message) = state.messages.pop_front() {
+                    drop(state);
+                    self.writable.notify_one();
+                    return Some(message);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            // Un seul lecteur par file : notify_one garde le signal s'il arrive avant l'attente
+            self.readable.notified().await;
+        }
+    }
+
+    // Plus aucun envoi accepté ; les messages déjà en file partent encore
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.readable.notify_one();
+        self.writable.notify_waiters();
+    }
+
+    // Jette la file, n'y laisse que l'explication puis ferme
+    fn disconnect(&self, reason: &str) {
+        let jetes = {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return;
+            }
+            let jetes = state.messages.len() as u64;
+            state.messages.clear();
+            state.messages.push_back(ServerMessage::Error { message: reason.to_string() });
+            state.closed = true;
+            jetes
+        };
+        self.count_dropped(jetes);
+        self.metrics.disconnected.fetch_add(1, Ordering::Relaxed);
+        self.readable.notify_one();
+        self.writable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(capacity: usize, policy: OverflowPolicy) -> (Arc<ClientQueue>, Arc<QueueMetrics>) {
+        let metrics = Arc::new(QueueMetrics::default());
+        let config = QueueConfig { capacity, policy, block_timeout: Duration::from_millis(100) };
+        (Arc::new(ClientQueue::new(config, metrics.clone())), metrics)
+    }
+
+    fn user(n: usize) -> ServerMessage {
+        ServerMessage::UserJoined { username: format!("user{}", n) }
+    }
+
+    fn username(message: Option<ServerMessage>) -> String {
+        match message {
+            Some(ServerMessage::UserJoined { username }) => username,
+            autre => panic!("Message inattendu: {:?}", autre),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_newest_messages() {
+        let (file, metrics) = queue(2, OverflowPolicy::DropOldest);
+        for n in 1..=5 {
+            assert!(file.push(user(n)).await);
+        }
+        file.close();
+        assert!(!file.push(user(6)).await);
+
+        assert_eq!(username(file.pop().await), "user4");
+        assert_eq!(username(file.pop().await), "user5");
+        assert!(file.pop().await.is_none());
+        assert_eq!(file.dropped(), 3);
+        assert_eq!(metrics.snapshot(), QueueStats { dropped: 3, disconnected: 0, blocked: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_leaves_only_the_reason() {
+        let (file, metrics) = queue(2, OverflowPolicy::Disconnect);
+        assert!(file.push(user(1)).await);
+        assert!(file.push(user(2)).await);
+        assert!(!file.push(user(3)).await);
+        assert!(file.is_closed());
+
+        assert!(matches!(file.pop().await, Some(ServerMessage::Error { message }) if message.contains("trop lente")));
+        assert!(file.pop().await.is_none());
+        assert_eq!(metrics.snapshot(), QueueStats { dropped: 3, disconnected: 1, blocked: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room_then_gives_up() {
+        let (file, metrics) = queue(1, OverflowPolicy::Block);
+        assert!(file.push(user(1)).await);
+
+        // Le lecteur libère une place : l'envoi en attente passe
+        let lecteur = file.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            lecteur.pop().await
+        });
+        assert!(file.push(user(2)).await);
+        assert_eq!(metrics.snapshot().blocked, 1);
+
+        // Plus personne ne lit : l'expéditeur abandonne et le client est coupé
+        assert!(!file.push(user(3)).await);
+        assert!(matches!(file.pop().await, Some(ServerMessage::Error { .. })));
+        assert!(file.pop().await.is_none());
+        assert_eq!(metrics.snapshot(), QueueStats { dropped: 2, disconnected: 1, blocked: 2 });
+    }
+}
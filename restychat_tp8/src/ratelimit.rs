@@ -0,0 +1,236 @@
+// =============================
+// Limitation de débit (anti-flood)
+// Seaux à jetons : un seau de capacité `burst` se remplit de `per_second` jetons
+// par seconde, chaque message ou connexion en consomme un.
+// - messages : un seau par client et un par adresse IP (toutes ses connexions)
+// - connexions : un seau par adresse IP, vérifié avant même la poignée de main
+// Un message refusé est une infraction : avertissements, puis silence temporaire,
+// puis déconnexion (voir Escalation).
+// =============================
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+// Au-delà, les adresses inactives sont oubliées
+const MAX_TRACKED_IPS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub burst: u32,       // rafale tolérée
+    pub per_second: f64,  // rythme soutenu
+}
+
+impl Rate {
+    pub const fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+// Réponse aux infractions successives d'un même client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Escalation {
+    pub warnings: u32,      // avertissements avant chaque silence
+    pub mute: Duration,     // durée du silence (zéro : pas de silence, déconnexion directe)
+    pub max_mutes: u32,     // silences avant la déconnexion
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    pub messages_per_client: Option<Rate>, // None : pas de limite
+    pub messages_per_ip: Option<Rate>,
+    pub connections_per_ip: Option<Rate>,
+    pub escalation: Escalation,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            messages_per_client: Some(Rate::new(10, 2.0)),
+            messages_per_ip: Some(Rate::new(30, 5.0)),
+            connections_per_ip: Some(Rate::new(10, 0.5)),
+            escalation: Escalation {
+                warnings: 2,
+                mute: Duration::from_secs(60),
+                max_mutes: 2,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: Rate,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: Rate, now: Instant) -> Self {
+        Self { rate, tokens: rate.burst as f64, last: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let ecoule = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + ecoule * self.rate.per_second).min(self.rate.burst as f64);
+        self.last = now;
+    }
+
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Plein : rien à retenir sur cette source
+    fn is_idle(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.rate.burst as f64
+    }
+}
+
+// Décision pour un message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Allow,
+    Warn { strike: u32, of: u32 }, // message refusé, avertissement n sur N
+    Mute(Duration),                // message refusé, auteur réduit au silence
+    Disconnect,                    // trop de récidives
+}
+
+#[derive(Default)]
+struct Offenses {
+    strikes: u32, // infractions depuis le dernier silence
+    mutes: u32,
+}
+
+struct ClientLimits {
+    messages: Option<TokenBucket>,
+    offenses: Offenses,
+}
+
+struct IpLimits {
+    messages: Option<TokenBucket>,
+    connections: Option<TokenBucket>,
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    clients: HashMap<u32, ClientLimits>,
+    ips: HashMap<IpAddr, IpLimits>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            clients: HashMap::new(),
+            ips: HashMap::new(),
+        }
+    }
+
+    fn ip(&mut self, ip: IpAddr, now: Instant) -> &mut IpLimits {
+        if self.ips.len() >= MAX_TRACKED_IPS && !self.ips.contains_key(&ip) {
+            self.ips.retain(|_, limits| {
+                limits.messages.as_mut().is_none_or(|bucket| bucket.is_idle(now))
+                    && limits.connections.as_mut().is_none_or(|bucket| bucket.is_idle(now))
+            });
+        }
+        let config = self.config;
+        self.ips.entry(ip).or_insert_with(|| IpLimits {
+            messages: config.messages_per_ip.map(|rate| TokenBucket::new(rate, now)),
+            connections: config.connections_per_ip.map(|rate| TokenBucket::new(rate, now)),
+        })
+    }
+
+    // Nouvelle connexion TCP depuis cette adresse
+    pub fn allow_connection(&mut self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        self.ip(ip, now).connections.as_mut().is_none_or(|bucket| bucket.try_take(now))
+    }
+
+    // Message de chat d'un client : les deux seaux doivent avoir un jeton
+    pub fn check_message(&mut self, client_id: u32, ip: IpAddr) -> Verdict {
+        let now = Instant::now();
+        let ip_ok = self.ip(ip, now).messages.as_mut().is_none_or(|bucket| bucket.try_take(now));
+
+        let config = self.config;
+        let client = self.clients.entry(client_id).or_insert_with(|| ClientLimits {
+            messages: config.messages_per_client.map(|rate| TokenBucket::new(rate, now)),
+            offenses: Offenses::default(),
+        });
+        let client_ok = client.messages.as_mut().is_none_or(|bucket| bucket.try_take(now));
+        if ip_ok && client_ok {
+            return Verdict::Allow;
+        }
+
+        let escalation = config.escalation;
+        let offenses = &mut client.offenses;
+        offenses.strikes += 1;
+        if offenses.strikes <= escalation.warnings {
+            return Verdict::Warn { strike: offenses.strikes, of: escalation.warnings };
+        }
+        offenses.strikes = 0;
+        offenses.mutes += 1;
+        if offenses.mutes > escalation.max_mutes || escalation.mute.is_zero() {
+            Verdict::Disconnect
+        } else {
+            Verdict::Mute(escalation.mute)
+        }
+    }
+
+    pub fn forget_client(&mut self, client_id: u32) {
+        self.clients.remove(&client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_refills() {
+        let debut = Instant::now();
+        let mut bucket = TokenBucket::new(Rate::new(3, 2.0), debut);
+        assert!((0..3).all(|_| bucket.try_take(debut)));
+        assert!(!bucket.try_take(debut));
+        assert!(bucket.try_take(debut + Duration::from_millis(500)));
+        assert!(!bucket.try_take(debut + Duration::from_millis(500)));
+        // Jamais plus que la rafale, même après une longue pause
+        let tard = debut + Duration::from_secs(60);
+        assert!((0..3).all(|_| bucket.try_take(tard)));
+        assert!(!bucket.try_take(tard));
+    }
+
+    #[test]
+    fn test_offenses_escalate_to_disconnect() {
+        let config = RateLimitConfig {
+            messages_per_client: Some(Rate::new(1, 0.001)),
+            messages_per_ip: None,
+            connections_per_ip: Some(Rate::new(1, 0.001)),
+            escalation: Escalation { warnings: 1, mute: Duration::from_secs(30), max_mutes: 1 },
+        };
+        let mut limiter = RateLimiter::new(config);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+
+        let verdicts: Vec<Verdict> = (0..6).map(|_| limiter.check_message(1, ip)).collect();
+        assert_eq!(verdicts, [
+            Verdict::Allow,
+            Verdict::Warn { strike: 1, of: 1 },
+            Verdict::Mute(Duration::from_secs(30)),
+            Verdict::Warn { strike: 1, of: 1 },
+            Verdict::Disconnect,
+            Verdict::Warn { strike: 1, of: 1 },
+        ]);
+        // Un autre client a son propre seau
+        assert_eq!(limiter.check_message(2, ip), Verdict::Allow);
+
+        assert!(limiter.allow_connection(ip));
+        assert!(!limiter.allow_connection(ip));
+        assert!(limiter.allow_connection("192.0.2.2".parse().unwrap()));
+    }
+}
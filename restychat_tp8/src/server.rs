@@ -1,15 +1,54 @@
+use crate::accounts::{
+    LOCKOUT_DURATION, MIN_PASSWORD_LEN, UserStore, dummy_password_hash, hash_password,
+    is_valid_username, verify_password,
+};
+use crate::history::{DEFAULT_HISTORY_LEN, MAX_HISTORY_LEN, MessageHistory, unix_timestamp};
+use crate::moderation::{BanList, BanTarget, MAX_MUTE_DURATION, MuteList};
+use crate::offline::{OfflineQueue, OfflineSnapshot, QueuedMessage};
+use crate::queue::{ClientQueue, QueueConfig, QueueMetrics, QueueStats};
+use crate::ratelimit::{RateLimitConfig, RateLimiter, Verdict};
+use crate::session::SessionStore;
+use crate::transfer::{CHUNK_SIZE, MAX_FILE_SIZE, is_valid_file_name, is_valid_sha256};
 use crate::{
-    ClientMessage, ProtocolMessage, ServerClientState, ServerMessage,
-    MSG_CONNECT, MSG_DISCONNECT, MSG_LIST_USERS, MSG_PRIVATE_MESSAGE, MSG_PUBLIC_MESSAGE,
+    CAP_COMPRESSION, CAP_HISTORY, CAP_MSGPACK, CAP_ROOMS, ClientMessage, DEFAULT_ROOM,
+    DeliveryStatus, Encoding, HeartbeatConfig, MIN_PROTOCOL_VERSION, MSG_BAN, MSG_CONNECT,
+    MSG_DISCONNECT, MSG_FILE_ACCEPT, MSG_FILE_ACK, MSG_FILE_CANCEL, MSG_FILE_CHUNK, MSG_FILE_DONE,
+    MSG_FILE_OFFER, MSG_FILE_REJECT, MSG_HELLO, MSG_HISTORY, MSG_JOIN_ROOM, MSG_KICK,
+    MSG_LEAVE_ROOM, MSG_LIST_ROOMS, MSG_LIST_USERS, MSG_MARK_READ, MSG_MUTE, MSG_PING, MSG_PONG,
+    MSG_PRIVATE_MESSAGE, MSG_PUBLIC_MESSAGE, MSG_REGISTER, MSG_RESUME, MSG_UNBAN, ModerationAction,
+    PROTOCOL_VERSION, ProtocolMessage, RoomInfo, ServerClientState, ServerMessage,
+    is_valid_room_name,
 };
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, RwLock};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, Notify, RwLock};
+use tokio_rustls::TlsAcceptor;
 use tracing::{error, info, warn};
 
+use crate::tls::ServerTlsConfig;
+
+// Délai maximal pour la poignée de main TLS
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Messages privés remis dont on attend encore l'accusé de lecture (les plus anciens sont oubliés)
+const MAX_AWAITING_READ: usize = 10_000;
+
+// Transferts de fichiers proposés ou en cours par un même expéditeur
+const MAX_TRANSFERS_PER_CLIENT: usize = 8;
+
+// Auteur des sanctions automatiques (anti-flood)
+const SERVER_NAME: &str = "serveur";
+
+// Capacités proposées par ce serveur
+const SERVER_CAPABILITIES: &[&str] = &[CAP_HISTORY, CAP_ROOMS, CAP_MSGPACK, CAP_COMPRESSION];
+
 type ClientId = u32;
 
 #[derive(Debug, Clone)]
@@ -17,37 +56,171 @@ pub struct ClientInfo {
     pub id: ClientId,
     pub addr: SocketAddr,
     pub state: ServerClientState,
-    pub sender: broadcast::Sender<ServerMessage>,
+    pub queue: Arc<ClientQueue>, // messages en attente d'écriture
+    pub rooms: HashSet<String>, // salons rejoints
+    pub capabilities: HashSet<String>, // négociées pendant la poignée de main
+    pub encoding: Encoding,            // des messages reçus après le Hello
+    pub session_token: Option<String>, // une fois authentifié
+    pub shutdown: Arc<Notify>,         // coupe la connexion sans attendre la fin de la file
+}
+
+// Transfert de fichier relayé : le serveur ne connaît que les deux connexions
+#[derive(Debug, Clone, Copy)]
+struct Transfer {
+    from: ClientId,
+    to: ClientId,
+    size: u64,
+    accepted: bool,
+}
+
+// Écrit la file des messages en attente hors des verrous, sans bloquer le runtime
+async fn save_offline(snapshot: Option<OfflineSnapshot>) -> Result<()> {
+    let Some(snapshot) = snapshot else {
+        return Ok(());
+    };
+    tokio::task::spawn_blocking(move || snapshot.write()).await?
+}
+
+// Configuration du serveur
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    pub history_path: Option<PathBuf>, // None : historique gardé en mémoire seulement
+    pub accounts_path: Option<PathBuf>, // None : comptes gardés en mémoire seulement
+    pub offline_path: Option<PathBuf>,  // None : messages privés en attente gardés en mémoire seulement
+    pub bans_path: Option<PathBuf>,     // None : bannissements gardés en mémoire seulement
+    pub operators: HashSet<String>,     // comptes autorisés à /kick, /ban et /mute
+    pub tls: Option<ServerTlsConfig>,   // None : TCP en clair
+    pub heartbeat: HeartbeatConfig,
+    pub queue: QueueConfig,
+    pub rate_limits: RateLimitConfig,
+    pub max_clients: Option<usize>,      // None : pas de limite de connexions simultanées
+    pub max_message_size: Option<usize>, // None : seule la taille de trame limite un message (octets)
+    pub motd: Option<String>,            // message du jour envoyé après l'identification
 }
 
 pub struct ChatServer {
     clients: Arc<RwLock<HashMap<ClientId, ClientInfo>>>,
     next_client_id: Arc<RwLock<ClientId>>,
-    broadcast_tx: broadcast::Sender<ServerMessage>,
+    history: Arc<Mutex<MessageHistory>>,
+    accounts: Arc<Mutex<UserStore>>,
+    offline: Arc<Mutex<OfflineQueue>>,
+    awaiting_read: Arc<Mutex<BTreeMap<u64, (String, String)>>>, // id -> (expéditeur, destinataire)
+    sessions: Arc<Mutex<SessionStore>>,
+    bans: Arc<Mutex<BanList>>,
+    mutes: Arc<Mutex<MuteList>>,
+    operators: Arc<HashSet<String>>,
+    limiter: Arc<Mutex<RateLimiter>>,
+    transfers: Arc<Mutex<HashMap<u64, Transfer>>>,
+    next_transfer_id: Arc<AtomicU64>,
+    tls: Option<TlsAcceptor>,
+    heartbeat: HeartbeatConfig,
+    queue: QueueConfig,
+    queue_metrics: Arc<QueueMetrics>,
+    max_clients: Option<usize>,
+    max_message_size: Option<usize>,
+    motd: Option<Arc<str>>,
+}
+
+impl Default for ChatServer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ChatServer {
     pub fn new() -> Self {
-        let (broadcast_tx, _) = broadcast::channel(1000);
+        Self::with_stores(MessageHistory::in_memory(), UserStore::in_memory(), OfflineQueue::in_memory())
+    }
+
+    pub fn with_config(config: ServerConfig) -> Result<Self> {
+        let history = match &config.history_path {
+            Some(path) => MessageHistory::open(path)?,
+            None => MessageHistory::in_memory(),
+        };
+        let accounts = match &config.accounts_path {
+            Some(path) => UserStore::open(path)?,
+            None => UserStore::in_memory(),
+        };
+        let offline = match &config.offline_path {
+            Some(path) => OfflineQueue::open(path)?,
+            None => OfflineQueue::in_memory(),
+        };
+        let mut server = Self::with_stores(history, accounts, offline);
+        if let Some(path) = &config.bans_path {
+            server.bans = Arc::new(Mutex::new(BanList::open(path)?));
+        }
+        server.operators = Arc::new(config.operators.clone());
+        server.limiter = Arc::new(Mutex::new(RateLimiter::new(config.rate_limits)));
+        server.heartbeat = config.heartbeat;
+        server.queue = config.queue;
+        server.max_clients = config.max_clients;
+        server.max_message_size = config.max_message_size;
+        server.motd = config.motd.as_deref().map(Arc::from);
+        if let Some(tls) = &config.tls {
+            server.tls = Some(tls.acceptor()?);
+        }
+        Ok(server)
+    }
 
+    fn with_stores(mut history: MessageHistory, accounts: UserStore, offline: OfflineQueue) -> Self {
+        // Les messages privés en attente ne sont pas dans l'historique
+        if let Some(id) = offline.max_id() {
+            history.reserve_through(id);
+        }
         Self {
             clients: Arc::new(RwLock::new(HashMap::new())),
             next_client_id: Arc::new(RwLock::new(1)),
-            broadcast_tx,
+            history: Arc::new(Mutex::new(history)),
+            accounts: Arc::new(Mutex::new(accounts)),
+            offline: Arc::new(Mutex::new(offline)),
+            awaiting_read: Arc::new(Mutex::new(BTreeMap::new())),
+            sessions: Arc::new(Mutex::new(SessionStore::default())),
+            bans: Arc::new(Mutex::new(BanList::in_memory())),
+            mutes: Arc::new(Mutex::new(MuteList::default())),
+            operators: Arc::new(HashSet::new()),
+            limiter: Arc::new(Mutex::new(RateLimiter::new(RateLimitConfig::default()))),
+            transfers: Arc::new(Mutex::new(HashMap::new())),
+            next_transfer_id: Arc::new(AtomicU64::new(1)),
+            tls: None,
+            heartbeat: HeartbeatConfig::default(),
+            queue: QueueConfig::default(),
+            queue_metrics: Arc::new(QueueMetrics::default()),
+            max_clients: None,
+            max_message_size: None,
+            motd: None,
         }
     }
 
     pub async fn start(&self, addr: &str) -> Result<()> {
         let listener = TcpListener::bind(addr).await?;
-        info!("🚀 Serveur RustChat démarré sur {}", addr);
+        info!("🚀 Serveur RustChat démarré sur {}{}", addr, if self.tls.is_some() { " (TLS)" } else { "" });
+        self.serve(listener).await
+    }
 
+    // Accepte les connexions sur un listener déjà ouvert
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
         loop {
             match listener.accept().await {
                 Ok((stream, addr)) => {
+                    // Trop de connexions depuis cette adresse : fermée sans un mot, avant TLS
+                    if !self.limiter.lock().await.allow_connection(addr.ip()) {
+                        warn!("🚧 Connexion de {} refusée (trop de tentatives)", addr);
+                        continue;
+                    }
                     info!("📱 Nouvelle connexion de {}", addr);
                     let server = self.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = server.handle_client(stream, addr).await {
+                        let resultat = match &server.tls {
+                            Some(acceptor) => {
+                                match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                                    Ok(Ok(tls_stream)) => server.handle_client(tls_stream, addr).await,
+                                    Ok(Err(e)) => Err(anyhow::anyhow!("Poignée de main TLS refusée: {}", e)),
+                                    Err(_) => Err(anyhow::anyhow!("Poignée de main TLS trop lente")),
+                                }
+                            }
+                            None => server.handle_client(stream, addr).await,
+                        };
+                        if let Err(e) = resultat {
                             error!("❌ Erreur avec client {}: {}", addr, e);
                         }
                     });
@@ -59,7 +232,11 @@ impl ChatServer {
         }
     }
 
-    async fn handle_client(&self, stream: TcpStream, addr: SocketAddr) -> Result<()> {
+    // Générique : TcpStream en clair ou flux TLS
+    async fn handle_client<S>(&self, mut stream: S, addr: SocketAddr) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
         let client_id = {
             let mut next_id = self.next_client_id.write().await;
             let id = *next_id;
@@ -67,58 +244,95 @@ impl ChatServer {
             id
         };
 
-        let (client_tx, mut client_rx) = broadcast::channel(100);
+        let client_queue = Arc::new(ClientQueue::new(self.queue, self.queue_metrics.clone()));
+        let shutdown = Arc::new(Notify::new());
 
         let client_info = ClientInfo {
             id: client_id,
             addr,
-            state: ServerClientState::WaitingAuth,
-            sender: client_tx,
+            state: ServerClientState::Handshake,
+            queue: client_queue.clone(),
+            rooms: HashSet::new(),
+            capabilities: HashSet::new(),
+            encoding: Encoding::Json,
+            session_token: None,
+            shutdown: shutdown.clone(),
         };
 
-        {
+        // Compté et inscrit sous le même verrou : deux connexions simultanées ne dépassent pas la limite
+        let plein = {
             let mut clients = self.clients.write().await;
-            clients.insert(client_id, client_info);
+            let plein = self.max_clients.filter(|max| clients.len() >= *max);
+            if plein.is_none() {
+                clients.insert(client_id, client_info);
+            }
+            plein
+        };
+        if let Some(max) = plein {
+            warn!("🈵 Connexion de {} refusée : serveur plein ({} connexions)", addr, max);
+            let message = format!("Serveur plein ({} connexions), réessayez plus tard", max);
+            ServerMessage::Error { message }.to_protocol_message()?.write_to(&mut stream).await?;
+            return Ok(());
         }
 
-        let (mut reader, mut writer) = stream.into_split();
+        let (mut reader, mut writer) = tokio::io::split(stream);
 
         // Task pour envoyer les messages au client
-        let send_task = tokio::spawn(async move {
-            while let Ok(msg) = client_rx.recv().await {
-                if let Ok(protocol_msg) = msg.to_protocol_message() {
-                    if protocol_msg.write_to(&mut writer).await.is_err() {
-                        break;
-                    }
+        let mut send_task = tokio::spawn(async move {
+            let mut encoding = Encoding::Json;
+            let mut compression = false;
+            while let Some(msg) = client_queue.pop().await {
+                if let Ok(protocol_msg) = msg.encode(encoding)
+                    && protocol_msg.compressed(compression).write_to(&mut writer).await.is_err()
+                {
+                    break;
+                }
+                // Le Welcome part en JSON non compressé, la suite selon la négociation
+                if let ServerMessage::Welcome { capabilities, .. } = &msg {
+                    encoding = Encoding::negotiate(capabilities);
+                    compression = capabilities.iter().any(|cap| cap == CAP_COMPRESSION);
                 }
             }
         });
 
+        // Battements manqués d'affilée, remis à zéro par tout message reçu
+        let missed = Arc::new(AtomicU32::new(0));
+
         // Task pour recevoir les messages du client
         let server_clone = self.clone();
-        let receive_task = tokio::spawn(async move {
-            loop {
-                match ProtocolMessage::read_from(&mut reader).await {
-                    Ok(msg) => {
-                        if server_clone.process_client_message(client_id, &msg).await.is_err() {
-                            break;
-                        }
+        let missed_clone = missed.clone();
+        let mut receive_task = tokio::spawn(async move {
+            while let Ok(msg) = ProtocolMessage::read_from(&mut reader).await {
+                missed_clone.store(0, Ordering::Relaxed);
+                if server_clone.process_client_message(client_id, &msg).await.is_err() {
+                    break;
+                }
 
-                        // Déconnexion explicite
-                        if msg.msg_type == MSG_DISCONNECT {
-                            break;
-                        }
-                    },
-                    Err(_) => break,
+                // Déconnexion explicite
+                if msg.msg_type == MSG_DISCONNECT {
+                    break;
                 }
             }
         });
 
+        // Task de battement de cœur : une connexion à moitié ouverte finit par être coupée
+        let server_clone = self.clone();
+        let mut heartbeat_task = tokio::spawn(async move {
+            server_clone.run_heartbeat(client_id, &missed).await;
+        });
+
         // Attendre que l'une des tâches se termine
         tokio::select! {
-            _ = send_task => {},
-            _ = receive_task => {},
+            _ = &mut send_task => {},
+            _ = &mut receive_task => {},
+            _ = &mut heartbeat_task => {},
+            _ = shutdown.notified() => {},
         }
+        // La lecture d'un client muet ne finirait jamais seule ; l'écriture vers une
+        // connexion remplacée peut rester bloquée
+        send_task.abort();
+        receive_task.abort();
+        heartbeat_task.abort();
 
         // Nettoyage
         self.remove_client(client_id).await;
@@ -128,28 +342,137 @@ impl ChatServer {
     }
 
     async fn process_client_message(&self, client_id: ClientId, msg: &ProtocolMessage) -> Result<()> {
+        let (en_poignee_de_main, encoding, compression) = {
+            let clients = self.clients.read().await;
+            match clients.get(&client_id) {
+                Some(client) => (
+                    matches!(client.state, ServerClientState::Handshake),
+                    client.encoding,
+                    client.capabilities.contains(CAP_COMPRESSION),
+                ),
+                None => (false, Encoding::Json, false),
+            }
+        };
+        if en_poignee_de_main {
+            return self.handle_hello(client_id, msg).await;
+        }
+
+        let decompresse;
+        let msg = if msg.is_compressed() {
+            if !compression {
+                self.send_error(client_id, &format!("Capacité '{}' non négociée pendant la poignée de main", CAP_COMPRESSION)).await;
+                return Ok(());
+            }
+            decompresse = msg.decompress()?;
+            &decompresse
+        } else {
+            msg
+        };
+
         match msg.msg_type {
+            MSG_HELLO => {
+                self.send_error(client_id, "Poignée de main déjà effectuée").await;
+            },
             MSG_CONNECT => {
-                let client_msg = ClientMessage::from_protocol_message(msg)?;
-                if let ClientMessage::Connect { username } = client_msg {
-                    self.handle_connect(client_id, username).await?;
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::Connect { username, password } = client_msg {
+                    self.handle_connect(client_id, username, password).await?;
+                }
+            },
+            MSG_REGISTER => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::Register { username, password } = client_msg {
+                    self.handle_register(client_id, username, password).await?;
+                }
+            },
+            MSG_RESUME => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::Resume { token, last_seen_id } = client_msg {
+                    self.handle_resume(client_id, token, last_seen_id).await?;
                 }
             },
             MSG_PUBLIC_MESSAGE => {
-                let client_msg = ClientMessage::from_protocol_message(msg)?;
-                if let ClientMessage::PublicMessage { content } = client_msg {
-                    self.handle_public_message(client_id, content).await?;
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::PublicMessage { content, room, client_ref } = client_msg {
+                    self.handle_public_message(client_id, room, content, client_ref).await?;
                 }
             },
             MSG_PRIVATE_MESSAGE => {
-                let client_msg = ClientMessage::from_protocol_message(msg)?;
-                if let ClientMessage::PrivateMessage { to, content } = client_msg {
-                    self.handle_private_message(client_id, to, content).await?;
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::PrivateMessage { to, content, client_ref } = client_msg {
+                    self.handle_private_message(client_id, to, content, client_ref).await?;
+                }
+            },
+            MSG_MARK_READ => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::MarkRead { id } = client_msg {
+                    self.handle_mark_read(client_id, id).await?;
                 }
             },
             MSG_LIST_USERS => {
                 self.handle_list_users(client_id).await?;
             },
+            MSG_HISTORY => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::History { limit, room } = client_msg {
+                    self.handle_history(client_id, room, limit).await?;
+                }
+            },
+            MSG_JOIN_ROOM => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::JoinRoom { room } = client_msg {
+                    self.handle_join_room(client_id, room).await?;
+                }
+            },
+            MSG_LEAVE_ROOM => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::LeaveRoom { room } = client_msg {
+                    self.handle_leave_room(client_id, room).await?;
+                }
+            },
+            MSG_LIST_ROOMS => {
+                self.handle_list_rooms(client_id).await?;
+            },
+            MSG_KICK => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::Kick { username, reason } = client_msg {
+                    self.handle_kick(client_id, username, reason).await?;
+                }
+            },
+            MSG_BAN => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::Ban { target, reason } = client_msg {
+                    self.handle_ban(client_id, target, reason).await?;
+                }
+            },
+            MSG_UNBAN => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::Unban { target } = client_msg {
+                    self.handle_unban(client_id, target).await?;
+                }
+            },
+            MSG_MUTE => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::Mute { username, seconds, reason } = client_msg {
+                    self.handle_mute(client_id, username, seconds, reason).await?;
+                }
+            },
+            MSG_FILE_OFFER => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                if let ClientMessage::FileOffer { to, name, size, sha256, client_ref } = client_msg {
+                    self.handle_file_offer(client_id, to, name, size, sha256, client_ref).await?;
+                }
+            },
+            MSG_FILE_ACCEPT | MSG_FILE_REJECT | MSG_FILE_CHUNK | MSG_FILE_ACK | MSG_FILE_DONE | MSG_FILE_CANCEL => {
+                let client_msg = ClientMessage::decode(msg, encoding)?;
+                self.relay_file_message(client_id, client_msg).await;
+            },
+            MSG_PING => {
+                self.send_to_client(client_id, ServerMessage::Pong).await;
+            },
+            MSG_PONG => {
+                // Signe de vie déjà compté à la réception
+            },
             MSG_DISCONNECT => {
                 info!("👋 Déconnexion explicite du client {}", client_id);
             },
@@ -160,109 +483,479 @@ impl ChatServer {
         Ok(())
     }
 
-    async fn handle_connect(&self, client_id: ClientId, username: String) -> Result<()> {
-        // Vérifier si le nom d'utilisateur est déjà pris
-        let is_taken = {
-            let clients = self.clients.read().await;
-            clients.values().any(|client| {
-                matches!(client.state, ServerClientState::Authenticated(ref name) if name == &username)
-            })
+    // Envoie Ping à chaque intervalle ; rend la main quand le client a manqué trop de battements
+    async fn run_heartbeat(&self, client_id: ClientId, missed: &AtomicU32) {
+        if self.heartbeat.interval.is_zero() {
+            return std::future::pending().await;
+        }
+
+        let mut intervalle = tokio::time::interval(self.heartbeat.interval);
+        intervalle.tick().await; // le premier tick est immédiat
+        loop {
+            intervalle.tick().await;
+            let manques = missed.fetch_add(1, Ordering::Relaxed);
+            if manques >= self.heartbeat.max_missed {
+                warn!("💔 Client {} muet depuis {} battements, déconnexion", client_id, manques);
+                return;
+            }
+            // Pas de Ping avant la poignée de main : le client attend le Welcome
+            if !self.is_in_handshake(client_id).await {
+                self.send_to_client(client_id, ServerMessage::Ping).await;
+            }
+        }
+    }
+
+    async fn is_in_handshake(&self, client_id: ClientId) -> bool {
+        let clients = self.clients.read().await;
+        clients.get(&client_id).is_some_and(|client| matches!(client.state, ServerClientState::Handshake))
+    }
+
+    // Premier message obligatoire : vérifie la version et choisit les capacités communes.
+    // Toute erreur ferme la connexion (l'Error est envoyé avant la fermeture).
+    async fn handle_hello(&self, client_id: ClientId, msg: &ProtocolMessage) -> Result<()> {
+        let hello = match msg.msg_type {
+            MSG_HELLO => ClientMessage::from_protocol_message(msg).ok(),
+            _ => None,
         };
+        let Some(ClientMessage::Hello { version, capabilities }) = hello else {
+            let message = format!("Poignée de main attendue: envoyez Hello (protocole v{})", PROTOCOL_VERSION);
+            self.send_error(client_id, &message).await;
+            return Err(anyhow::anyhow!("Client {} sans poignée de main", client_id));
+        };
+
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+            let message = format!(
+                "Version de protocole {} non supportée (serveur: v{} à v{})",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            );
+            self.send_error(client_id, &message).await;
+            return Err(anyhow::anyhow!("Client {} en protocole v{}", client_id, version));
+        }
 
-        let response = if is_taken {
-            ServerMessage::ConnectResponse {
-                success: false,
-                message: format!("Nom d'utilisateur '{}' déjà pris", username),
+        let communes: Vec<String> = capabilities.into_iter()
+            .filter(|cap| SERVER_CAPABILITIES.contains(&cap.as_str()))
+            .collect();
+        {
+            let mut clients = self.clients.write().await;
+            if let Some(client) = clients.get_mut(&client_id) {
+                client.state = ServerClientState::WaitingAuth;
+                client.capabilities = communes.iter().cloned().collect();
+                client.encoding = Encoding::negotiate(&communes);
             }
-        } else {
-            // Mettre à jour l'état du client
-            {
-                let mut clients = self.clients.write().await;
-                if let Some(client) = clients.get_mut(&client_id) {
-                    client.state = ServerClientState::Authenticated(username.clone());
-                }
+        }
+
+        info!("🤝 Client {} en protocole v{} (capacités: {:?})", client_id, version, communes);
+        self.send_to_client(client_id, ServerMessage::Welcome { version, capabilities: communes }).await;
+        Ok(())
+    }
+
+    async fn handle_connect(&self, client_id: ClientId, username: String, password: String) -> Result<()> {
+        if self.is_authenticated(client_id).await {
+            self.send_error(client_id, "Vous êtes déjà connecté").await;
+            return Ok(());
+        }
+        if !is_valid_username(&username) {
+            self.send_connect_failure(client_id, "Nom invalide (1 à 32 lettres, chiffres, '-' ou '_')".to_string()).await;
+            return Ok(());
+        }
+        if self.refuse_if_banned(client_id, &username).await {
+            return Ok(());
+        }
+
+        // Le verrou des comptes est relâché avant de répondre : send_connect_failure
+        // prend celui des clients, qui ne doit jamais être attendu sous un autre verrou
+        let password_hash = {
+            let accounts = self.accounts.lock().await;
+            match accounts.locked_for(&username) {
+                Some(reste) => Err(format!("Compte verrouillé après trop d'échecs, réessayez dans {} s", reste.as_secs() + 1)),
+                None => Ok(accounts.password_hash(&username)),
+            }
+        };
+        let password_hash = match password_hash {
+            Ok(password_hash) => password_hash,
+            Err(message) => {
+                self.send_connect_failure(client_id, message).await;
+                return Ok(());
+            }
+        };
+
+        // Argon2 est volontairement lent : on le sort du runtime async. Un nom sans
+        // compte est vérifié contre un hash factice pour répondre dans le même temps
+        let valide = tokio::task::spawn_blocking(move || match password_hash {
+            Some(hash) => verify_password(&password, &hash),
+            None => {
+                verify_password(&password, dummy_password_hash());
+                false
             }
+        })
+        .await?;
+
+        if !valide {
+            let restants = self.accounts.lock().await.record_failure(&username);
+            warn!("🔒 Échec de connexion pour '{}' (client {})", username, client_id);
+            let message = if restants == 0 {
+                format!("Identifiants invalides, compte verrouillé {} minutes", LOCKOUT_DURATION.as_secs() / 60)
+            } else {
+                "Nom d'utilisateur ou mot de passe incorrect".to_string()
+            };
+            self.send_connect_failure(client_id, message).await;
+            return Ok(());
+        }
+
+        self.accounts.lock().await.record_success(&username);
+        if self.complete_login(client_id, username, HashSet::from([DEFAULT_ROOM.to_string()])).await {
+            self.send_motd(client_id).await;
+        }
+        Ok(())
+    }
+
+    async fn handle_register(&self, client_id: ClientId, username: String, password: String) -> Result<()> {
+        if self.is_authenticated(client_id).await {
+            self.send_error(client_id, "Vous êtes déjà connecté").await;
+            return Ok(());
+        }
+        if self.refuse_if_banned(client_id, &username).await {
+            return Ok(());
+        }
+        if !is_valid_username(&username) {
+            self.send_connect_failure(client_id, "Nom invalide (1 à 32 lettres, chiffres, '-' ou '_')".to_string()).await;
+            return Ok(());
+        }
+        if password.chars().count() < MIN_PASSWORD_LEN {
+            let message = format!("Le mot de passe doit faire au moins {} caractères", MIN_PASSWORD_LEN);
+            self.send_connect_failure(client_id, message).await;
+            return Ok(());
+        }
+        if self.accounts.lock().await.contains(&username) {
+            self.send_connect_failure(client_id, format!("Le nom '{}' est déjà enregistré", username)).await;
+            return Ok(());
+        }
+
+        let password_hash = tokio::task::spawn_blocking(move || hash_password(&password)).await??;
+        // Un autre client a pu enregistrer le même nom pendant le hachage : register le refuse
+        let enregistrement = self.accounts.lock().await.register(&username, password_hash);
+        if let Err(e) = enregistrement {
+            self.send_connect_failure(client_id, e.to_string()).await;
+            return Ok(());
+        }
+
+        info!("🆕 Compte '{}' créé", username);
+        if self.complete_login(client_id, username, HashSet::from([DEFAULT_ROOM.to_string()])).await {
+            self.send_motd(client_id).await;
+        }
+        Ok(())
+    }
+
+    // Reprise après une coupure : le jeton remplace le mot de passe, les salons sont
+    // restaurés et les messages publics manqués renvoyés. Les messages privés arrivés
+    // entre-temps attendent dans la file hors ligne.
+    async fn handle_resume(&self, client_id: ClientId, token: String, last_seen_id: Option<u64>) -> Result<()> {
+        if self.is_authenticated(client_id).await {
+            self.send_error(client_id, "Vous êtes déjà connecté").await;
+            return Ok(());
+        }
 
-            // Notifier les autres clients
-            let notification = ServerMessage::UserJoined {
-                username: username.clone(),
+        let active = self.sessions.lock().await.get(&token).map(|session| session.is_active());
+        if active == Some(true) {
+            // Le serveur n'a pas encore vu la coupure : l'ancienne connexion est fermée
+            let ancienne = {
+                let clients = self.clients.read().await;
+                clients.values()
+                    .find(|client| client.session_token.as_deref() == Some(token.as_str()))
+                    .map(|client| (client.id, client.shutdown.clone()))
             };
-            self.broadcast_to_authenticated(notification, Some(client_id)).await;
+            if let Some((ancienne, shutdown)) = ancienne {
+                info!("🔁 Client {} remplacé par sa reprise de session", ancienne);
+                self.remove_client(ancienne).await;
+                shutdown.notify_one();
+            }
+        }
+
+        let Some(session) = self.sessions.lock().await.take(&token) else {
+            self.send_connect_failure(client_id, "Session expirée, reconnectez-vous avec votre mot de passe".to_string()).await;
+            return Ok(());
+        };
+
+        if self.refuse_if_banned(client_id, &session.username).await {
+            return Ok(());
+        }
+        let rooms = session.rooms.clone();
+        if !self.complete_login(client_id, session.username.clone(), session.rooms).await {
+            return Ok(());
+        }
+        info!("🔁 Session de '{}' reprise", session.username);
 
-            info!("✅ Utilisateur '{}' connecté", username);
+        // Les messages manqués ne sont rejoués qu'avec l'historique négocié
+        let Some(after_id) = last_seen_id else {
+            return Ok(());
+        };
+        if !self.has_capability(client_id, CAP_HISTORY).await {
+            return Ok(());
+        }
+        let mut rooms: Vec<String> = rooms.into_iter().collect();
+        rooms.sort();
+        for room in rooms {
+            let messages = self.history.lock().await.since(&room, after_id, MAX_HISTORY_LEN);
+            if !messages.is_empty() {
+                self.send_to_client(client_id, ServerMessage::History { room, messages }).await;
+            }
+        }
+        Ok(())
+    }
 
-            ServerMessage::ConnectResponse {
-                success: true,
-                message: format!("Bienvenue, {}!", username),
+    // Identifiants vérifiés : passe le client en Authenticated dans `rooms` (sauf si le
+    // compte est déjà en ligne) et lui délivre un jeton de session. Vrai si connecté.
+    async fn complete_login(&self, client_id: ClientId, username: String, rooms: HashSet<String>) -> bool {
+        let session_token = {
+            let mut clients = self.clients.write().await;
+            let is_taken = clients.values().any(|client| {
+                matches!(client.state, ServerClientState::Authenticated(ref name) if name == &username)
+            });
+            match clients.get_mut(&client_id) {
+                Some(client) if !is_taken => {
+                    let token = self.sessions.lock().await.open(&username, rooms.clone());
+                    client.state = ServerClientState::Authenticated(username.clone());
+                    client.rooms = rooms;
+                    client.session_token = Some(token.clone());
+                    Some(token)
+                }
+                _ => None,
             }
         };
 
+        let Some(session_token) = session_token else {
+            self.send_connect_failure(client_id, format!("'{}' est déjà connecté ailleurs", username)).await;
+            return false;
+        };
+
+        // Notifier les autres clients
+        let notification = ServerMessage::UserJoined {
+            username: username.clone(),
+        };
+        self.broadcast_to_authenticated(notification, Some(client_id)).await;
+
+        info!("✅ Utilisateur '{}' connecté", username);
+
+        let response = ServerMessage::ConnectResponse {
+            success: true,
+            message: format!("Bienvenue, {}!", username),
+            session_token: Some(session_token),
+        };
         self.send_to_client(client_id, response).await;
-        Ok(())
+
+        self.deliver_offline_messages(client_id, &username).await;
+        true
     }
 
-    async fn handle_public_message(&self, client_id: ClientId, content: String) -> Result<()> {
-        let username = {
-            let clients = self.clients.read().await;
-            if let Some(client) = clients.get(&client_id) {
-                if let ServerClientState::Authenticated(ref username) = client.state {
-                    username.clone()
-                } else {
-                    self.send_error(client_id, "Vous devez être connecté pour envoyer des messages").await;
-                    return Ok(());
+    // Remet les messages privés reçus pendant l'absence et prévient leurs expéditeurs connectés
+    async fn deliver_offline_messages(&self, client_id: ClientId, username: &str) {
+        let retrait = self.offline.lock().await.take(username);
+        let messages = match retrait {
+            Ok((messages, snapshot)) => {
+                if let Err(e) = save_offline(snapshot).await {
+                    error!("❌ Messages en attente non sauvegardés: {}", e);
+                }
+                messages
+            }
+            Err(e) => {
+                error!("❌ Messages en attente de {} illisibles: {}", username, e);
+                return;
+            }
+        };
+        if messages.is_empty() {
+            return;
+        }
+
+        info!("📬 {} message(s) en attente remis à {}", messages.len(), username);
+        for queued in messages {
+            self.await_read_receipt(queued.id, &queued.from, username).await;
+            if let Some(sender_id) = self.find_client_by_username(&queued.from).await {
+                let status = ServerMessage::PrivateMessageStatus {
+                    id: queued.id,
+                    to: username.to_string(),
+                    status: DeliveryStatus::Delivered,
+                };
+                self.send_to_client(sender_id, status).await;
+            }
+            let message = ServerMessage::PrivateMessageDelivery {
+                id: queued.id,
+                timestamp: queued.timestamp,
+                from: queued.from,
+                content: queued.content,
+                offline: true,
+            };
+            self.send_to_client(client_id, message).await;
+        }
+    }
+
+    async fn find_client_by_username(&self, username: &str) -> Option<ClientId> {
+        let clients = self.clients.read().await;
+        clients.iter()
+            .find(|(_, client)| matches!(client.state, ServerClientState::Authenticated(ref name) if name == username))
+            .map(|(id, _)| *id)
+    }
+
+    // Pas après une reprise de session : l'utilisateur l'a déjà vu
+    async fn send_motd(&self, client_id: ClientId) {
+        if let Some(motd) = &self.motd {
+            self.send_to_client(client_id, ServerMessage::Motd { message: motd.to_string() }).await;
+        }
+    }
+
+    async fn send_connect_failure(&self, client_id: ClientId, message: String) {
+        self.send_to_client(client_id, ServerMessage::ConnectResponse { success: false, message, session_token: None }).await;
+    }
+
+    async fn handle_public_message(&self, client_id: ClientId, room: String, content: String, client_ref: Option<u64>) -> Result<()> {
+        let Some(username) = self.authenticated_username(client_id).await else {
+            return Ok(());
+        };
+        if self.refuse_if_muted(client_id, &username).await
+            || self.refuse_if_flooding(client_id, &username).await
+            || self.refuse_if_too_long(client_id, &content).await
+        {
+            return Ok(());
+        }
+        if room != DEFAULT_ROOM && self.refuse_without_capability(client_id, CAP_ROOMS).await {
+            return Ok(());
+        }
+        if !self.is_member(client_id, &room).await {
+            self.send_error(client_id, &format!("Vous n'êtes pas dans le salon {}", room)).await;
+            return Ok(());
+        }
+
+        // Un échec d'écriture ne doit pas empêcher la diffusion
+        let (id, timestamp) = {
+            let mut history = self.history.lock().await;
+            match history.append(&room, &username, &content) {
+                Ok(entry) => (entry.id, entry.timestamp),
+                Err(e) => {
+                    error!("❌ Impossible d'archiver le message de {}: {}", username, e);
+                    (history.next_id(), unix_timestamp())
                 }
-            } else {
-                return Ok(());
             }
         };
 
         let message = ServerMessage::MessageBroadcast {
+            id,
+            timestamp,
             from: username.clone(),
             content,
+            room: room.clone(),
         };
 
-        info!("💬 Message public de {}: {:?}", username, message);
-        self.broadcast_to_authenticated(message, None).await;
+        info!("💬 Message public de {} dans {}: {:?}", username, room, message);
+        self.send_to_client(client_id, ServerMessage::Ack { id, timestamp, client_ref }).await;
+        self.broadcast_to_room(&room, message).await;
         Ok(())
     }
 
-    async fn handle_private_message(&self, client_id: ClientId, to: String, content: String) -> Result<()> {
-        let from_username = {
-            let clients = self.clients.read().await;
-            if let Some(client) = clients.get(&client_id) {
-                if let ServerClientState::Authenticated(ref username) = client.state {
-                    username.clone()
-                } else {
-                    self.send_error(client_id, "Vous devez être connecté pour envoyer des messages").await;
-                    return Ok(());
-                }
-            } else {
-                return Ok(());
-            }
+    async fn handle_private_message(&self, client_id: ClientId, to: String, content: String, client_ref: Option<u64>) -> Result<()> {
+        let Some(from_username) = self.authenticated_username(client_id).await else {
+            return Ok(());
         };
+        if self.refuse_if_muted(client_id, &from_username).await
+            || self.refuse_if_flooding(client_id, &from_username).await
+            || self.refuse_if_too_long(client_id, &content).await
+        {
+            return Ok(());
+        }
 
-        // Trouver le destinataire
-        let target_client_id = {
+        // Seule la file du destinataire sort du verrou des clients : aucun autre
+        // verrou n'est attendu sous celui-ci
+        let target = {
             let clients = self.clients.read().await;
-            clients.iter()
-                .find(|(_, client)| {
-                    matches!(client.state, ServerClientState::Authenticated(ref username) if username == &to)
-                })
-                .map(|(id, _)| *id)
+            clients.values()
+                .find(|client| matches!(client.state, ServerClientState::Authenticated(ref username) if username == &to))
+                .map(|client| client.queue.clone())
         };
+        let connu = target.is_some() || self.accounts.lock().await.contains(&to);
+        if !connu {
+            self.send_error(client_id, &format!("Utilisateur '{}' introuvable", to)).await;
+            return Ok(());
+        }
 
-        if let Some(target_id) = target_client_id {
+        let id = self.history.lock().await.next_id();
+        let timestamp = unix_timestamp();
+
+        let status = if let Some(queue) = target {
             let message = ServerMessage::PrivateMessageDelivery {
+                id,
+                timestamp,
                 from: from_username.clone(),
                 content,
+                offline: false,
             };
 
-            info!("📨 Message privé de {} vers {}", from_username, to);
-            self.send_to_client(target_id, message).await;
+            info!("📨 Message privé #{} de {} vers {}", id, from_username, to);
+            self.await_read_receipt(id, &from_username, &to).await;
+            queue.push(message).await;
+            DeliveryStatus::Delivered
         } else {
-            self.send_error(client_id, &format!("Utilisateur '{}' introuvable", to)).await;
+            let queued = QueuedMessage { id, from: from_username.clone(), content, timestamp };
+            let snapshot = self.offline.lock().await.push(&to, queued);
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    self.send_error(client_id, &format!("Message pour {} non enregistré: {}", to, e)).await;
+                    return Ok(());
+                }
+            };
+            if let Err(e) = save_offline(snapshot).await {
+                error!("❌ Messages en attente non sauvegardés: {}", e);
+                self.send_error(client_id, &format!("Message pour {} gardé en mémoire seulement: {}", to, e)).await;
+            }
+            info!("📭 Message privé #{} de {} mis en attente pour {}", id, from_username, to);
+            DeliveryStatus::Queued
+        };
+
+        self.send_to_client(client_id, ServerMessage::Ack { id, timestamp, client_ref }).await;
+        self.send_to_client(client_id, ServerMessage::PrivateMessageStatus { id, to: to.clone(), status }).await;
+
+        // Un destinataire connecté entre la recherche et la mise en attente a déjà
+        // vidé sa boîte : le message lui est remis maintenant (take ne le rend qu'une fois)
+        if status == DeliveryStatus::Queued
+            && let Some(target_id) = self.find_client_by_username(&to).await
+        {
+            self.deliver_offline_messages(target_id, &to).await;
+        }
+        Ok(())
+    }
+
+    // Retient l'expéditeur d'un message privé remis, pour lui transmettre l'accusé de lecture
+    async fn await_read_receipt(&self, id: u64, from: &str, to: &str) {
+        let mut awaiting_read = self.awaiting_read.lock().await;
+        awaiting_read.insert(id, (from.to_string(), to.to_string()));
+        while awaiting_read.len() > MAX_AWAITING_READ {
+            awaiting_read.pop_first();
         }
+    }
 
+    // Seul le destinataire d'un message peut le marquer comme lu, une seule fois
+    async fn handle_mark_read(&self, client_id: ClientId, id: u64) -> Result<()> {
+        let Some(username) = self.authenticated_username(client_id).await else {
+            return Ok(());
+        };
+
+        let sender = {
+            let mut awaiting_read = self.awaiting_read.lock().await;
+            match awaiting_read.get(&id) {
+                Some((from, to)) if to == &username => {
+                    let from = from.clone();
+                    awaiting_read.remove(&id);
+                    Some(from)
+                }
+                _ => None,
+            }
+        };
+
+        // L'accusé est perdu si l'expéditeur s'est déconnecté entre-temps
+        if let Some(sender) = sender
+            && let Some(sender_id) = self.find_client_by_username(&sender).await
+        {
+            let status = ServerMessage::PrivateMessageStatus { id, to: username, status: DeliveryStatus::Read };
+            self.send_to_client(sender_id, status).await;
+        }
         Ok(())
     }
 
@@ -285,39 +978,572 @@ impl ChatServer {
         Ok(())
     }
 
-    async fn send_to_client(&self, client_id: ClientId, message: ServerMessage) {
-        let clients = self.clients.read().await;
-        if let Some(client) = clients.get(&client_id) {
-            let _ = client.sender.send(message);
+    async fn handle_history(&self, client_id: ClientId, room: String, limit: Option<usize>) -> Result<()> {
+        if self.authenticated_username(client_id).await.is_none() || self.refuse_without_capability(client_id, CAP_HISTORY).await {
+            return Ok(());
         }
+        if !self.is_member(client_id, &room).await {
+            self.send_error(client_id, &format!("Rejoignez {} pour consulter son historique", room)).await;
+            return Ok(());
+        }
+
+        let limit = limit.unwrap_or(DEFAULT_HISTORY_LEN).min(MAX_HISTORY_LEN);
+        let messages = self.history.lock().await.last(&room, limit);
+        self.send_to_client(client_id, ServerMessage::History { room, messages }).await;
+        Ok(())
     }
 
-    async fn send_error(&self, client_id: ClientId, error_message: &str) {
-        let error = ServerMessage::Error {
-            message: error_message.to_string(),
+    async fn handle_join_room(&self, client_id: ClientId, room: String) -> Result<()> {
+        let Some(username) = self.authenticated_username(client_id).await else {
+            return Ok(());
         };
-        self.send_to_client(client_id, error).await;
-    }
+        if self.refuse_without_capability(client_id, CAP_ROOMS).await {
+            return Ok(());
+        }
+        if !is_valid_room_name(&room) {
+            self.send_error(client_id, &format!("Nom de salon invalide: '{}' (ex: #projet-x)", room)).await;
+            return Ok(());
+        }
 
-    async fn broadcast_to_authenticated(&self, message: ServerMessage, exclude_client: Option<ClientId>) {
-        let clients = self.clients.read().await;
-        for (id, client) in clients.iter() {
-            if let Some(exclude_id) = exclude_client {
-                if *id == exclude_id {
-                    continue;
-                }
-            }
+        let nouveau = {
+            let mut clients = self.clients.write().await;
+            clients.get_mut(&client_id).is_some_and(|client| client.rooms.insert(room.clone()))
+        };
 
-            if matches!(client.state, ServerClientState::Authenticated(_)) {
-                let _ = client.sender.send(message.clone());
-            }
+        let notification = ServerMessage::RoomJoined { room: room.clone(), username: username.clone() };
+        if nouveau {
+            info!("🚪 {} rejoint {}", username, room);
+            self.broadcast_to_room(&room, notification).await;
+        } else {
+            // Déjà membre : on confirme seulement au demandeur
+            self.send_to_client(client_id, notification).await;
         }
+        Ok(())
     }
 
-    async fn remove_client(&self, client_id: ClientId) {
-        let username = {
-            let mut clients = self.clients.write().await;
+    async fn handle_leave_room(&self, client_id: ClientId, room: String) -> Result<()> {
+        let Some(username) = self.authenticated_username(client_id).await else {
+            return Ok(());
+        };
+        if self.refuse_without_capability(client_id, CAP_ROOMS).await {
+            return Ok(());
+        }
+
+        let etait_membre = {
+            let mut clients = self.clients.write().await;
+            clients.get_mut(&client_id).is_some_and(|client| client.rooms.remove(&room))
+        };
+        if !etait_membre {
+            self.send_error(client_id, &format!("Vous n'êtes pas dans le salon {}", room)).await;
+            return Ok(());
+        }
+
+        info!("🚪 {} quitte {}", username, room);
+        let notification = ServerMessage::RoomLeft { room: room.clone(), username };
+        self.send_to_client(client_id, notification.clone()).await;
+        self.broadcast_to_room(&room, notification).await;
+        Ok(())
+    }
+
+    async fn handle_list_rooms(&self, client_id: ClientId) -> Result<()> {
+        if self.authenticated_username(client_id).await.is_none() || self.refuse_without_capability(client_id, CAP_ROOMS).await {
+            return Ok(());
+        }
+
+        let rooms = {
+            let clients = self.clients.read().await;
+            let mut membres: BTreeMap<String, usize> = BTreeMap::from([(DEFAULT_ROOM.to_string(), 0)]);
+            for room in clients.values().flat_map(|client| client.rooms.iter()) {
+                *membres.entry(room.clone()).or_default() += 1;
+            }
+            membres.into_iter().map(|(name, members)| RoomInfo { name, members }).collect()
+        };
+
+        self.send_to_client(client_id, ServerMessage::RoomList { rooms }).await;
+        Ok(())
+    }
+
+    // Nom du client s'il est authentifié ; sinon, lui envoie une erreur
+    // Vrai (et connexion refusée) si le nom ou l'adresse du client est banni
+    async fn refuse_if_banned(&self, client_id: ClientId, username: &str) -> bool {
+        let Some(addr) = self.client_addr(client_id).await else {
+            return true;
+        };
+        let message = {
+            let bans = self.bans.lock().await;
+            bans.find(Some(username), addr.ip()).map(|ban| match &ban.reason {
+                Some(reason) => format!("Vous êtes banni de ce serveur ({})", reason),
+                None => "Vous êtes banni de ce serveur".to_string(),
+            })
+        };
+        let Some(message) = message else {
+            return false;
+        };
+        warn!("🚫 Connexion refusée à '{}' depuis {} (banni)", username, addr);
+        self.send_connect_failure(client_id, message).await;
+        true
+    }
+
+    async fn has_capability(&self, client_id: ClientId, capability: &str) -> bool {
+        let clients = self.clients.read().await;
+        clients.get(&client_id).is_some_and(|client| client.capabilities.contains(capability))
+    }
+
+    // Vrai (et erreur envoyée) si la capacité n'a pas été négociée pendant la poignée de main
+    async fn refuse_without_capability(&self, client_id: ClientId, capability: &str) -> bool {
+        if self.has_capability(client_id, capability).await {
+            return false;
+        }
+        self.send_error(client_id, &format!("Capacité '{}' non négociée pendant la poignée de main", capability)).await;
+        true
+    }
+
+    async fn refuse_if_muted(&self, client_id: ClientId, username: &str) -> bool {
+        let reste = self.mutes.lock().await.remaining(username);
+        if let Some(reste) = reste {
+            self.send_error(client_id, &format!("Vous êtes réduit au silence encore {} s", reste.as_secs() + 1)).await;
+        }
+        reste.is_some()
+    }
+
+    // Consomme un jeton ; sinon avertit, réduit au silence ou déconnecte selon les récidives
+    async fn refuse_if_flooding(&self, client_id: ClientId, username: &str) -> bool {
+        let Some(addr) = self.client_addr(client_id).await else {
+            return true;
+        };
+        let verdict = self.limiter.lock().await.check_message(client_id, addr.ip());
+        let target = BanTarget::User(username.to_string());
+        let reason = Some("flood".to_string());
+        match verdict {
+            Verdict::Allow => return false,
+            Verdict::Warn { strike, of } => {
+                let message = format!("Trop de messages, ralentissez (avertissement {}/{})", strike, of);
+                self.send_error(client_id, &message).await;
+            },
+            Verdict::Mute(duree) => {
+                warn!("🚧 {} réduit au silence {} s pour flood", username, duree.as_secs());
+                self.mutes.lock().await.mute(username, duree);
+                self.announce(client_id, ModerationAction::Mute, &target, SERVER_NAME, reason, Some(duree.as_secs())).await;
+            },
+            Verdict::Disconnect => {
+                warn!("🚧 {} déconnecté pour flood", username);
+                self.eject(&[client_id], SERVER_NAME, &reason, false).await;
+                self.announce(client_id, ModerationAction::Kick, &target, SERVER_NAME, reason, None).await;
+            },
+        }
+        true
+    }
+
+    // Vrai (et le client prévenu) si le contenu dépasse max_message_size
+    async fn refuse_if_too_long(&self, client_id: ClientId, content: &str) -> bool {
+        match self.max_message_size {
+            Some(max) if content.len() > max => {
+                let message = format!("Message trop long ({} octets, maximum {})", content.len(), max);
+                self.send_error(client_id, &message).await;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // Nom de l'opérateur, ou None (après une erreur) si le client n'en est pas un
+    async fn operator_username(&self, client_id: ClientId) -> Option<String> {
+        let username = self.authenticated_username(client_id).await?;
+        if self.operators.contains(&username) {
+            return Some(username);
+        }
+        self.send_error(client_id, "Commande réservée aux opérateurs").await;
+        None
+    }
+
+    // Ferme ces connexions après leur avoir dit pourquoi ; leur session ne peut pas être reprise
+    async fn eject(&self, client_ids: &[ClientId], by: &str, reason: &Option<String>, banned: bool) {
+        let cibles: Vec<(Arc<ClientQueue>, Option<String>)> = {
+            let clients = self.clients.read().await;
+            client_ids.iter()
+                .filter_map(|id| clients.get(id))
+                .map(|client| (client.queue.clone(), client.session_token.clone()))
+                .collect()
+        };
+        for (queue, token) in cibles {
+            if let Some(token) = token {
+                self.sessions.lock().await.take(&token);
+            }
+            queue.push(ServerMessage::Kicked { by: by.to_string(), reason: reason.clone(), banned }).await;
+            queue.close();
+        }
+    }
+
+    // Une action sur un nom est annoncée à tous ; sur une adresse IP, à l'opérateur seulement
+    async fn announce(
+        &self,
+        client_id: ClientId,
+        action: ModerationAction,
+        target: &BanTarget,
+        by: &str,
+        reason: Option<String>,
+        seconds: Option<u64>,
+    ) {
+        let notice = ServerMessage::Moderation { action, target: target.to_string(), by: by.to_string(), reason, seconds };
+        match target {
+            BanTarget::User(_) => self.broadcast_to_authenticated(notice, None).await,
+            BanTarget::Ip(_) => self.send_to_client(client_id, notice).await,
+        }
+    }
+
+    async fn handle_kick(&self, client_id: ClientId, username: String, reason: Option<String>) -> Result<()> {
+        let Some(operator) = self.operator_username(client_id).await else {
+            return Ok(());
+        };
+        if username == operator {
+            self.send_error(client_id, "Vous ne pouvez pas vous viser vous-même").await;
+            return Ok(());
+        }
+        let Some(target_id) = self.find_client_by_username(&username).await else {
+            self.send_error(client_id, &format!("Utilisateur '{}' non connecté", username)).await;
+            return Ok(());
+        };
+
+        warn!("👢 {} expulsé par {}", username, operator);
+        self.eject(&[target_id], &operator, &reason, false).await;
+        self.announce(client_id, ModerationAction::Kick, &BanTarget::User(username), &operator, reason, None).await;
+        Ok(())
+    }
+
+    async fn handle_ban(&self, client_id: ClientId, target: String, reason: Option<String>) -> Result<()> {
+        let Some(operator) = self.operator_username(client_id).await else {
+            return Ok(());
+        };
+        let Some(target) = BanTarget::parse(&target) else {
+            self.send_error(client_id, &format!("Cible invalide '{}' : nom d'utilisateur ou adresse IP", target)).await;
+            return Ok(());
+        };
+        let soi_meme = match &target {
+            BanTarget::User(username) => username == &operator,
+            BanTarget::Ip(ip) => self.client_addr(client_id).await.is_some_and(|addr| addr.ip() == *ip),
+        };
+        if soi_meme {
+            self.send_error(client_id, "Vous ne pouvez pas vous viser vous-même").await;
+            return Ok(());
+        }
+
+        if let Err(e) = self.bans.lock().await.ban(&target, &operator, reason.clone()) {
+            error!("❌ Bannissement de {} non enregistré: {}", target, e);
+            self.send_error(client_id, &format!("Bannissement non enregistré: {}", e)).await;
+            return Ok(());
+        }
+        warn!("🚫 {} banni par {}", target, operator);
+
+        // Toutes les connexions visées sont fermées, identifiées ou non
+        let cibles: Vec<ClientId> = {
+            let clients = self.clients.read().await;
+            clients.values()
+                .filter(|client| match &target {
+                    BanTarget::User(username) => matches!(client.state, ServerClientState::Authenticated(ref name) if name == username),
+                    BanTarget::Ip(ip) => client.addr.ip() == *ip,
+                })
+                .map(|client| client.id)
+                .collect()
+        };
+        self.eject(&cibles, &operator, &reason, true).await;
+        self.announce(client_id, ModerationAction::Ban, &target, &operator, reason, None).await;
+        Ok(())
+    }
+
+    async fn handle_unban(&self, client_id: ClientId, target: String) -> Result<()> {
+        let Some(operator) = self.operator_username(client_id).await else {
+            return Ok(());
+        };
+        let Some(target) = BanTarget::parse(&target) else {
+            self.send_error(client_id, &format!("Cible invalide '{}' : nom d'utilisateur ou adresse IP", target)).await;
+            return Ok(());
+        };
+
+        let resultat = self.bans.lock().await.unban(&target);
+        match resultat {
+            Ok(true) => {
+                info!("✅ {} débanni par {}", target, operator);
+                self.announce(client_id, ModerationAction::Unban, &target, &operator, None, None).await;
+            },
+            Ok(false) => self.send_error(client_id, &format!("{} n'est pas banni", target)).await,
+            Err(e) => self.send_error(client_id, &format!("Levée du bannissement non enregistrée: {}", e)).await,
+        }
+        Ok(())
+    }
+
+    async fn handle_mute(&self, client_id: ClientId, username: String, seconds: u64, reason: Option<String>) -> Result<()> {
+        let Some(operator) = self.operator_username(client_id).await else {
+            return Ok(());
+        };
+        if !is_valid_username(&username) {
+            self.send_error(client_id, &format!("Nom invalide '{}'", username)).await;
+            return Ok(());
+        }
+        if username == operator {
+            self.send_error(client_id, "Vous ne pouvez pas vous viser vous-même").await;
+            return Ok(());
+        }
+
+        let target = BanTarget::User(username.clone());
+        if seconds == 0 {
+            if !self.mutes.lock().await.unmute(&username) {
+                self.send_error(client_id, &format!("{} n'est pas réduit au silence", username)).await;
+                return Ok(());
+            }
+            info!("🔊 {} peut de nouveau parler ({})", username, operator);
+            self.announce(client_id, ModerationAction::Unmute, &target, &operator, reason, None).await;
+        } else {
+            // Le silence vaut aussi hors ligne : il suit le nom, pas la connexion
+            let duree = Duration::from_secs(seconds).min(MAX_MUTE_DURATION);
+            self.mutes.lock().await.mute(&username, duree);
+            info!("🔇 {} réduit au silence {} s par {}", username, duree.as_secs(), operator);
+            self.announce(client_id, ModerationAction::Mute, &target, &operator, reason, Some(duree.as_secs())).await;
+        }
+        Ok(())
+    }
+
+    async fn handle_file_offer(
+        &self,
+        client_id: ClientId,
+        to: String,
+        name: String,
+        size: u64,
+        sha256: String,
+        client_ref: u64,
+    ) -> Result<()> {
+        let Some(from) = self.authenticated_username(client_id).await else {
+            return Ok(());
+        };
+        if self.refuse_if_muted(client_id, &from).await || self.refuse_if_flooding(client_id, &from).await {
+            return Ok(());
+        }
+        if !is_valid_file_name(&name) || !is_valid_sha256(&sha256) {
+            self.send_error(client_id, "Offre de fichier invalide (nom ou empreinte SHA-256)").await;
+            return Ok(());
+        }
+        if size > MAX_FILE_SIZE {
+            self.send_error(client_id, &format!("Fichier trop gros (maximum {} Mo)", MAX_FILE_SIZE / (1024 * 1024))).await;
+            return Ok(());
+        }
+        if to == from {
+            self.send_error(client_id, "Impossible de s'envoyer un fichier à soi-même").await;
+            return Ok(());
+        }
+        let Some(target_id) = self.find_client_by_username(&to).await else {
+            self.send_error(client_id, &format!("Utilisateur '{}' non connecté", to)).await;
+            return Ok(());
+        };
+
+        let transfer_id = {
+            let mut transfers = self.transfers.lock().await;
+            if transfers.values().filter(|transfer| transfer.from == client_id).count() >= MAX_TRANSFERS_PER_CLIENT {
+                drop(transfers);
+                self.send_error(client_id, &format!("Déjà {} transferts en cours", MAX_TRANSFERS_PER_CLIENT)).await;
+                return Ok(());
+            }
+            let transfer_id = self.next_transfer_id.fetch_add(1, Ordering::Relaxed);
+            transfers.insert(transfer_id, Transfer { from: client_id, to: target_id, size, accepted: false });
+            transfer_id
+        };
+
+        info!("📎 Transfert #{} : {} propose {} ({} octets) à {}", transfer_id, from, name, size, to);
+        self.send_to_client(client_id, ServerMessage::FileOfferSent { transfer_id, client_ref }).await;
+        self.send_to_client(target_id, ServerMessage::FileOffered { transfer_id, from, name, size, sha256 }).await;
+        Ok(())
+    }
+
+    // Suite d'un transfert : vérifie qui parle et à quelle étape, puis relaie à l'autre extrémité
+    async fn relay_file_message(&self, client_id: ClientId, msg: ClientMessage) {
+        let transfer_id = match &msg {
+            ClientMessage::FileAccept { transfer_id, .. }
+            | ClientMessage::FileReject { transfer_id, .. }
+            | ClientMessage::FileChunk { transfer_id, .. }
+            | ClientMessage::FileAck { transfer_id, .. }
+            | ClientMessage::FileDone { transfer_id, .. }
+            | ClientMessage::FileCancel { transfer_id, .. } => *transfer_id,
+            _ => return,
+        };
+
+        // Les blocs et leurs accusés suivent le débit du transfert ; les autres messages
+        // de contrôle arrivent chez le pair comme un message de chat et comptent pareil
+        if !matches!(msg, ClientMessage::FileChunk { .. } | ClientMessage::FileAck { .. }) {
+            let Some(username) = self.authenticated_username(client_id).await else {
+                return;
+            };
+            if self.refuse_if_flooding(client_id, &username).await {
+                return;
+            }
+        }
+
+        let relais = {
+            let mut transfers = self.transfers.lock().await;
+            let Some(transfer) = transfers.get(&transfer_id).copied() else {
+                drop(transfers);
+                self.send_error(client_id, &format!("Transfert #{} inconnu ou terminé", transfer_id)).await;
+                return;
+            };
+            let (expediteur, destinataire) = (client_id == transfer.from, client_id == transfer.to);
+
+            // (destinataire du relais, message, transfert terminé)
+            let relais = match msg {
+                ClientMessage::FileAccept { offset, .. } if destinataire && !transfer.accepted && offset <= transfer.size => {
+                    Some((transfer.from, ServerMessage::FileAccepted { transfer_id, offset }, false))
+                },
+                ClientMessage::FileReject { reason, .. } if destinataire && !transfer.accepted => {
+                    Some((transfer.from, ServerMessage::FileRejected { transfer_id, reason }, true))
+                },
+                ClientMessage::FileChunk { offset, data, crc32, .. }
+                    if expediteur && transfer.accepted && data.len() <= CHUNK_SIZE
+                        && offset.checked_add(data.len() as u64).is_some_and(|fin| fin <= transfer.size) =>
+                {
+                    Some((transfer.to, ServerMessage::FileChunk { transfer_id, offset, data, crc32 }, false))
+                },
+                ClientMessage::FileAck { next_offset, .. } if destinataire && transfer.accepted => {
+                    Some((transfer.from, ServerMessage::FileAcked { transfer_id, next_offset }, false))
+                },
+                ClientMessage::FileDone { ok, .. } if destinataire && transfer.accepted => {
+                    Some((transfer.from, ServerMessage::FileDone { transfer_id, ok }, true))
+                },
+                ClientMessage::FileCancel { reason, .. } if expediteur || destinataire => {
+                    let autre = if expediteur { transfer.to } else { transfer.from };
+                    Some((autre, ServerMessage::FileCancelled { transfer_id, reason }, true))
+                },
+                _ => None,
+            };
+
+            match &relais {
+                Some((_, _, true)) => {
+                    transfers.remove(&transfer_id);
+                },
+                // Seule l'acceptation change l'état d'un transfert qui continue
+                Some((_, _, false)) => {
+                    if let Some(transfer) = transfers.get_mut(&transfer_id) {
+                        transfer.accepted = true;
+                    }
+                },
+                None => {},
+            }
+            relais
+        };
+
+        match relais {
+            Some((vers, message, _)) => self.send_to_client(vers, message).await,
+            None => self.send_error(client_id, &format!("Message inattendu pour le transfert #{}", transfer_id)).await,
+        }
+    }
+
+    // Une extrémité est partie : l'autre est prévenue, l'expéditeur pourra reproposer le fichier
+    async fn cancel_transfers(&self, client_id: ClientId) {
+        let interrompus: Vec<(u64, ClientId)> = {
+            let mut transfers = self.transfers.lock().await;
+            let ids: Vec<u64> = transfers.iter()
+                .filter(|(_, transfer)| transfer.from == client_id || transfer.to == client_id)
+                .map(|(id, _)| *id)
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| transfers.remove(&id).map(|transfer| (id, transfer)))
+                .map(|(id, transfer)| (id, if transfer.from == client_id { transfer.to } else { transfer.from }))
+                .collect()
+        };
+        for (transfer_id, autre) in interrompus {
+            let reason = Some("Correspondant déconnecté".to_string());
+            self.send_to_client(autre, ServerMessage::FileCancelled { transfer_id, reason }).await;
+        }
+    }
+
+    async fn client_addr(&self, client_id: ClientId) -> Option<SocketAddr> {
+        let clients = self.clients.read().await;
+        clients.get(&client_id).map(|client| client.addr)
+    }
+
+    async fn authenticated_username(&self, client_id: ClientId) -> Option<String> {
+        let username = {
+            let clients = self.clients.read().await;
+            match clients.get(&client_id).map(|client| &client.state) {
+                Some(ServerClientState::Authenticated(username)) => Some(username.clone()),
+                _ => None,
+            }
+        };
+        if username.is_none() {
+            self.send_error(client_id, "Vous devez être connecté pour utiliser cette commande").await;
+        }
+        username
+    }
+
+    async fn is_authenticated(&self, client_id: ClientId) -> bool {
+        let clients = self.clients.read().await;
+        clients.get(&client_id).is_some_and(|client| matches!(client.state, ServerClientState::Authenticated(_)))
+    }
+
+    async fn is_member(&self, client_id: ClientId, room: &str) -> bool {
+        let clients = self.clients.read().await;
+        clients.get(&client_id).is_some_and(|client| client.rooms.contains(room))
+    }
+
+    // Les files sont remplies hors du verrou des clients : avec OverflowPolicy::Block,
+    // un lecteur lent ne fait attendre que l'expéditeur, pas tout le serveur
+    async fn send_to_client(&self, client_id: ClientId, message: ServerMessage) {
+        let queue = {
+            let clients = self.clients.read().await;
+            clients.get(&client_id).map(|client| client.queue.clone())
+        };
+        if let Some(queue) = queue {
+            queue.push(message).await;
+        }
+    }
+
+    async fn send_error(&self, client_id: ClientId, error_message: &str) {
+        let error = ServerMessage::Error {
+            message: error_message.to_string(),
+        };
+        self.send_to_client(client_id, error).await;
+    }
+
+    async fn broadcast_to_authenticated(&self, message: ServerMessage, exclude_client: Option<ClientId>) {
+        let queues: Vec<Arc<ClientQueue>> = {
+            let clients = self.clients.read().await;
+            clients.iter()
+                .filter(|(id, _)| exclude_client != Some(**id))
+                .filter(|(_, client)| matches!(client.state, ServerClientState::Authenticated(_)))
+                .map(|(_, client)| client.queue.clone())
+                .collect()
+        };
+        for queue in queues {
+            queue.push(message.clone()).await;
+        }
+    }
+
+    async fn broadcast_to_room(&self, room: &str, message: ServerMessage) {
+        let queues: Vec<Arc<ClientQueue>> = {
+            let clients = self.clients.read().await;
+            clients.values()
+                .filter(|client| client.rooms.contains(room))
+                .map(|client| client.queue.clone())
+                .collect()
+        };
+        for queue in queues {
+            queue.push(message.clone()).await;
+        }
+    }
+
+    // Compteurs des files d'envoi depuis le démarrage
+    pub fn queue_stats(&self) -> QueueStats {
+        self.queue_metrics.snapshot()
+    }
+
+    async fn remove_client(&self, client_id: ClientId) {
+        let username = {
+            let mut clients = self.clients.write().await;
             if let Some(client) = clients.remove(&client_id) {
+                // La tâche d'écriture envoie encore ce qui est en file, puis s'arrête
+                client.queue.close();
+                if client.queue.dropped() > 0 {
+                    warn!("📉 Client {} : {} messages perdus (file d'envoi pleine)", client_id, client.queue.dropped());
+                }
+                self.limiter.lock().await.forget_client(client_id);
+                if let Some(token) = &client.session_token {
+                    self.sessions.lock().await.suspend(token, client.rooms.clone());
+                }
                 if let ServerClientState::Authenticated(username) = client.state {
                     Some(username)
                 } else {
@@ -332,6 +1558,7 @@ impl ChatServer {
             let notification = ServerMessage::UserLeft { username };
             self.broadcast_to_authenticated(notification, None).await;
         }
+        self.cancel_transfers(client_id).await;
     }
 }
 
@@ -340,7 +1567,651 @@ impl Clone for ChatServer {
         Self {
             clients: self.clients.clone(),
             next_client_id: self.next_client_id.clone(),
-            broadcast_tx: self.broadcast_tx.clone(),
+            history: self.history.clone(),
+            accounts: self.accounts.clone(),
+            offline: self.offline.clone(),
+            awaiting_read: self.awaiting_read.clone(),
+            sessions: self.sessions.clone(),
+            bans: self.bans.clone(),
+            mutes: self.mutes.clone(),
+            operators: self.operators.clone(),
+            limiter: self.limiter.clone(),
+            transfers: self.transfers.clone(),
+            next_transfer_id: self.next_transfer_id.clone(),
+            tls: self.tls.clone(),
+            heartbeat: self.heartbeat,
+            queue: self.queue,
+            queue_metrics: self.queue_metrics.clone(),
+            max_clients: self.max_clients,
+            max_message_size: self.max_message_size,
+            motd: self.motd.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ratelimit::{Escalation, Rate};
+    use crate::tls::ClientTlsConfig;
+    use crate::COMPRESSION_THRESHOLD;
+    use std::fs;
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+
+    async fn spawn_server(server: ChatServer) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.serve(listener).await });
+        addr
+    }
+
+    async fn send<S: AsyncWrite + Unpin>(stream: &mut S, msg: ClientMessage) {
+        msg.to_protocol_message().unwrap().write_to(stream).await.unwrap();
+    }
+
+    async fn recv<S: AsyncRead + Unpin>(stream: &mut S) -> ServerMessage {
+        let msg = timeout(Duration::from_secs(2), ProtocolMessage::read_from(stream)).await.unwrap().unwrap();
+        ServerMessage::from_protocol_message(&msg).unwrap()
+    }
+
+    fn public(content: &str, room: &str) -> ClientMessage {
+        ClientMessage::PublicMessage { content: content.to_string(), room: room.to_string(), client_ref: None }
+    }
+
+    // Ouvre une connexion et fait la poignée de main
+    async fn hello(addr: SocketAddr) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let capabilities = vec![CAP_HISTORY.to_string(), CAP_ROOMS.to_string()];
+        send(&mut stream, ClientMessage::Hello { version: PROTOCOL_VERSION, capabilities }).await;
+        assert!(matches!(recv(&mut stream).await, ServerMessage::Welcome { .. }));
+        stream
+    }
+
+    // Crée le compte (mot de passe "motdepasse") et s'y connecte
+    async fn login(addr: SocketAddr, username: &str) -> TcpStream {
+        let mut stream = hello(addr).await;
+        send(&mut stream, ClientMessage::Register { username: username.to_string(), password: "motdepasse".to_string() }).await;
+        assert!(matches!(recv(&mut stream).await, ServerMessage::ConnectResponse { success: true, .. }));
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_history_replayed_to_late_joiner() {
+        let addr = spawn_server(ChatServer::new()).await;
+
+        let mut alice = login(addr, "alice").await;
+        for content in ["un", "deux", "trois"] {
+            send(&mut alice, public(content, DEFAULT_ROOM)).await;
+            let ServerMessage::Ack { id: ack, .. } = recv(&mut alice).await else { panic!("Ack attendu") };
+            assert!(matches!(recv(&mut alice).await, ServerMessage::MessageBroadcast { id, .. } if id == ack));
+        }
+
+        let mut bob = login(addr, "bob").await;
+        send(&mut bob, ClientMessage::History { limit: Some(2), room: DEFAULT_ROOM.to_string() }).await;
+        match recv(&mut bob).await {
+            ServerMessage::History { messages, .. } => {
+                let contenus: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+                assert_eq!(contenus, ["deux", "trois"]);
+                assert!(messages[0].id < messages[1].id);
+                assert_eq!(messages[0].from, "alice");
+            }
+            autre => panic!("réponse inattendue: {:?}", autre),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rooms_scope_broadcasts() {
+        let addr = spawn_server(ChatServer::new()).await;
+        let mut alice = login(addr, "alice").await;
+        let mut bob = login(addr, "bob").await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::UserJoined { .. }));
+
+        send(&mut alice, ClientMessage::JoinRoom { room: "#rust".to_string() }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::RoomJoined { .. }));
+
+        // bob n'est pas dans #rust : refusé, et alice est seule à recevoir
+        send(&mut bob, public("intrus", "#rust")).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::Error { .. }));
+        send(&mut alice, public("entre nous", "#rust")).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Ack { .. }));
+        match recv(&mut alice).await {
+            ServerMessage::MessageBroadcast { room, content, .. } => assert_eq!((room.as_str(), content.as_str()), ("#rust", "entre nous")),
+            autre => panic!("réponse inattendue: {:?}", autre),
+        }
+
+        send(&mut bob, ClientMessage::ListRooms).await;
+        match recv(&mut bob).await {
+            ServerMessage::RoomList { rooms } => assert_eq!(rooms, vec![
+                RoomInfo { name: "#general".to_string(), members: 2 },
+                RoomInfo { name: "#rust".to_string(), members: 1 },
+            ]),
+            autre => panic!("réponse inattendue: {:?}", autre),
+        }
+
+        send(&mut bob, ClientMessage::JoinRoom { room: "pas de dièse".to_string() }).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::Error { .. }));
+        send(&mut alice, ClientMessage::LeaveRoom { room: "#rust".to_string() }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::RoomLeft { .. }));
+        send(&mut alice, public("plus membre", "#rust")).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_login_requires_password_and_locks_out() {
+        let addr = spawn_server(ChatServer::new()).await;
+        drop(login(addr, "alice").await);
+
+        let connect = |password: &str| ClientMessage::Connect { username: "alice".to_string(), password: password.to_string() };
+        let mut stream = hello(addr).await;
+
+        // Non authentifié : les messages publics sont refusés
+        send(&mut stream, public("anonyme", DEFAULT_ROOM)).await;
+        assert!(matches!(recv(&mut stream).await, ServerMessage::Error { .. }));
+
+        for _ in 0..crate::accounts::MAX_FAILED_LOGINS {
+            send(&mut stream, connect("mauvais")).await;
+            assert!(matches!(recv(&mut stream).await, ServerMessage::ConnectResponse { success: false, .. }));
+        }
+
+        // Verrouillé : même le bon mot de passe est refusé
+        send(&mut stream, connect("motdepasse")).await;
+        match recv(&mut stream).await {
+            ServerMessage::ConnectResponse { success, message, .. } => {
+                assert!(!success);
+                assert!(message.contains("verrouillé"), "{}", message);
+            }
+            autre => panic!("réponse inattendue: {:?}", autre),
+        }
+
+        // Un nom déjà enregistré ne peut pas être recréé
+        send(&mut stream, ClientMessage::Register { username: "alice".to_string(), password: "autremotdepasse".to_string() }).await;
+        assert!(matches!(recv(&mut stream).await, ServerMessage::ConnectResponse { success: false, .. }));
+
+        // Un nom sans compte reçoit la même réponse qu'un mauvais mot de passe, sans verrouillage
+        for _ in 0..=crate::accounts::MAX_FAILED_LOGINS {
+            send(&mut stream, ClientMessage::Connect { username: "personne".to_string(), password: "mauvais".to_string() }).await;
+            assert!(matches!(recv(&mut stream).await, ServerMessage::ConnectResponse { success: false, message, .. } if message.contains("incorrect")));
         }
+        send(&mut stream, ClientMessage::Connect { username: "pas de nom".to_string(), password: "mauvais".to_string() }).await;
+        assert!(matches!(recv(&mut stream).await, ServerMessage::ConnectResponse { success: false, message, .. } if message.contains("invalide")));
+    }
+
+    #[tokio::test]
+    async fn test_tls_session_with_self_signed_cert() {
+        let dir = std::env::temp_dir().join(format!("restychat_tls_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        fs::write(&cert_path, certified.cert.pem()).unwrap();
+        fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+
+        let config = ServerConfig {
+            tls: Some(ServerTlsConfig { cert_path: cert_path.clone(), key_path, client_ca_path: None }),
+            ..Default::default()
+        };
+        let addr = spawn_server(ChatServer::with_config(config).unwrap()).await;
+
+        let client_tls = ClientTlsConfig {
+            ca_path: cert_path,
+            server_name: Some("localhost".to_string()),
+            cert_path: None,
+            key_path: None,
+        };
+        let (connector, server_name) = client_tls.connector(&addr.to_string()).unwrap();
+        let mut stream = connector.connect(server_name, TcpStream::connect(addr).await.unwrap()).await.unwrap();
+        send(&mut stream, ClientMessage::Hello { version: PROTOCOL_VERSION, capabilities: vec![] }).await;
+        assert!(matches!(recv(&mut stream).await, ServerMessage::Welcome { .. }));
+        send(&mut stream, ClientMessage::Register { username: "alice".to_string(), password: "motdepasse".to_string() }).await;
+        assert!(matches!(recv(&mut stream).await, ServerMessage::ConnectResponse { success: true, .. }));
+
+        // Un client en clair est coupé pendant la poignée de main
+        let mut clair = TcpStream::connect(addr).await.unwrap();
+        send(&mut clair, ClientMessage::ListUsers).await;
+        let lecture = timeout(Duration::from_secs(2), ProtocolMessage::read_from(&mut clair)).await.unwrap();
+        assert!(lecture.is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_required_and_versions_checked() {
+        let addr = spawn_server(ChatServer::new()).await;
+
+        // Connect sans Hello : erreur explicite puis fermeture
+        let mut ancien = TcpStream::connect(addr).await.unwrap();
+        send(&mut ancien, ClientMessage::Connect { username: "alice".to_string(), password: "motdepasse".to_string() }).await;
+        assert!(matches!(recv(&mut ancien).await, ServerMessage::Error { message } if message.contains("Hello")));
+        assert!(ProtocolMessage::read_from(&mut ancien).await.is_err());
+
+        let mut futur = TcpStream::connect(addr).await.unwrap();
+        send(&mut futur, ClientMessage::Hello { version: PROTOCOL_VERSION + 1, capabilities: vec![] }).await;
+        assert!(matches!(recv(&mut futur).await, ServerMessage::Error { message } if message.contains("non supportée")));
+        assert!(ProtocolMessage::read_from(&mut futur).await.is_err());
+
+        // Seules les capacités connues des deux côtés sont retenues
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let capabilities = vec![CAP_ROOMS.to_string(), "teleportation".to_string()];
+        send(&mut stream, ClientMessage::Hello { version: PROTOCOL_VERSION, capabilities }).await;
+        match recv(&mut stream).await {
+            ServerMessage::Welcome { version, capabilities } => {
+                assert_eq!(version, PROTOCOL_VERSION);
+                assert_eq!(capabilities, vec![CAP_ROOMS.to_string()]);
+            }
+            autre => panic!("réponse inattendue: {:?}", autre),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_msgpack_negotiated_after_welcome() {
+        let addr = spawn_server(ChatServer::new()).await;
+        let mut bob = login(addr, "bob").await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let capabilities = vec![CAP_ROOMS.to_string(), CAP_MSGPACK.to_string()];
+        send(&mut stream, ClientMessage::Hello { version: PROTOCOL_VERSION, capabilities }).await;
+        assert!(matches!(recv(&mut stream).await, ServerMessage::Welcome { capabilities, .. }
+            if Encoding::negotiate(&capabilities) == Encoding::MessagePack));
+
+        let register = ClientMessage::Register { username: "alice".to_string(), password: "motdepasse".to_string() };
+        register.encode(Encoding::MessagePack).unwrap().write_to(&mut stream).await.unwrap();
+        let reponse = ProtocolMessage::read_from(&mut stream).await.unwrap();
+        assert!(ServerMessage::from_protocol_message(&reponse).is_err());
+        assert!(matches!(ServerMessage::decode(&reponse, Encoding::MessagePack).unwrap(),
+            ServerMessage::ConnectResponse { success: true, .. }));
+
+        // bob, resté en JSON, reçoit normalement les messages d'alice
+        assert!(matches!(recv(&mut bob).await, ServerMessage::UserJoined { username } if username == "alice"));
+        public("salut", DEFAULT_ROOM).encode(Encoding::MessagePack).unwrap().write_to(&mut stream).await.unwrap();
+        assert!(matches!(recv(&mut bob).await, ServerMessage::MessageBroadcast { content, .. } if content == "salut"));
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_enforced() {
+        let addr = spawn_server(ChatServer::new()).await;
+
+        // Sans "rooms" ni "history" : seul #general reste accessible
+        let mut minimal = TcpStream::connect(addr).await.unwrap();
+        send(&mut minimal, ClientMessage::Hello { version: PROTOCOL_VERSION, capabilities: vec![] }).await;
+        assert!(matches!(recv(&mut minimal).await, ServerMessage::Welcome { .. }));
+        send(&mut minimal, ClientMessage::Register { username: "alice".to_string(), password: "motdepasse".to_string() }).await;
+        assert!(matches!(recv(&mut minimal).await, ServerMessage::ConnectResponse { success: true, .. }));
+        send(&mut minimal, ClientMessage::JoinRoom { room: "#rust".to_string() }).await;
+        assert!(matches!(recv(&mut minimal).await, ServerMessage::Error { message } if message.contains(CAP_ROOMS)));
+        send(&mut minimal, ClientMessage::History { room: DEFAULT_ROOM.to_string(), limit: None }).await;
+        assert!(matches!(recv(&mut minimal).await, ServerMessage::Error { message } if message.contains(CAP_HISTORY)));
+        send(&mut minimal, public("salut", DEFAULT_ROOM)).await;
+        assert!(matches!(recv(&mut minimal).await, ServerMessage::Ack { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_compression_negotiated() {
+        let addr = spawn_server(ChatServer::new()).await;
+        let register = |username: &str| ClientMessage::Register { username: username.to_string(), password: "motdepasse".to_string() };
+
+        // Une trame compressée sans compression négociée est refusée
+        let mut clair = hello(addr).await;
+        send(&mut clair, register("alice")).await;
+        assert!(matches!(recv(&mut clair).await, ServerMessage::ConnectResponse { success: true, .. }));
+        let long = "x".repeat(COMPRESSION_THRESHOLD * 4);
+        public(&long, DEFAULT_ROOM).to_protocol_message().unwrap().compressed(true).write_to(&mut clair).await.unwrap();
+        assert!(matches!(recv(&mut clair).await, ServerMessage::Error { message } if message.contains(CAP_COMPRESSION)));
+
+        let mut compresse = TcpStream::connect(addr).await.unwrap();
+        send(&mut compresse, ClientMessage::Hello { version: PROTOCOL_VERSION, capabilities: vec![CAP_COMPRESSION.to_string()] }).await;
+        assert!(matches!(recv(&mut compresse).await, ServerMessage::Welcome { capabilities, .. } if capabilities == [CAP_COMPRESSION]));
+        send(&mut compresse, register("bob")).await;
+        assert!(matches!(recv(&mut compresse).await, ServerMessage::ConnectResponse { success: true, .. }));
+        assert!(matches!(recv(&mut clair).await, ServerMessage::UserJoined { .. }));
+
+        public(&long, DEFAULT_ROOM).to_protocol_message().unwrap().compressed(true).write_to(&mut compresse).await.unwrap();
+        assert!(matches!(recv(&mut compresse).await, ServerMessage::Ack { .. }));
+        let diffusion = timeout(Duration::from_secs(2), ProtocolMessage::read_from(&mut compresse)).await.unwrap().unwrap();
+        assert!(diffusion.is_compressed());
+        assert!(matches!(ServerMessage::from_protocol_message(&diffusion.decompress().unwrap()).unwrap(),
+            ServerMessage::MessageBroadcast { content, .. } if content == long));
+        // alice, sans compression, reçoit le même message en clair
+        assert!(matches!(recv(&mut clair).await, ServerMessage::MessageBroadcast { content, .. } if content == long));
+    }
+
+    #[tokio::test]
+    async fn test_private_messages_queued_for_offline_users() {
+        let addr = spawn_server(ChatServer::new()).await;
+        drop(login(addr, "alice").await);
+        let mut bob = login(addr, "bob").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let pm = |to: &str, content: &str| ClientMessage::PrivateMessage { to: to.to_string(), content: content.to_string(), client_ref: None };
+        send(&mut bob, pm("alice", "tu es là ?")).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::Ack { .. }));
+        assert!(matches!(recv(&mut bob).await,
+            ServerMessage::PrivateMessageStatus { to, status: DeliveryStatus::Queued, .. } if to == "alice"));
+        send(&mut bob, pm("personne", "allô")).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::Error { message } if message.contains("introuvable")));
+
+        // À sa reconnexion, alice reçoit le message et bob apprend qu'il est remis
+        let mut alice = hello(addr).await;
+        send(&mut alice, ClientMessage::Connect { username: "alice".to_string(), password: "motdepasse".to_string() }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::ConnectResponse { success: true, .. }));
+        assert!(matches!(recv(&mut alice).await,
+            ServerMessage::PrivateMessageDelivery { from, offline: true, .. } if from == "bob"));
+        assert!(matches!(recv(&mut bob).await, ServerMessage::UserJoined { .. }));
+        assert!(matches!(recv(&mut bob).await,
+            ServerMessage::PrivateMessageStatus { status: DeliveryStatus::Delivered, .. }));
+
+        send(&mut bob, pm("alice", "ah te voilà")).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::PrivateMessageDelivery { offline: false, .. }));
+        assert!(matches!(recv(&mut bob).await, ServerMessage::Ack { .. }));
+        assert!(matches!(recv(&mut bob).await,
+            ServerMessage::PrivateMessageStatus { status: DeliveryStatus::Delivered, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_private_message_ids_not_reused_after_restart() {
+        let path = std::env::temp_dir().join(format!("restychat_ids_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let queued = QueuedMessage { id: 7, from: "bob".to_string(), content: "salut".to_string(), timestamp: unix_timestamp() };
+        let snapshot = OfflineQueue::open(&path).unwrap().push("alice", queued).unwrap();
+        save_offline(snapshot).await.unwrap();
+
+        // L'historique public est vide : seule la boîte d'alice connaît l'identifiant 7
+        let server = ChatServer::with_config(ServerConfig { offline_path: Some(path.clone()), ..Default::default() }).unwrap();
+        assert_eq!(server.history.lock().await.next_id(), 8);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_acks_and_read_receipts() {
+        let addr = spawn_server(ChatServer::new()).await;
+        let mut carol = login(addr, "carol").await;
+        let mut alice = login(addr, "alice").await;
+        let mut bob = login(addr, "bob").await;
+
+        send(&mut bob, ClientMessage::PrivateMessage { to: "alice".to_string(), content: "lu ?".to_string(), client_ref: Some(7) }).await;
+        let ServerMessage::Ack { id, timestamp, client_ref: Some(7) } = recv(&mut bob).await else { panic!("Ack attendu") };
+        assert!(matches!(recv(&mut bob).await, ServerMessage::PrivateMessageStatus { id: i, status: DeliveryStatus::Delivered, .. } if i == id));
+        while !matches!(recv(&mut alice).await, ServerMessage::PrivateMessageDelivery { id: i, timestamp: t, .. } if i == id && t == timestamp) {}
+
+        // Seul le destinataire peut accuser la lecture, et une seule fois
+        send(&mut carol, ClientMessage::MarkRead { id }).await;
+        send(&mut alice, ClientMessage::MarkRead { id }).await;
+        send(&mut alice, ClientMessage::MarkRead { id }).await;
+        assert!(matches!(recv(&mut bob).await,
+            ServerMessage::PrivateMessageStatus { id: i, to, status: DeliveryStatus::Read } if i == id && to == "alice"));
+
+        // Les identifiants sont partagés entre messages publics et privés
+        send(&mut bob, public("suivant", DEFAULT_ROOM)).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::Ack { id: suivant, client_ref: None, .. } if suivant > id));
+    }
+
+    #[tokio::test]
+    async fn test_session_resume_replays_missed_messages() {
+        let addr = spawn_server(ChatServer::new()).await;
+        let mut alice = hello(addr).await;
+        send(&mut alice, ClientMessage::Register { username: "alice".to_string(), password: "motdepasse".to_string() }).await;
+        let ServerMessage::ConnectResponse { session_token: Some(token), .. } = recv(&mut alice).await else { panic!("jeton attendu") };
+        send(&mut alice, ClientMessage::JoinRoom { room: "#rust".to_string() }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::RoomJoined { .. }));
+
+        let mut bob = login(addr, "bob").await;
+        send(&mut bob, public("avant", DEFAULT_ROOM)).await;
+        let ServerMessage::Ack { id: vu, .. } = recv(&mut bob).await else { panic!("Ack attendu") };
+        drop(alice);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        send(&mut bob, public("pendant la coupure", DEFAULT_ROOM)).await;
+
+        // Jeton inconnu : refus explicite
+        let mut intrus = hello(addr).await;
+        send(&mut intrus, ClientMessage::Resume { token: "0".repeat(32), last_seen_id: None }).await;
+        assert!(matches!(recv(&mut intrus).await, ServerMessage::ConnectResponse { success: false, .. }));
+
+        let mut alice = hello(addr).await;
+        send(&mut alice, ClientMessage::Resume { token: token.clone(), last_seen_id: Some(vu) }).await;
+        let nouveau = match recv(&mut alice).await {
+            ServerMessage::ConnectResponse { success: true, session_token: Some(nouveau), .. } => nouveau,
+            autre => panic!("réponse inattendue: {:?}", autre),
+        };
+        assert_ne!(nouveau, token);
+        match recv(&mut alice).await {
+            ServerMessage::History { room, messages } => {
+                assert_eq!(room, DEFAULT_ROOM);
+                assert_eq!(messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), ["pendant la coupure"]);
+            }
+            autre => panic!("réponse inattendue: {:?}", autre),
+        }
+
+        // Les salons sont restaurés, et le jeton ne sert qu'une fois
+        send(&mut alice, public("de retour", "#rust")).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Ack { .. }));
+        let mut rejeu = hello(addr).await;
+        send(&mut rejeu, ClientMessage::Resume { token, last_seen_id: None }).await;
+        assert!(matches!(recv(&mut rejeu).await, ServerMessage::ConnectResponse { success: false, .. }));
+
+        // Reprise alors que l'ancienne connexion est encore ouverte : elle est fermée
+        let mut reprise = hello(addr).await;
+        send(&mut reprise, ClientMessage::Resume { token: nouveau, last_seen_id: None }).await;
+        assert!(matches!(recv(&mut reprise).await, ServerMessage::ConnectResponse { success: true, .. }));
+        let fin = timeout(Duration::from_secs(2), async { while ProtocolMessage::read_from(&mut alice).await.is_ok() {} }).await;
+        assert!(fin.is_ok(), "l'ancienne connexion devrait être fermée");
+    }
+
+    #[tokio::test]
+    async fn test_silent_clients_dropped_after_missed_heartbeats() {
+        let heartbeat = HeartbeatConfig { interval: Duration::from_millis(100), max_missed: 2 };
+        let addr = spawn_server(ChatServer::with_config(ServerConfig { heartbeat, ..Default::default() }).unwrap()).await;
+
+        // alice ne répond jamais aux Ping ; bob répond à chacun
+        let mut alice = login(addr, "alice").await;
+        let mut bob = login(addr, "bob").await;
+        let mut pings = 0;
+        loop {
+            match recv(&mut bob).await {
+                ServerMessage::Ping => {
+                    pings += 1;
+                    send(&mut bob, ClientMessage::Pong).await;
+                }
+                ServerMessage::UserLeft { username } => {
+                    assert_eq!(username, "alice");
+                    break;
+                }
+                _ => {}
+            }
+        }
+        assert!(pings >= 2);
+
+        // La connexion d'alice est fermée, son nom est libéré ; bob est toujours là
+        while ProtocolMessage::read_from(&mut alice).await.is_ok() {}
+        send(&mut bob, ClientMessage::Ping).await;
+        while !matches!(recv(&mut bob).await, ServerMessage::Pong) {}
+        let mut alice = hello(addr).await;
+        send(&mut alice, ClientMessage::Connect { username: "alice".to_string(), password: "motdepasse".to_string() }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::ConnectResponse { success: true, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_operators_mute_kick_and_ban() {
+        let operators = HashSet::from(["alice".to_string()]);
+        let addr = spawn_server(ChatServer::with_config(ServerConfig { operators, ..Default::default() }).unwrap()).await;
+        let connect = |username: &str| ClientMessage::Connect { username: username.to_string(), password: "motdepasse".to_string() };
+        let moderation = |msg: ServerMessage| match msg {
+            ServerMessage::Moderation { action, target, .. } => (action, target),
+            autre => panic!("Moderation attendu: {:?}", autre),
+        };
+
+        let mut alice = login(addr, "alice").await;
+        let mut bob = login(addr, "bob").await;
+        let mut mallory = login(addr, "mallory").await;
+        for _ in 0..2 {
+            assert!(matches!(recv(&mut alice).await, ServerMessage::UserJoined { .. }));
+        }
+        assert!(matches!(recv(&mut bob).await, ServerMessage::UserJoined { .. }));
+
+        // Réservé aux opérateurs
+        send(&mut bob, ClientMessage::Kick { username: "mallory".to_string(), reason: None }).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::Error { message } if message.contains("opérateurs")));
+
+        // Silence : annoncé à tous, puis messages refusés
+        send(&mut alice, ClientMessage::Mute { username: "mallory".to_string(), seconds: 60, reason: None }).await;
+        for stream in [&mut alice, &mut bob, &mut mallory] {
+            assert_eq!(moderation(recv(stream).await), (ModerationAction::Mute, "mallory".to_string()));
+        }
+        send(&mut mallory, public("spam", DEFAULT_ROOM)).await;
+        assert!(matches!(recv(&mut mallory).await, ServerMessage::Error { message } if message.contains("silence")));
+
+        // Expulsion : mallory est prévenu puis déconnecté, sans reprise de session possible
+        send(&mut alice, ClientMessage::Kick { username: "mallory".to_string(), reason: Some("flood".to_string()) }).await;
+        assert!(matches!(recv(&mut mallory).await, ServerMessage::Kicked { banned: false, .. }));
+        while ProtocolMessage::read_from(&mut mallory).await.is_ok() {}
+        for stream in [&mut alice, &mut bob] {
+            assert_eq!(moderation(recv(stream).await), (ModerationAction::Kick, "mallory".to_string()));
+            assert!(matches!(recv(stream).await, ServerMessage::UserLeft { .. }));
+        }
+
+        // Bannissement : refusé à la connexion ; l'opérateur ne peut pas bannir sa propre adresse
+        send(&mut alice, ClientMessage::Ban { target: "mallory".to_string(), reason: Some("spam".to_string()) }).await;
+        assert_eq!(moderation(recv(&mut alice).await), (ModerationAction::Ban, "mallory".to_string()));
+        let mut mallory = hello(addr).await;
+        send(&mut mallory, connect("mallory")).await;
+        assert!(matches!(recv(&mut mallory).await, ServerMessage::ConnectResponse { success: false, message, .. } if message.contains("banni")));
+        send(&mut alice, ClientMessage::Ban { target: "127.0.0.1".to_string(), reason: None }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { .. }));
+
+        send(&mut alice, ClientMessage::Unban { target: "mallory".to_string() }).await;
+        assert_eq!(moderation(recv(&mut alice).await), (ModerationAction::Unban, "mallory".to_string()));
+        send(&mut mallory, connect("mallory")).await;
+        assert!(matches!(recv(&mut mallory).await, ServerMessage::ConnectResponse { success: true, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_flood_is_warned_then_muted_and_connections_limited() {
+        let rate_limits = RateLimitConfig {
+            messages_per_client: Some(Rate::new(2, 0.001)),
+            messages_per_ip: None,
+            connections_per_ip: Some(Rate::new(2, 0.001)),
+            escalation: Escalation { warnings: 1, mute: Duration::from_secs(60), max_mutes: 1 },
+        };
+        let addr = spawn_server(ChatServer::with_config(ServerConfig { rate_limits, ..Default::default() }).unwrap()).await;
+
+        let mut alice = login(addr, "alice").await;
+        for content in ["un", "deux"] {
+            send(&mut alice, public(content, DEFAULT_ROOM)).await;
+            assert!(matches!(recv(&mut alice).await, ServerMessage::Ack { .. }));
+            assert!(matches!(recv(&mut alice).await, ServerMessage::MessageBroadcast { .. }));
+        }
+        send(&mut alice, public("trois", DEFAULT_ROOM)).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { message } if message.contains("avertissement 1/1")));
+        send(&mut alice, public("quatre", DEFAULT_ROOM)).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Moderation { action: ModerationAction::Mute, .. }));
+        send(&mut alice, ClientMessage::PrivateMessage { to: "alice".to_string(), content: "cinq".to_string(), client_ref: None }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { message } if message.contains("silence")));
+
+        // Deuxième connexion acceptée, la troisième fermée avant la poignée de main
+        drop(hello(addr).await);
+        let mut refusee = TcpStream::connect(addr).await.unwrap();
+        let lu = timeout(Duration::from_secs(2), ProtocolMessage::read_from(&mut refusee)).await.unwrap();
+        assert!(lu.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_offers_and_cancels_count_as_messages() {
+        let rate_limits = RateLimitConfig {
+            messages_per_client: Some(Rate::new(2, 0.001)),
+            messages_per_ip: None,
+            connections_per_ip: None,
+            escalation: Escalation { warnings: 1, mute: Duration::from_secs(60), max_mutes: 1 },
+        };
+        let addr = spawn_server(ChatServer::with_config(ServerConfig { rate_limits, ..Default::default() }).unwrap()).await;
+        let mut alice = login(addr, "alice").await;
+        let mut bob = login(addr, "bob").await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::UserJoined { .. }));
+        let offer = ClientMessage::FileOffer { to: "bob".to_string(), name: "notes.txt".to_string(), size: 5, sha256: "ab".repeat(32), client_ref: 1 };
+
+        send(&mut alice, offer).await;
+        let ServerMessage::FileOfferSent { transfer_id, .. } = recv(&mut alice).await else { panic!("FileOfferSent attendu") };
+        assert!(matches!(recv(&mut bob).await, ServerMessage::FileOffered { .. }));
+        send(&mut alice, ClientMessage::FileCancel { transfer_id, reason: None }).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::FileCancelled { .. }));
+
+        // Offre et annulation ont vidé la rafale : la suivante est refusée
+        let offer = ClientMessage::FileOffer { to: "bob".to_string(), name: "notes.txt".to_string(), size: 5, sha256: "ab".repeat(32), client_ref: 2 };
+        send(&mut alice, offer).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { message } if message.contains("avertissement 1/1")));
+    }
+
+    #[tokio::test]
+    async fn test_file_transfer_relayed_between_both_ends() {
+        let addr = spawn_server(ChatServer::new()).await;
+        let mut alice = login(addr, "alice").await;
+        let mut bob = login(addr, "bob").await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::UserJoined { .. }));
+        let sha256 = "ab".repeat(32);
+        let offer = |to: &str, name: &str| ClientMessage::FileOffer {
+            to: to.to_string(), name: name.to_string(), size: 5, sha256: sha256.clone(), client_ref: 3,
+        };
+
+        // Offres refusées par le serveur
+        send(&mut alice, offer("alice", "notes.txt")).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { .. }));
+        send(&mut alice, offer("bob", "../notes.txt")).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { .. }));
+
+        send(&mut alice, offer("bob", "notes.txt")).await;
+        let ServerMessage::FileOfferSent { transfer_id, client_ref: 3 } = recv(&mut alice).await else { panic!("FileOfferSent attendu") };
+        assert!(matches!(recv(&mut bob).await, ServerMessage::FileOffered { transfer_id: t, from, size: 5, .. } if t == transfer_id && from == "alice"));
+
+        // Pas de bloc avant l'acceptation, ni d'acceptation par l'expéditeur
+        let chunk = |offset: u64, data: &[u8]| ClientMessage::FileChunk { transfer_id, offset, data: data.to_vec(), crc32: crate::transfer::crc32(data) };
+        send(&mut alice, chunk(0, b"bon")).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { message } if message.contains("inattendu")));
+        send(&mut alice, ClientMessage::FileAccept { transfer_id, offset: 0 }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { .. }));
+
+        // Reprise : bob avait déjà les deux premiers octets
+        send(&mut bob, ClientMessage::FileAccept { transfer_id, offset: 2 }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::FileAccepted { offset: 2, .. }));
+        send(&mut alice, chunk(2, b"jour")).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { .. })); // dépasse la taille annoncée
+        send(&mut alice, chunk(2, b"jou")).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::FileChunk { offset: 2, data, .. } if data == b"jou"));
+        send(&mut bob, ClientMessage::FileAck { transfer_id, next_offset: 5 }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::FileAcked { next_offset: 5, .. }));
+        send(&mut bob, ClientMessage::FileDone { transfer_id, ok: true }).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::FileDone { ok: true, .. }));
+
+        // Terminé : le transfert n'existe plus
+        send(&mut bob, ClientMessage::FileCancel { transfer_id, reason: None }).await;
+        assert!(matches!(recv(&mut bob).await, ServerMessage::Error { message } if message.contains("inconnu")));
+
+        // Départ d'une extrémité : l'autre est prévenue
+        send(&mut alice, offer("bob", "notes.txt")).await;
+        let ServerMessage::FileOfferSent { transfer_id, .. } = recv(&mut alice).await else { panic!("FileOfferSent attendu") };
+        assert!(matches!(recv(&mut bob).await, ServerMessage::FileOffered { .. }));
+        drop(bob);
+        assert!(matches!(recv(&mut alice).await, ServerMessage::UserLeft { .. }));
+        assert!(matches!(recv(&mut alice).await, ServerMessage::FileCancelled { transfer_id: t, .. } if t == transfer_id));
+    }
+
+    #[tokio::test]
+    async fn test_motd_message_size_and_client_limits() {
+        let config = ServerConfig {
+            max_clients: Some(1),
+            max_message_size: Some(5),
+            motd: Some("Bienvenue".to_string()),
+            ..Default::default()
+        };
+        let addr = spawn_server(ChatServer::with_config(config).unwrap()).await;
+
+        let mut alice = login(addr, "alice").await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Motd { message } if message == "Bienvenue"));
+
+        send(&mut alice, public("trop long", DEFAULT_ROOM)).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Error { message } if message.contains("Message trop long (9 octets, maximum 5)")));
+        send(&mut alice, public("court", DEFAULT_ROOM)).await;
+        assert!(matches!(recv(&mut alice).await, ServerMessage::Ack { .. }));
+
+        // Deuxième connexion refusée, avant même la poignée de main
+        let mut refusee = TcpStream::connect(addr).await.unwrap();
+        assert!(matches!(recv(&mut refusee).await, ServerMessage::Error { message } if message.contains("Serveur plein")));
     }
 }
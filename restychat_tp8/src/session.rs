@@ -0,0 +1,113 @@
+// =============================
+// Jetons de session
+// Chaque connexion réussie reçoit un jeton. Après une coupure, le client le
+// présente (Resume) pour retrouver son nom et ses salons sans mot de passe.
+// Un jeton ne sert qu'une fois et expire RESUME_WINDOW après la déconnexion.
+// Les jetons restent en mémoire : un redémarrage du serveur les invalide.
+// =============================
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+pub const RESUME_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone)]
+pub struct ResumableSession {
+    pub username: String,
+    pub rooms: HashSet<String>,
+    expires_at: Option<Instant>, // None : connexion encore ouverte
+}
+
+impl ResumableSession {
+    pub fn is_active(&self) -> bool {
+        self.expires_at.is_none()
+    }
+}
+
+pub struct SessionStore {
+    window: Duration,
+    sessions: HashMap<String, ResumableSession>,
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new(RESUME_WINDOW)
+    }
+}
+
+// 128 bits aléatoires en hexadécimal
+fn new_token() -> String {
+    let mut octets = [0u8; 16];
+    OsRng.fill_bytes(&mut octets);
+    octets.iter().map(|octet| format!("{:02x}", octet)).collect()
+}
+
+impl SessionStore {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            sessions: HashMap::new(),
+        }
+    }
+
+    fn purge(&mut self) {
+        let maintenant = Instant::now();
+        self.sessions.retain(|_, session| session.expires_at.is_none_or(|fin| fin > maintenant));
+    }
+
+    // Nouvelle session pour une connexion qui vient de s'authentifier
+    pub fn open(&mut self, username: &str, rooms: HashSet<String>) -> String {
+        self.purge();
+        let token = new_token();
+        self.sessions.insert(token.clone(), ResumableSession {
+            username: username.to_string(),
+            rooms,
+            expires_at: None,
+        });
+        token
+    }
+
+    // La connexion est fermée : la session reste reprenable pendant la fenêtre
+    pub fn suspend(&mut self, token: &str, rooms: HashSet<String>) {
+        if let Some(session) = self.sessions.get_mut(token) {
+            session.rooms = rooms;
+            session.expires_at = Some(Instant::now() + self.window);
+        }
+    }
+
+    pub fn get(&mut self, token: &str) -> Option<&ResumableSession> {
+        self.purge();
+        self.sessions.get(token)
+    }
+
+    // Consomme le jeton : la reprise en délivre un nouveau
+    pub fn take(&mut self, token: &str) -> Option<ResumableSession> {
+        self.purge();
+        self.sessions.remove(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_single_use_and_expire() {
+        let mut store = SessionStore::new(Duration::from_secs(60));
+        let token = store.open("alice", HashSet::from(["#general".to_string()]));
+        assert_eq!(token.len(), 32);
+        assert!(store.get(&token).is_some_and(ResumableSession::is_active));
+
+        store.suspend(&token, HashSet::from(["#rust".to_string()]));
+        let session = store.take(&token).unwrap();
+        assert_eq!((session.username.as_str(), session.is_active()), ("alice", false));
+        assert!(session.rooms.contains("#rust"));
+        assert!(store.take(&token).is_none());
+
+        let mut immediat = SessionStore::new(Duration::ZERO);
+        let token = immediat.open("bob", HashSet::new());
+        immediat.suspend(&token, HashSet::new());
+        assert!(immediat.take(&token).is_none());
+    }
+}
@@ -0,0 +1,127 @@
+// =============================
+// TLS optionnel pour RustChat (rustls)
+// Serveur : certificat + clé privée, et éventuellement une CA pour exiger
+// un certificat client (TLS mutuel).
+// Client : CA de confiance pour vérifier le serveur, et éventuellement
+// son propre certificat + clé.
+// Tous les fichiers sont au format PEM.
+// =============================
+
+use anyhow::{anyhow, Context, Result};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::RootCertStore;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+// Fichiers TLS du serveur
+#[derive(Debug, Clone)]
+pub struct ServerTlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub client_ca_path: Option<PathBuf>, // Some : seuls les clients signés par cette CA sont acceptés
+}
+
+// Fichiers TLS du client
+#[derive(Debug, Clone)]
+pub struct ClientTlsConfig {
+    pub ca_path: PathBuf,
+    pub server_name: Option<String>, // None : l'hôte de l'adresse du serveur
+    pub cert_path: Option<PathBuf>,  // certificat client, pour le TLS mutuel
+    pub key_path: Option<PathBuf>,
+}
+
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("Ouverture de {}", path.display()))?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(anyhow!("Aucun certificat dans {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("Ouverture de {}", path.display()))?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| anyhow!("Aucune clé privée dans {}", path.display()))
+}
+
+fn load_roots(path: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert)?;
+    }
+    Ok(roots)
+}
+
+impl ServerTlsConfig {
+    pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        let builder = rustls::ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?;
+
+        let builder = match &self.client_ca_path {
+            Some(ca_path) => {
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(load_roots(ca_path)?), provider())
+                    .build()?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let config = builder.with_single_cert(load_certs(&self.cert_path)?, load_key(&self.key_path)?)?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+impl ClientTlsConfig {
+    // Connecteur et nom attendu dans le certificat du serveur
+    pub fn connector(&self, server_addr: &str) -> Result<(TlsConnector, ServerName<'static>)> {
+        let builder = rustls::ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(load_roots(&self.ca_path)?);
+
+        let config = match (&self.cert_path, &self.key_path) {
+            (Some(cert), Some(key)) => builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)?,
+            (None, None) => builder.with_no_client_auth(),
+            _ => return Err(anyhow!("Le certificat client et sa clé vont ensemble")),
+        };
+
+        let host = match &self.server_name {
+            Some(name) => name.clone(),
+            None => host_of(server_addr).to_string(),
+        };
+        let server_name = ServerName::try_from(host.clone())
+            .map_err(|_| anyhow!("Nom de serveur TLS invalide: {}", host))?;
+
+        Ok((TlsConnector::from(Arc::new(config)), server_name))
+    }
+}
+
+// "chat.example.com:8080" -> "chat.example.com", "[::1]:8080" -> "::1"
+fn host_of(addr: &str) -> &str {
+    let host = match addr.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => addr,
+    };
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(host_of("127.0.0.1:8080"), "127.0.0.1");
+        assert_eq!(host_of("chat.example.com:443"), "chat.example.com");
+        assert_eq!(host_of("[::1]:8080"), "::1");
+        assert_eq!(host_of("localhost"), "localhost");
+    }
+}
@@ -0,0 +1,183 @@
+// =============================
+// Transfert de fichiers
+// Le serveur relaie entre les deux clients sans rien écrire sur disque :
+//   expéditeur                          serveur                      destinataire
+//   FileOffer{to, name, size, sha256} → FileOfferSent{id}  ·  FileOffer{id, from, ...} →
+//                                     ← FileAccepted{id, offset}  ← FileAccept{id, offset}
+//   FileChunk{id, offset, data, crc32} →        relayé             →
+//                                     ← FileAck{id, next_offset}  ← (bloc vérifié puis écrit)
+//   ... un bloc à la fois jusqu'à `size` ...
+//                                     ← FileDone{id, ok}          ← (SHA-256 du fichier entier)
+// Un bloc abîmé ou hors séquence est simplement redemandé : FileAck indique
+// toujours le prochain octet attendu.
+// Reprise : la réception se fait dans un fichier .part conservé après une coupure ;
+// la même offre renvoyée plus tard (même nom, même SHA-256) est acceptée à partir
+// de ce qui a déjà été reçu.
+// Un bloc de CHUNK_SIZE octets tient dans une trame de 1 Mo, même en JSON.
+// =============================
+
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const CHUNK_SIZE: usize = 64 * 1024;
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+// Empreinte SHA-256 en hexadécimal (lecture complète du fichier : hors du runtime async)
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|octet| format!("{:02x}", octet)).collect())
+}
+
+// Un simple nom de fichier : ni chemin, ni caractère de contrôle
+pub fn is_valid_file_name(name: &str) -> bool {
+    (1..=255).contains(&name.len())
+        && name != "."
+        && name != ".."
+        && !name.chars().any(|c| c == '/' || c == '\\' || c.is_control())
+}
+
+pub fn is_valid_sha256(sha256: &str) -> bool {
+    sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Bloc commençant à `offset` (vide à la fin du fichier)
+pub fn read_chunk(path: &Path, offset: u64) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::with_capacity(CHUNK_SIZE);
+    file.take(CHUNK_SIZE as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// Fichier en cours de réception
+#[derive(Debug)]
+pub struct IncomingFile {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+    dir: PathBuf,
+    part: PathBuf,
+    received: u64,
+}
+
+impl IncomingFile {
+    // Reprend le fichier partiel d'une offre identique s'il existe
+    pub fn open(dir: &Path, name: &str, size: u64, sha256: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let part = dir.join(format!(".{}.{}.part", name, &sha256[..16]));
+        let mut received = fs::metadata(&part).map(|meta| meta.len()).unwrap_or(0);
+        if received > size {
+            fs::remove_file(&part)?;
+            received = 0;
+        }
+        Ok(Self {
+            name: name.to_string(),
+            size,
+            sha256: sha256.to_string(),
+            dir: dir.to_path_buf(),
+            part,
+            received,
+        })
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received == self.size
+    }
+
+    // Écrit le bloc s'il est celui attendu et intact ; renvoie le prochain octet attendu
+    pub fn write_chunk(&mut self, offset: u64, data: &[u8], checksum: u32) -> io::Result<u64> {
+        let depasse = offset + data.len() as u64 > self.size;
+        if offset != self.received || depasse || crc32(data) != checksum {
+            return Ok(self.received);
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.part)?;
+        file.write_all(data)?;
+        self.received += data.len() as u64;
+        Ok(self.received)
+    }
+
+    // Vérifie l'empreinte puis donne au fichier son nom définitif (sans écraser l'existant).
+    // Un fichier corrompu est supprimé : la prochaine offre repartira de zéro.
+    pub fn finish(self) -> io::Result<PathBuf> {
+        if sha256_file(&self.part)? != self.sha256 {
+            fs::remove_file(&self.part)?;
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Empreinte SHA-256 différente"));
+        }
+        let mut destination = self.dir.join(&self.name);
+        let mut n = 1;
+        while destination.exists() {
+            destination = self.dir.join(format!("{} ({})", self.name, n));
+            n += 1;
+        }
+        fs::rename(&self.part, &destination)?;
+        Ok(destination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_are_checked_resumed_and_verified() {
+        let dir = std::env::temp_dir().join(format!("restychat_transfer_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.bin");
+        let contenu: Vec<u8> = (0..CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+        fs::write(&source, &contenu).unwrap();
+        let sha256 = sha256_file(&source).unwrap();
+        let recus = dir.join("recus");
+        let size = contenu.len() as u64;
+
+        let mut incoming = IncomingFile::open(&recus, "photo.bin", size, &sha256).unwrap();
+        let bloc = read_chunk(&source, 0).unwrap();
+        assert_eq!(bloc.len(), CHUNK_SIZE);
+        // Bloc abîmé ou hors séquence : ignoré, le même octet est redemandé
+        assert_eq!(incoming.write_chunk(0, &bloc, crc32(&bloc) ^ 1).unwrap(), 0);
+        assert_eq!(incoming.write_chunk(CHUNK_SIZE as u64, &bloc, crc32(&bloc)).unwrap(), 0);
+        assert_eq!(incoming.write_chunk(0, &bloc, crc32(&bloc)).unwrap(), CHUNK_SIZE as u64);
+        drop(incoming);
+
+        // Coupure : la même offre reprend là où elle s'était arrêtée
+        let mut incoming = IncomingFile::open(&recus, "photo.bin", size, &sha256).unwrap();
+        let mut offset = incoming.received();
+        assert_eq!(offset, CHUNK_SIZE as u64);
+        while !incoming.is_complete() {
+            let bloc = read_chunk(&source, offset).unwrap();
+            offset = incoming.write_chunk(offset, &bloc, crc32(&bloc)).unwrap();
+        }
+        let chemin = incoming.finish().unwrap();
+        assert_eq!(chemin, recus.join("photo.bin"));
+        assert_eq!(fs::read(&chemin).unwrap(), contenu);
+
+        // Empreinte annoncée fausse : rien n'est gardé
+        let mut incoming = IncomingFile::open(&recus, "faux.bin", 3, &"0".repeat(64)).unwrap();
+        incoming.write_chunk(0, b"abc", crc32(b"abc")).unwrap();
+        assert!(incoming.finish().is_err());
+        assert_eq!(fs::read_dir(&recus).unwrap().count(), 1);
+
+        assert!(!is_valid_file_name("../secret"));
+        assert!(is_valid_file_name("rapport final.pdf"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
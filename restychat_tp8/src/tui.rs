@@ -0,0 +1,558 @@
+// =============================
+// Interface plein écran du client (ratatui)
+// ┌ messages ───────────────────────┐┌ salons ────┐
+// │ 💬 [#general] alice: bonjour     ││ #general   │
+// │ ...                              │├ utilisateurs┤
+// │                                  ││ alice      │
+// └──────────────────────────────────┘└────────────┘
+// ┌ saisie ─────────────────────────────────────────┐
+// Le terminal est piloté par un thread dédié (crossterm est bloquant) :
+// - la session lui envoie des UiEvent (lignes à afficher, arrivées, départs...)
+// - il renvoie chaque ligne validée par un canal, comme l'entrée standard en mode console
+// Touches : Entrée envoie, ↑/↓ parcourent l'historique, Tab complète les noms après
+// /msg (et les autres commandes qui attendent un nom), PgUp/PgDn font défiler les messages,
+// Ctrl-C ou Ctrl-D (ligne vide) quittent.
+// Les couleurs suivent le thème du profil (voir config::Theme).
+// =============================
+
+use crate::config::Theme;
+use crate::DEFAULT_ROOM;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{BTreeSet, VecDeque};
+use std::io;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TryRecvError};
+
+// Lignes gardées dans le panneau de messages
+const MAX_LINES: usize = 2000;
+// Lignes gardées dans l'historique de saisie
+const MAX_INPUT_HISTORY: usize = 200;
+const SIDEBAR_WIDTH: u16 = 24;
+// Attente maximale d'une touche avant de regarder les messages de la session
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Commandes dont le premier argument est un nom d'utilisateur (complétion par Tab)
+const USER_COMMANDS: [&str; 7] = ["/msg", "/pm", "/send", "/kick", "/ban", "/mute", "/unmute"];
+
+// Ce que la session demande à l'interface
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiEvent {
+    Line(String),             // ligne à ajouter au panneau de messages
+    Users(Vec<String>),       // liste complète des connectés (UserList)
+    UserJoined(String),
+    UserLeft(String),
+    RoomJoined(String),       // salon rejoint par l'utilisateur lui-même
+    RoomLeft(String),
+    CurrentRoom(String),
+    Identity(Option<String>), // identifié sous ce nom, ou de retour à l'identification
+    Password(bool),           // saisie masquée (mot de passe) ou normale
+}
+
+// Un appui sur Tab déjà fait : le suivant propose le nom d'après
+#[derive(Debug)]
+struct Completion {
+    start: usize, // début du nom dans la saisie
+    candidates: Vec<String>,
+    index: usize,
+}
+
+// Ligne de saisie avec son historique
+#[derive(Debug, Default)]
+struct InputLine {
+    text: String,
+    cursor: usize,           // position en octets, toujours sur une frontière de caractère
+    history: Vec<String>,
+    browsing: Option<usize>, // entrée de l'historique affichée
+    draft: String,           // saisie en cours, mise de côté pendant le parcours de l'historique
+    completion: Option<Completion>,
+}
+
+impl InputLine {
+    fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    fn backspace(&mut self) {
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+            self.text.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    fn left(&mut self) {
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+        }
+    }
+
+    fn right(&mut self) {
+        if let Some(c) = self.text[self.cursor..].chars().next() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    fn replace(&mut self, text: String) {
+        self.text = text;
+        self.cursor = self.text.len();
+    }
+
+    // Vide la ligne ; `remember` : la garder dans l'historique (jamais un mot de passe)
+    fn submit(&mut self, remember: bool) -> String {
+        let line = std::mem::take(&mut self.text);
+        self.cursor = 0;
+        self.browsing = None;
+        self.draft.clear();
+        if remember && !line.trim().is_empty() && self.history.last() != Some(&line) {
+            if self.history.len() == MAX_INPUT_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+        line
+    }
+
+    // ↑ : entrée précédente de l'historique
+    fn previous(&mut self) {
+        let index = match self.browsing {
+            _ if self.history.is_empty() => return,
+            None => {
+                self.draft = std::mem::take(&mut self.text);
+                self.history.len() - 1
+            },
+            Some(0) => return,
+            Some(i) => i - 1,
+        };
+        self.browsing = Some(index);
+        self.replace(self.history[index].clone());
+    }
+
+    // ↓ : entrée suivante, puis retour à la saisie mise de côté
+    fn next(&mut self) {
+        match self.browsing {
+            None => {},
+            Some(i) if i + 1 < self.history.len() => {
+                self.browsing = Some(i + 1);
+                self.replace(self.history[i + 1].clone());
+            },
+            Some(_) => {
+                self.browsing = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.replace(draft);
+            },
+        }
+    }
+
+    // Tab : "/msg al" devient "/msg alice " ; un nouveau Tab passe au nom suivant
+    fn complete<'a>(&mut self, users: impl Iterator<Item = &'a String>) {
+        if let Some(completion) = &mut self.completion {
+            completion.index = (completion.index + 1) % completion.candidates.len();
+            let name = format!("{} ", completion.candidates[completion.index]);
+            let start = completion.start;
+            self.text.replace_range(start.., &name);
+            self.cursor = self.text.len();
+            return;
+        }
+
+        // Seulement en fin de ligne, sur le premier argument
+        if self.cursor != self.text.len() {
+            return;
+        }
+        let Some((command, partial)) = self.text.split_once(' ') else {
+            return;
+        };
+        if !USER_COMMANDS.contains(&command) || partial.contains(' ') {
+            return;
+        }
+        let partial = partial.to_lowercase();
+        let candidates: Vec<String> = users.filter(|user| user.to_lowercase().starts_with(&partial)).cloned().collect();
+        let Some(premier) = candidates.first() else {
+            return;
+        };
+
+        let start = command.len() + 1;
+        let name = format!("{} ", premier);
+        self.text.replace_range(start.., &name);
+        self.cursor = self.text.len();
+        self.completion = Some(Completion { start, candidates, index: 0 });
+    }
+}
+
+// Styles de chaque sorte de ligne, selon le thème
+#[derive(Default)]
+struct Palette {
+    private: Style,   // 📨 messages privés
+    error: Style,     // ❌ 🚫 ⚠
+    muted: Style,     // accusés de réception, saisie recopiée
+    presence: Style,  // arrivées, départs, reconnexions
+    transfer: Style,  // fichiers
+    success: Style,   // ✅
+    notice: Style,    // 📢 message du jour
+    highlight: Style, // salon courant
+    me: Style,        // son propre nom dans la liste
+}
+
+impl Palette {
+    fn new(theme: Theme) -> Self {
+        let gras = Style::new().add_modifier(Modifier::BOLD);
+        match theme {
+            Theme::Dark => Self {
+                private: Style::new().fg(Color::Magenta),
+                error: Style::new().fg(Color::Red),
+                muted: Style::new().fg(Color::DarkGray),
+                presence: Style::new().fg(Color::Cyan),
+                transfer: Style::new().fg(Color::Yellow),
+                success: Style::new().fg(Color::Green),
+                notice: gras.fg(Color::LightBlue),
+                highlight: gras.fg(Color::Yellow),
+                me: gras,
+            },
+            // Jaune et cyan se lisent mal sur fond blanc
+            Theme::Light => Self {
+                private: Style::new().fg(Color::Magenta),
+                error: Style::new().fg(Color::Red),
+                muted: Style::new().fg(Color::Gray),
+                presence: Style::new().fg(Color::Blue),
+                transfer: Style::new().fg(Color::Indexed(130)),
+                success: Style::new().fg(Color::Indexed(28)),
+                notice: gras.fg(Color::Blue),
+                highlight: gras.fg(Color::Indexed(130)),
+                me: gras,
+            },
+            Theme::Mono => Self {
+                error: gras,
+                muted: Style::new().add_modifier(Modifier::DIM),
+                notice: gras,
+                highlight: gras.add_modifier(Modifier::UNDERLINED),
+                me: gras,
+                ..Self::default()
+            },
+        }
+    }
+
+    // Style d'une ligne d'après son emoji de tête
+    fn line(&self, line: &str) -> Style {
+        match line.chars().next() {
+            Some('📨') => self.private,
+            Some('❌' | '🚫' | '⚠') => self.error,
+            Some('✓' | '👁' | '📭' | '›') => self.muted,
+            Some('👋' | '🚪' | '🔁' | '🔌') => self.presence,
+            Some('📎' | '📤' | '📥') => self.transfer,
+            Some('✅') => self.success,
+            Some('📢') => self.notice,
+            _ => Style::new(),
+        }
+    }
+}
+
+// Réaction à une touche
+enum Action {
+    None,
+    Submit(String),
+    Quit,
+}
+
+#[derive(Default)]
+struct App {
+    lines: VecDeque<String>,
+    scroll: usize, // lignes affichées remontées depuis le bas (0 : on suit les nouveaux messages)
+    page: usize,   // hauteur du panneau de messages au dernier affichage
+    users: BTreeSet<String>,
+    rooms: BTreeSet<String>,
+    current_room: String,
+    me: Option<String>,
+    masked: bool,
+    input: InputLine,
+    palette: Palette,
+}
+
+impl App {
+    fn new(theme: Theme) -> Self {
+        Self {
+            current_room: DEFAULT_ROOM.to_string(),
+            palette: Palette::new(theme),
+            ..Self::default()
+        }
+    }
+
+    fn apply(&mut self, event: UiEvent) {
+        match event {
+            UiEvent::Line(line) => {
+                if self.lines.len() == MAX_LINES {
+                    self.lines.pop_front();
+                }
+                self.lines.push_back(line);
+                // En pleine lecture de l'historique, la vue ne bouge pas
+                if self.scroll > 0 {
+                    self.scroll += 1;
+                }
+            },
+            UiEvent::Users(users) => self.users = users.into_iter().collect(),
+            UiEvent::UserJoined(username) => {
+                self.users.insert(username);
+            },
+            UiEvent::UserLeft(username) => {
+                self.users.remove(&username);
+            },
+            UiEvent::RoomJoined(room) => {
+                self.rooms.insert(room);
+            },
+            UiEvent::RoomLeft(room) => {
+                self.rooms.remove(&room);
+            },
+            UiEvent::CurrentRoom(room) => self.current_room = room,
+            UiEvent::Identity(me) => self.me = me,
+            UiEvent::Password(masked) => self.masked = masked,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.code != KeyCode::Tab {
+            self.input.completion = None;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => return Action::Quit,
+            KeyCode::Char('d') if ctrl && self.input.text.is_empty() => return Action::Quit,
+            KeyCode::Char('u') if ctrl => self.input.replace(String::new()),
+            KeyCode::Char(c) => self.input.insert(c),
+            KeyCode::Backspace => self.input.backspace(),
+            KeyCode::Delete => self.input.delete(),
+            KeyCode::Left => self.input.left(),
+            KeyCode::Right => self.input.right(),
+            KeyCode::Home => self.input.cursor = 0,
+            KeyCode::End => self.input.cursor = self.input.text.len(),
+            KeyCode::Up if !self.masked => self.input.previous(),
+            KeyCode::Down if !self.masked => self.input.next(),
+            KeyCode::Tab if !self.masked => {
+                let me = self.me.clone();
+                self.input.complete(self.users.iter().filter(|user| Some(*user) != me.as_ref()));
+            },
+            KeyCode::PageUp => self.scroll += self.page.saturating_sub(1).max(1),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(self.page.saturating_sub(1).max(1)),
+            KeyCode::Enter => {
+                let line = self.input.submit(!self.masked);
+                // Avant l'identification, les réponses aux questions restent visibles
+                if !self.masked && (self.me.is_none() || line.starts_with('/')) {
+                    self.apply(UiEvent::Line(format!("› {}", line)));
+                }
+                self.scroll = 0;
+                return Action::Submit(line);
+            },
+            _ => {},
+        }
+        Action::None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [haut, saisie] = Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+        let [messages, cote] = Layout::horizontal([Constraint::Min(20), Constraint::Length(SIDEBAR_WIDTH)]).areas(haut);
+        let hauteur_salons = (self.rooms.len() as u16 + 2).min(cote.height / 2).max(3);
+        let [salons, utilisateurs] = Layout::vertical([Constraint::Length(hauteur_salons), Constraint::Min(3)]).areas(cote);
+
+        // Messages : on fait défiler depuis le bas, en tenant compte des lignes repliées
+        let lines: Vec<Line> = self.lines.iter().map(|line| Line::styled(line.as_str(), self.palette.line(line))).collect();
+        let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false });
+        let total = paragraph.line_count(messages.width.saturating_sub(2));
+        self.page = messages.height.saturating_sub(2) as usize;
+        let max_scroll = total.saturating_sub(self.page);
+        self.scroll = self.scroll.min(max_scroll);
+        let titre = if self.scroll == 0 {
+            format!(" RustChat — {} ", self.current_room)
+        } else {
+            format!(" RustChat — {} (historique, PgDn pour revenir en bas) ", self.current_room)
+        };
+        let premiere = (max_scroll - self.scroll).min(u16::MAX as usize) as u16;
+        frame.render_widget(paragraph.block(Block::bordered().title(titre)).scroll((premiere, 0)), messages);
+
+        let items: Vec<ListItem> = self.rooms.iter().map(|room| {
+            let style = if *room == self.current_room { self.palette.highlight } else { Style::new() };
+            ListItem::new(room.as_str()).style(style)
+        }).collect();
+        frame.render_widget(List::new(items).block(Block::bordered().title(" Salons ")), salons);
+
+        let items: Vec<ListItem> = self.users.iter().map(|user| {
+            let style = if Some(user) == self.me.as_ref() { self.palette.me } else { Style::new() };
+            ListItem::new(user.as_str()).style(style)
+        }).collect();
+        let titre = format!(" Utilisateurs ({}) ", self.users.len());
+        frame.render_widget(List::new(items).block(Block::bordered().title(titre)), utilisateurs);
+
+        // Saisie : défile horizontalement pour garder le curseur visible
+        let (texte, colonne) = if self.masked {
+            let n = self.input.text.chars().count();
+            ("•".repeat(n), self.input.text[..self.input.cursor].chars().count())
+        } else {
+            (self.input.text.clone(), Line::raw(&self.input.text[..self.input.cursor]).width())
+        };
+        let largeur = saisie.width.saturating_sub(2) as usize;
+        let decalage = (colonne + 1).saturating_sub(largeur);
+        let titre = match (&self.me, self.masked) {
+            (_, true) => " Mot de passe ".to_string(),
+            (Some(me), false) => format!(" {} → {} ", me, self.current_room),
+            (None, false) => " Saisie ".to_string(),
+        };
+        let paragraph = Paragraph::new(texte).scroll((0, decalage.min(u16::MAX as usize) as u16));
+        frame.render_widget(paragraph.block(Block::bordered().title(titre)), saisie);
+        frame.set_cursor_position(Position::new(saisie.x + 1 + (colonne - decalage) as u16, saisie.y + 1));
+    }
+}
+
+// Terminal en plein écran, piloté par son propre thread
+pub struct Tui {
+    events: mpsc::UnboundedSender<Option<UiEvent>>, // None : fermer l'interface
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl Tui {
+    // Chaque ligne validée part dans `lines` ; le canal est fermé quand l'utilisateur quitte
+    pub fn start(lines: mpsc::UnboundedSender<String>, theme: Theme) -> io::Result<Self> {
+        let terminal = ratatui::try_init()?;
+        let (events, events_rx) = mpsc::unbounded_channel();
+        let thread = std::thread::spawn(move || {
+            let resultat = Self::run(terminal, App::new(theme), events_rx, lines);
+            ratatui::restore();
+            resultat
+        });
+        Ok(Self { events, thread })
+    }
+
+    pub fn sender(&self) -> UiSender {
+        UiSender(self.events.clone())
+    }
+
+    // Rend le terminal dans son état d'origine
+    pub async fn close(self) {
+        let Self { events, thread } = self;
+        let _ = events.send(None);
+        if let Ok(Ok(Err(e))) = tokio::task::spawn_blocking(move || thread.join()).await {
+            eprintln!("❌ Erreur de l'interface: {}", e);
+        }
+    }
+
+    fn run(
+        mut terminal: DefaultTerminal,
+        mut app: App,
+        mut events: mpsc::UnboundedReceiver<Option<UiEvent>>,
+        lines: mpsc::UnboundedSender<String>,
+    ) -> io::Result<()> {
+        let mut lines = Some(lines);
+        let mut a_redessiner = true;
+        loop {
+            loop {
+                match events.try_recv() {
+                    Ok(Some(event)) => {
+                        app.apply(event);
+                        a_redessiner = true;
+                    },
+                    Ok(None) | Err(TryRecvError::Disconnected) => return Ok(()),
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+            if a_redessiner {
+                terminal.draw(|frame| app.draw(frame))?;
+                a_redessiner = false;
+            }
+
+            if !event::poll(POLL_INTERVAL)? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match app.handle_key(key) {
+                    Action::Submit(line) => {
+                        if let Some(lines) = &lines {
+                            let _ = lines.send(line);
+                        }
+                    },
+                    // Fin de la saisie, comme une entrée standard fermée : la session se termine
+                    // puis ferme l'interface
+                    Action::Quit => lines = None,
+                    Action::None => {},
+                },
+                Event::Resize(..) => {},
+                _ => continue,
+            }
+            a_redessiner = true;
+        }
+    }
+}
+
+// Côté session : envoie les événements à l'interface
+#[derive(Clone)]
+pub struct UiSender(mpsc::UnboundedSender<Option<UiEvent>>);
+
+impl UiSender {
+    pub fn send(&self, event: UiEvent) {
+        let _ = self.0.send(Some(event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_history_keeps_the_draft() {
+        let mut input = InputLine::default();
+        for line in ["bonjour", "/users", "/users", ""] {
+            input.replace(line.to_string());
+            input.submit(true);
+        }
+        // Doublons consécutifs et lignes vides ne sont pas gardés
+        assert_eq!(input.history, ["bonjour", "/users"]);
+
+        input.replace("en cours".to_string());
+        input.previous();
+        assert_eq!(input.text, "/users");
+        input.previous();
+        input.previous();
+        assert_eq!(input.text, "bonjour");
+        input.next();
+        input.next();
+        assert_eq!(input.text, "en cours");
+
+        // Un mot de passe n'est jamais gardé
+        input.replace("secret".to_string());
+        input.submit(false);
+        assert_eq!(input.history.len(), 2);
+
+        input.replace("été".to_string());
+        input.left();
+        input.backspace();
+        assert_eq!((input.text.as_str(), input.cursor), ("éé", 2));
+    }
+
+    #[test]
+    fn test_tab_completes_usernames_after_msg() {
+        let users: Vec<String> = ["alice", "Albert", "bob"].iter().map(|user| user.to_string()).collect();
+        let mut input = InputLine::default();
+
+        input.replace("/msg al".to_string());
+        input.complete(users.iter());
+        assert_eq!(input.text, "/msg alice ");
+        input.complete(users.iter());
+        assert_eq!(input.text, "/msg Albert ");
+        input.complete(users.iter());
+        assert_eq!(input.text, "/msg alice ");
+
+        // Ni dans un message, ni sur le deuxième argument
+        input.completion = None;
+        for texte in ["al", "/msg alice sa", "/join al"] {
+            input.replace(texte.to_string());
+            input.complete(users.iter());
+            assert_eq!(input.text, texte);
+        }
+    }
+}
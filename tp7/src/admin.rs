@@ -0,0 +1,361 @@
+// =============================
+// API d'administration HTTP/JSON
+//   GET    /records          -> liste de tous les enregistrements
+//   POST   /records          -> ajoute (ou remplace) un enregistrement
+//   PUT    /records          -> modifie un enregistrement existant (poids, santé...)
+//   DELETE /records          -> retire l'enregistrement décrit dans le corps
+//   DELETE /records/<nom>    -> retire tous les enregistrements d'un nom
+//   GET    /soa              -> numéro de série SOA courant
+// Chaque requête doit porter "Authorization: Bearer <token>".
+// Les modifications sont enregistrées dans le store (et le fichier de zone) avant d'être appliquées.
+// =============================
+
+use anyhow::{anyhow, Result};
+use arc_swap::ArcSwap;
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::dns::DnsDatabase;
+use crate::storage::{Change, RecordStore};
+use crate::zone::{self, ZoneRecord};
+
+const MAX_BODY: usize = 64 * 1024;
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+struct HttpResponse {
+    status: u16,
+    body: String,
+}
+
+impl HttpResponse {
+    fn json<T: Serialize>(status: u16, valeur: &T) -> Self {
+        Self { status, body: serde_json::to_string(valeur).unwrap_or_default() }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &serde_json::json!({ "error": message }))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+pub struct AdminApi {
+    listener: TcpListener,
+    token: String,
+    database: Arc<ArcSwap<DnsDatabase>>,
+    store: Arc<dyn RecordStore>,
+    zone: Option<PathBuf>,
+    // Une seule modification à la fois : lecture, écriture du store et du fichier, puis échange.
+    // Partagé avec le rechargement de la zone (SIGHUP)
+    ecriture: Arc<Mutex<()>>,
+}
+
+impl AdminApi {
+    pub fn bind(
+        addr: SocketAddr,
+        token: String,
+        database: Arc<ArcSwap<DnsDatabase>>,
+        store: Arc<dyn RecordStore>,
+        zone: Option<PathBuf>,
+        ecriture: Arc<Mutex<()>>,
+    ) -> Result<Self> {
+        if token.is_empty() {
+            return Err(anyhow!("L'API d'administration exige un token (--admin-token)"));
+        }
+
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            token,
+            database,
+            store,
+            zone,
+            ecriture,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    // Traite les connexions une par une dans un thread dédié
+    pub fn spawn(self) {
+        thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let reponse = match read_request(&mut stream) {
+                    Ok(requete) => self.handle(requete),
+                    Err(e) => HttpResponse::error(400, &e.to_string()),
+                };
+                if let Err(e) = write_response(&mut stream, &reponse) {
+                    eprintln!("❌ API admin: {}", e);
+                }
+            }
+        });
+    }
+
+    fn handle(&self, requete: HttpRequest) -> HttpResponse {
+        let autorise = requete.token.as_deref()
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), self.token.as_bytes()));
+        if !autorise {
+            return HttpResponse::error(401, "Token manquant ou invalide");
+        }
+
+        let chemin = requete.path.split('?').next().unwrap_or_default();
+        match (requete.method.as_str(), chemin) {
+            ("GET", "/records") => HttpResponse::json(200, &zone::zone_records(&self.database.load())),
+            ("POST", "/records") => self.with_record(&requete.body, Change::Insert, 201),
+            ("PUT", "/records") => self.with_record(&requete.body, Change::Update, 200),
+            ("DELETE", "/records") => self.with_record(&requete.body, Change::Remove, 200),
+            ("DELETE", nom) if nom.starts_with("/records/") => {
+                let nom = nom.trim_start_matches("/records/").to_string();
+                match self.commit(&Change::RemoveName(nom.clone())) {
+                    Ok(_) => HttpResponse::json(200, &serde_json::json!({ "deleted": nom })),
+                    Err(erreur) => erreur,
+                }
+            }
+            ("GET", "/soa") => HttpResponse::json(200, &serde_json::json!({ "serial": self.database.load().serial })),
+            (_, "/records") => HttpResponse::error(405, "Méthode non supportée"),
+            _ => HttpResponse::error(404, "Route inconnue"),
+        }
+    }
+
+    // Décode le corps JSON puis applique la modification ; renvoie l'enregistrement
+    fn with_record(&self, body: &[u8], change: fn(ZoneRecord) -> Change, status: u16) -> HttpResponse {
+        let record: ZoneRecord = match serde_json::from_slice(body) {
+            Ok(record) => record,
+            Err(e) => return HttpResponse::error(400, &format!("JSON invalide: {}", e)),
+        };
+        let reponse = HttpResponse::json(status, &record);
+        match self.commit(&change(record)) {
+            Ok(_) => reponse,
+            Err(erreur) => erreur,
+        }
+    }
+
+    // Copie la base et la modifie, enregistre la modification dans le fichier de
+    // zone et le store, puis échange la base. Renvoie le nouveau serial SOA.
+    // Le fichier de zone est écrit à part avant le store : s'il ne peut pas l'être,
+    // ni le store ni la base en service ne sont touchés. Seul son renommage
+    // (atomique) vient après l'enregistrement dans le store ; s'il échoue, le store
+    // reprend le contenu de la base en service.
+    fn commit(&self, change: &Change) -> Result<u32, HttpResponse> {
+        let _verrou = self.ecriture.lock().unwrap();
+        let ancienne = self.database.load_full();
+        let mut nouvelle = DnsDatabase::clone(&ancienne);
+
+        if !change.apply_to(&mut nouvelle) {
+            let message = match change {
+                Change::RemoveName(_) => "Nom introuvable",
+                _ => "Enregistrement introuvable",
+            };
+            return Err(HttpResponse::error(404, message));
+        }
+
+        let erreur_interne = |e: anyhow::Error| HttpResponse::error(500, &e.to_string());
+        let fichier = match &self.zone {
+            Some(chemin) => Some(zone::PendingZone::write(chemin, &nouvelle).map_err(erreur_interne)?),
+            None => None,
+        };
+        nouvelle.serial = self.store.apply(change).map_err(erreur_interne)?;
+        if let Some(fichier) = fichier
+            && let Err(e) = fichier.commit()
+        {
+            // Le serial du store, lui, continue d'avancer
+            return Err(match self.store.replace_all(&zone::zone_records(&ancienne)) {
+                Ok(_) => erreur_interne(e),
+                Err(annulation) => erreur_interne(anyhow!("{} (store non restauré: {})", e, annulation)),
+            });
+        }
+
+        let serial = nouvelle.serial;
+        self.database.store(Arc::new(nouvelle));
+        Ok(serial)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<HttpRequest> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream);
+
+    let mut ligne = String::new();
+    reader.read_line(&mut ligne)?;
+    let mut parties = ligne.split_whitespace();
+    let (Some(method), Some(path)) = (parties.next(), parties.next()) else {
+        return Err(anyhow!("Ligne de requête invalide"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut token = None;
+    let mut longueur = 0;
+    loop {
+        ligne.clear();
+        if reader.read_line(&mut ligne)? == 0 || ligne.trim().is_empty() {
+            break;
+        }
+        let Some((nom, valeur)) = ligne.split_once(':') else {
+            continue;
+        };
+        let valeur = valeur.trim();
+        if nom.eq_ignore_ascii_case("content-length") {
+            longueur = valeur.parse().map_err(|_| anyhow!("Content-Length invalide"))?;
+        } else if nom.eq_ignore_ascii_case("authorization") {
+            token = valeur.strip_prefix("Bearer ").map(str::to_string);
+        }
+    }
+
+    if longueur > MAX_BODY {
+        return Err(anyhow!("Corps trop gros"));
+    }
+    let mut body = vec![0u8; longueur];
+    reader.read_exact(&mut body)?;
+
+    Ok(HttpRequest { method, path, token, body })
+}
+
+fn write_response(stream: &mut TcpStream, reponse: &HttpResponse) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        reponse.status,
+        reponse.reason(),
+        reponse.body.len(),
+        reponse.body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::TYPE_A;
+    use crate::storage::MemoryStore;
+    use std::fs;
+
+    // API sur un port libre, servie dans un thread, avec la base de départ donnée
+    fn start_api(mut depart: DnsDatabase, store: Arc<dyn RecordStore>, zone: Option<PathBuf>) -> (SocketAddr, Arc<ArcSwap<DnsDatabase>>) {
+        depart.serial = store.replace_all(&zone::zone_records(&depart)).unwrap();
+        let database = Arc::new(ArcSwap::from_pointee(depart));
+
+        let ecriture = Arc::new(Mutex::new(()));
+        let api = AdminApi::bind("127.0.0.1:0".parse().unwrap(), "secret".to_string(), database.clone(), store, zone, ecriture).unwrap();
+        let addr = api.local_addr().unwrap();
+        api.spawn();
+        (addr, database)
+    }
+
+    fn http(addr: SocketAddr, methode: &str, chemin: &str, token: &str, corps: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n\r\n{}",
+            methode, chemin, token, corps.len(), corps
+        ).unwrap();
+        let mut reponse = String::new();
+        stream.read_to_string(&mut reponse).unwrap();
+
+        let status = reponse[9..12].parse().unwrap();
+        let corps = reponse.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, corps)
+    }
+
+    #[test]
+    fn test_admin_api_writes_through_to_zone() {
+        let chemin = std::env::temp_dir().join(format!("tp7_admin_{}.zone", std::process::id()));
+        fs::write(&chemin, "web.test A 10.0.0.1\n").unwrap();
+        let store: Arc<dyn RecordStore> = Arc::new(MemoryStore::new());
+        let (addr, database) = start_api(zone::load_zone(&chemin).unwrap(), store.clone(), Some(chemin.clone()));
+
+        assert_eq!(http(addr, "GET", "/records", "mauvais", "").0, 401);
+
+        let (status, corps) = http(addr, "POST", "/records", "secret", r#"{"type":"A","name":"web.test","address":"10.0.0.2","weight":3}"#);
+        assert_eq!(status, 201, "{}", corps);
+        let (status, _) = http(addr, "POST", "/records", "secret", r#"{"type":"SRV","name":"_web._tcp.test","priority":1,"weight":1,"port":80,"target":"web.test"}"#);
+        assert_eq!(status, 201);
+        assert_eq!(database.load().resolve("web.test", TYPE_A).len(), 2);
+
+        let (status, _) = http(addr, "PUT", "/records", "secret", r#"{"type":"A","name":"web.test","address":"10.0.0.1","healthy":false}"#);
+        assert_eq!(status, 200);
+        assert_eq!(http(addr, "PUT", "/records", "secret", r#"{"type":"A","name":"absent.test","address":"10.0.0.1"}"#).0, 404);
+        assert_eq!(http(addr, "POST", "/records", "secret", r#"{"type":"MX"}"#).0, 400);
+
+        let (status, _) = http(addr, "DELETE", "/records/_web._tcp.test", "secret", "");
+        assert_eq!(status, 200);
+
+        // Un ajout, un SRV, une modification, une suppression : 4 incréments depuis le chargement
+        assert_eq!(http(addr, "GET", "/soa", "secret", ""), (200, r#"{"serial":5}"#.to_string()));
+        assert_eq!(store.records().unwrap().len(), 2);
+
+        let (status, corps) = http(addr, "GET", "/records", "secret", "");
+        assert_eq!(status, 200);
+        let records: Vec<zone::ZoneRecord> = serde_json::from_str(&corps).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let contenu = fs::read_to_string(&chemin).unwrap();
+        assert!(contenu.contains("web.test A 10.0.0.1 down\n"));
+        assert!(contenu.contains("web.test A 10.0.0.2 weight=3\n"));
+        assert!(!contenu.contains("SRV"));
+        assert_eq!(zone::zone_records(&zone::parse_zone(&contenu).unwrap()), records);
+
+        fs::remove_file(&chemin).unwrap();
+    }
+
+    #[test]
+    fn test_admin_api_leaves_store_untouched_when_zone_write_fails() {
+        let chemin = std::env::temp_dir().join("tp7_dossier_absent").join("admin.zone");
+        let store: Arc<dyn RecordStore> = Arc::new(MemoryStore::new());
+        let (addr, database) = start_api(zone::parse_zone("web.test A 10.0.0.1\n").unwrap(), store.clone(), Some(chemin));
+
+        let (status, _) = http(addr, "POST", "/records", "secret", r#"{"type":"A","name":"web.test","address":"10.0.0.2"}"#);
+        assert_eq!(status, 500);
+        assert_eq!(store.serial().unwrap(), 1);
+        assert_eq!(store.records().unwrap().len(), 1);
+        assert_eq!(database.load().resolve("web.test", TYPE_A).len(), 1);
+    }
+
+    #[test]
+    fn test_admin_api_restores_store_when_zone_rename_fails() {
+        // Un dossier à la place du fichier : l'écriture du .tmp réussit, le renommage échoue
+        let chemin = std::env::temp_dir().join(format!("tp7_zone_dossier_{}.zone", std::process::id()));
+        fs::create_dir_all(chemin.join("occupe")).unwrap();
+        let store: Arc<dyn RecordStore> = Arc::new(MemoryStore::new());
+        let (addr, database) = start_api(zone::parse_zone("web.test A 10.0.0.1\n").unwrap(), store.clone(), Some(chemin.clone()));
+
+        let (status, _) = http(addr, "POST", "/records", "secret", r#"{"type":"A","name":"web.test","address":"10.0.0.2"}"#);
+        assert_eq!(status, 500);
+        let records = store.records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(zone::zone_records(&database.load()).contains(&records[0]));
+        assert_eq!(database.load().resolve("web.test", TYPE_A).len(), 1);
+        assert!(!chemin.with_extension("tmp").exists());
+
+        fs::remove_dir_all(&chemin).unwrap();
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::net::{SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod dns;
+use dns::*;
+
+// Au plus IDS_PAR_SOCKET requêtes en vol par socket : l'ID (16 bits) n'est
+// réutilisé que longtemps après l'expiration de la requête qui le portait
+const IDS_PAR_SOCKET: u64 = 32768;
+
+// Période d'expiration des requêtes sans réponse
+const TICK: Duration = Duration::from_millis(50);
+
+#[derive(Parser)]
+#[command(name = "dns_bench")]
+#[command(about = "Générateur de charge pour dns_server (façon dnsperf)")]
+struct Args {
+    /// Fichier de requêtes, une ligne "<nom> <type>" (type: A, AAAA, SRV)
+    queries: PathBuf,
+
+    /// Serveur DNS à tester
+    #[arg(short, long, default_value = "127.0.0.1:8053")]
+    server: String,
+
+    /// Débit visé en requêtes par seconde
+    #[arg(short, long, default_value = "1000")]
+    rate: u32,
+
+    /// Durée du test en secondes
+    #[arg(short, long, default_value = "10")]
+    duration: u64,
+
+    /// Délai après lequel une requête est comptée en timeout (ms)
+    #[arg(short, long, default_value = "1000")]
+    timeout: u64,
+}
+
+// Lit le fichier de requêtes ; le type est optionnel (A par défaut)
+fn parse_queries(contenu: &str) -> Result<Vec<(String, u16)>> {
+    let mut requetes = Vec::new();
+
+    for (numero, ligne) in contenu.lines().enumerate() {
+        let ligne = ligne.split('#').next().unwrap_or("").trim();
+        if ligne.is_empty() {
+            continue;
+        }
+
+        let mut champs = ligne.split_whitespace();
+        let nom = champs.next().unwrap_or_default().trim_end_matches('.').to_string();
+        let qtype = match champs.next().map(|t| t.to_uppercase()).as_deref() {
+            None | Some("A") => TYPE_A,
+            Some("AAAA") => TYPE_AAAA,
+            Some("SRV") => TYPE_SRV,
+            Some(autre) => autre
+                .parse()
+                .map_err(|_| anyhow!("Ligne {}: type inconnu '{}'", numero + 1, autre))?,
+        };
+        requetes.push((nom, qtype));
+    }
+
+    if requetes.is_empty() {
+        return Err(anyhow!("Le fichier de requêtes est vide"));
+    }
+    Ok(requetes)
+}
+
+// Percentile (0-100) d'une liste de latences déjà triée
+fn percentile(latences: &[Duration], p: f64) -> Duration {
+    if latences.is_empty() {
+        return Duration::ZERO;
+    }
+    let rang = ((p / 100.0) * latences.len() as f64).ceil() as usize;
+    latences[rang.clamp(1, latences.len()) - 1]
+}
+
+fn rcode_name(rcode: u16) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        autre => format!("RCODE{}", autre),
+    }
+}
+
+// Nombre de sockets pour qu'aucune ne dépasse IDS_PAR_SOCKET requêtes en vol
+fn sockets_needed(rate: u32, timeout: Duration) -> usize {
+    let en_vol = (rate as f64 * timeout.as_secs_f64()).ceil() as u64;
+    en_vol.div_ceil(IDS_PAR_SOCKET).max(1) as usize
+}
+
+// Requêtes en vol d'une socket : ID -> instant d'envoi, plus une file dans
+// l'ordre d'envoi pour expirer les plus anciennes sans parcourir toute la table
+#[derive(Default)]
+struct InFlight {
+    envois: HashMap<u16, Instant>,
+    ordre: VecDeque<(Instant, u16)>,
+}
+
+impl InFlight {
+    // Vrai si l'ID était encore en vol : l'ancienne requête est perdue
+    fn insert(&mut self, id: u16, envoi: Instant) -> bool {
+        self.ordre.push_back((envoi, id));
+        self.envois.insert(id, envoi).is_some()
+    }
+
+    fn remove(&mut self, id: u16) -> Option<Instant> {
+        self.envois.remove(&id)
+    }
+
+    // Retire les requêtes envoyées il y a plus de `timeout` et renvoie leur nombre.
+    // Les entrées de la file déjà répondues (ou dont l'ID a été réutilisé) sont sautées.
+    fn expire(&mut self, maintenant: Instant, timeout: Duration) -> u64 {
+        let mut expirees = 0;
+        while let Some(&(envoi, id)) = self.ordre.front() {
+            if maintenant.duration_since(envoi) < timeout {
+                break;
+            }
+            self.ordre.pop_front();
+            if self.envois.get(&id) == Some(&envoi) {
+                self.envois.remove(&id);
+                expirees += 1;
+            }
+        }
+        expirees
+    }
+
+    fn is_empty(&self) -> bool {
+        self.envois.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    sent: u64,
+    latences: Vec<Duration>,
+    timeouts: u64,
+    rcodes: BTreeMap<u16, u64>,
+}
+
+struct DnsBench {
+    sockets: Vec<UdpSocket>,
+    server_addr: SocketAddr,
+    requetes: Vec<(String, u16)>,
+    rate: u32,
+    duration: Duration,
+    timeout: Duration,
+}
+
+impl DnsBench {
+    fn run(&self) -> Result<(Stats, Duration)> {
+        // Une table de requêtes en vol et un thread de réception par socket
+        let en_vol: Vec<Arc<Mutex<InFlight>>> = self.sockets.iter().map(|_| Arc::default()).collect();
+        let envoi_termine = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(Mutex::new(Stats::default()));
+
+        let mut receivers = Vec::new();
+        for (socket, en_vol) in self.sockets.iter().zip(&en_vol) {
+            let socket = socket.try_clone()?;
+            let en_vol = en_vol.clone();
+            let envoi_termine = envoi_termine.clone();
+            let stats = stats.clone();
+            let timeout = self.timeout;
+            receivers.push(thread::spawn(move || receive_loop(socket, en_vol, envoi_termine, stats, timeout)));
+        }
+
+        let debut = Instant::now();
+        let intervalle = Duration::from_secs_f64(1.0 / self.rate.max(1) as f64);
+        let mut ids = vec![0u16; self.sockets.len()];
+        let mut envoyees: u64 = 0;
+
+        while debut.elapsed() < self.duration {
+            // Cadencement : la n-ième requête part à debut + n * intervalle
+            let echeance = debut + intervalle.mul_f64(envoyees as f64);
+            if let Some(attente) = echeance.checked_duration_since(Instant::now()) {
+                thread::sleep(attente);
+            }
+
+            // Les sockets sont utilisées à tour de rôle, chacune avec ses propres IDs
+            let voie = envoyees as usize % self.sockets.len();
+            let id = ids[voie];
+            ids[voie] = id.wrapping_add(1);
+
+            let (nom, qtype) = &self.requetes[envoyees as usize % self.requetes.len()];
+            let requete = DnsMessage::new_typed_query(id, nom.clone(), *qtype);
+
+            if en_vol[voie].lock().unwrap().insert(id, Instant::now()) {
+                stats.lock().unwrap().timeouts += 1;
+            }
+            if let Err(e) = self.sockets[voie].send_to(&requete.to_bytes(), self.server_addr) {
+                en_vol[voie].lock().unwrap().remove(id);
+                eprintln!("❌ Erreur d'envoi: {}", e);
+            } else {
+                envoyees += 1;
+            }
+        }
+
+        let duree_envoi = debut.elapsed();
+        envoi_termine.store(true, Ordering::Relaxed);
+        for receiver in receivers {
+            receiver.join().map_err(|_| anyhow!("Le thread de réception a paniqué"))?;
+        }
+
+        let mut stats = std::mem::take(&mut *stats.lock().unwrap());
+        stats.sent = envoyees;
+        Ok((stats, duree_envoi))
+    }
+}
+
+fn receive_loop(
+    socket: UdpSocket,
+    en_vol: Arc<Mutex<InFlight>>,
+    envoi_termine: Arc<AtomicBool>,
+    stats: Arc<Mutex<Stats>>,
+    timeout: Duration,
+) {
+    let mut buffer = [0u8; 512];
+    let _ = socket.set_read_timeout(Some(TICK));
+    let mut prochaine_expiration = Instant::now() + TICK;
+
+    loop {
+        if let Ok((size, _)) = socket.recv_from(&mut buffer)
+            && let Ok(header) = DnsHeader::from_bytes(&buffer[..size])
+        {
+            let envoi = en_vol.lock().unwrap().remove(header.id);
+            if let Some(envoi) = envoi {
+                let mut stats = stats.lock().unwrap();
+                stats.latences.push(envoi.elapsed());
+                *stats.rcodes.entry(header.flags & 0x000F).or_default() += 1;
+            }
+        }
+
+        // Les requêtes trop anciennes sont comptées en timeout, une fois par TICK
+        let maintenant = Instant::now();
+        if maintenant < prochaine_expiration {
+            continue;
+        }
+        prochaine_expiration = maintenant + TICK;
+
+        let (expirees, vide) = {
+            let mut en_vol = en_vol.lock().unwrap();
+            (en_vol.expire(maintenant, timeout), en_vol.is_empty())
+        };
+        stats.lock().unwrap().timeouts += expirees;
+
+        if envoi_termine.load(Ordering::Relaxed) && vide {
+            break;
+        }
+    }
+}
+
+fn print_report(stats: &mut Stats, duree: Duration) {
+    stats.latences.sort();
+    let recues = stats.latences.len() as u64;
+    let secondes = duree.as_secs_f64().max(f64::EPSILON);
+
+    println!("📊 Résultats");
+    println!("   Requêtes envoyées: {}", stats.sent);
+    println!("   Réponses reçues:   {}", recues);
+    println!("   Timeouts:          {}", stats.timeouts);
+    println!("   Durée:             {:.2} s", secondes);
+    println!("   QPS envoyées:      {:.1}", stats.sent as f64 / secondes);
+    println!("   QPS répondues:     {:.1}", recues as f64 / secondes);
+    println!("⏱️  Latences");
+    for p in [50.0, 90.0, 99.0, 99.9] {
+        println!("   p{:<5} {:?}", p, percentile(&stats.latences, p));
+    }
+    println!("   max    {:?}", stats.latences.last().copied().unwrap_or_default());
+    println!("📋 Codes de réponse");
+    for (rcode, total) in &stats.rcodes {
+        println!("   {:<9} {}", rcode_name(*rcode), total);
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let server_addr: SocketAddr = args.server.parse()
+        .map_err(|_| anyhow!("Adresse serveur invalide: {}", args.server))?;
+    let contenu = fs::read_to_string(&args.queries)
+        .map_err(|e| anyhow!("Impossible de lire {}: {}", args.queries.display(), e))?;
+    let requetes = parse_queries(&contenu)?;
+
+    println!("🏁 Benchmark DNS");
+    println!("   Serveur: {}", server_addr);
+    println!("   Requêtes: {} ({} différentes)", args.queries.display(), requetes.len());
+    println!("   Débit visé: {} req/s pendant {} s", args.rate, args.duration);
+
+    let timeout = Duration::from_millis(args.timeout);
+    let mut sockets = Vec::new();
+    for _ in 0..sockets_needed(args.rate, timeout) {
+        sockets.push(UdpSocket::bind("0.0.0.0:0")?);
+    }
+    if sockets.len() > 1 {
+        println!("   Sockets: {} (IDs de 16 bits : au plus {} requêtes en vol par socket)", sockets.len(), IDS_PAR_SOCKET);
+    }
+    println!();
+
+    let bench = DnsBench {
+        sockets,
+        server_addr,
+        requetes,
+        rate: args.rate,
+        duration: Duration::from_secs(args.duration),
+        timeout,
+    };
+
+    let (mut stats, duree) = bench.run()?;
+    print_report(&mut stats, duree);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_queries() {
+        let requetes = parse_queries("# mix\nexample.com A\nlocalhost AAAA\n_chat._tcp.example.com SRV\ngithub.com\nfoo 15\n").unwrap();
+
+        assert_eq!(requetes, vec![
+            ("example.com".to_string(), TYPE_A),
+            ("localhost".to_string(), TYPE_AAAA),
+            ("_chat._tcp.example.com".to_string(), TYPE_SRV),
+            ("github.com".to_string(), TYPE_A),
+            ("foo".to_string(), 15),
+        ]);
+        assert!(parse_queries("example.com MX").is_err());
+        assert!(parse_queries("# vide\n").is_err());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let latences: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+
+        assert_eq!(percentile(&latences, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&latences, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&latences, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&latences, 0.0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_in_flight_expiry_and_sockets() {
+        let debut = Instant::now();
+        let mut en_vol = InFlight::default();
+        assert!(!en_vol.insert(1, debut));
+        assert!(!en_vol.insert(2, debut + Duration::from_millis(10)));
+        assert!(!en_vol.insert(3, debut + Duration::from_millis(20)));
+        assert_eq!(en_vol.remove(1), Some(debut));
+
+        // La 1 a répondu, la 2 expire, la 3 est encore dans les temps
+        assert_eq!(en_vol.expire(debut + Duration::from_millis(115), Duration::from_millis(100)), 1);
+        assert_eq!(en_vol.remove(2), None);
+        assert!(en_vol.insert(3, debut + Duration::from_millis(30)));
+        assert_eq!(en_vol.expire(debut + Duration::from_millis(125), Duration::from_millis(100)), 0);
+        assert_eq!(en_vol.expire(debut + Duration::from_millis(130), Duration::from_millis(100)), 1);
+        assert!(en_vol.is_empty());
+
+        assert_eq!(sockets_needed(1000, Duration::from_secs(1)), 1);
+        assert_eq!(sockets_needed(50_000, Duration::from_secs(2)), 4);
+    }
+}
@@ -0,0 +1,167 @@
+// =============================
+// Listes de blocage (mode "sinkhole" façon Pi-hole)
+// Formats acceptés, éventuellement mélangés dans le même fichier :
+//   - hosts : "0.0.0.0 pub.example.com autre.example.com"
+//   - liste simple : "pub.example.com"
+// Un domaine bloqué bloque aussi tous ses sous-domaines.
+// =============================
+
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Noms présents dans tous les fichiers hosts, qu'il ne faut pas bloquer
+const NOMS_IGNORES: [&str; 5] = ["localhost", "localhost.localdomain", "local", "broadcasthost", "0.0.0.0"];
+
+// Réponse envoyée pour un domaine bloqué
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockAction {
+    NxDomain,
+    Sinkhole(Ipv4Addr), // 0.0.0.0 ou une IP de sinkhole configurée
+}
+
+impl FromStr for BlockAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("nxdomain") {
+            return Ok(BlockAction::NxDomain);
+        }
+        s.parse::<Ipv4Addr>()
+            .map(BlockAction::Sinkhole)
+            .map_err(|_| format!("'{}' n'est ni 'nxdomain' ni une adresse IPv4", s))
+    }
+}
+
+#[derive(Debug)]
+pub struct BlockList {
+    pub name: String,
+    domains: HashSet<String>,
+    hits: AtomicU64,
+}
+
+#[allow(dead_code)]
+impl BlockList {
+    pub fn parse(name: String, contenu: &str) -> Self {
+        let mut domains = HashSet::new();
+
+        for ligne in contenu.lines() {
+            let ligne = ligne.split('#').next().unwrap_or("").trim();
+            if ligne.is_empty() {
+                continue;
+            }
+
+            let mut champs = ligne.split_whitespace().peekable();
+            // Format hosts : le premier champ est une adresse IP
+            if champs.peek().is_some_and(|premier| premier.parse::<IpAddr>().is_ok()) {
+                champs.next();
+            }
+
+            for domaine in champs {
+                let domaine = domaine.trim_end_matches('.').to_lowercase();
+                if !NOMS_IGNORES.contains(&domaine.as_str()) {
+                    domains.insert(domaine);
+                }
+            }
+        }
+
+        Self { name, domains, hits: AtomicU64::new(0) }
+    }
+
+    pub fn load<P: AsRef<Path>>(chemin: P) -> Result<Self> {
+        let chemin = chemin.as_ref();
+        let contenu = fs::read_to_string(chemin)
+            .map_err(|e| anyhow!("Impossible de lire {}: {}", chemin.display(), e))?;
+        Ok(Self::parse(chemin.display().to_string(), &contenu))
+    }
+
+    // Vrai si le domaine ou l'un de ses domaines parents est dans la liste
+    pub fn matches(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        let mut suffixe = domain.as_str();
+        loop {
+            if self.domains.contains(suffixe) {
+                return true;
+            }
+            match suffixe.split_once('.') {
+                Some((_, parent)) => suffixe = parent,
+                None => return false,
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Blocklists {
+    lists: Vec<BlockList>,
+    pub action: BlockAction,
+}
+
+#[allow(dead_code)]
+impl Blocklists {
+    pub fn new(lists: Vec<BlockList>, action: BlockAction) -> Self {
+        Self { lists, action }
+    }
+
+    // Renvoie la première liste qui bloque le domaine et compte le hit
+    pub fn check(&self, domain: &str) -> Option<&BlockList> {
+        let list = self.lists.iter().find(|list| list.matches(domain))?;
+        list.hits.fetch_add(1, Ordering::Relaxed);
+        Some(list)
+    }
+
+    pub fn lists(&self) -> &[BlockList] {
+        &self.lists
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocklist_formats_and_subdomains() {
+        let hosts = BlockList::parse("hosts".to_string(), "127.0.0.1 localhost\n0.0.0.0 ads.example.com tracker.net # pub\n");
+        let simple = BlockList::parse("simple".to_string(), "# liste\nbad.org.\n");
+
+        assert_eq!(hosts.len(), 2);
+        assert!(hosts.matches("ads.example.com"));
+        assert!(hosts.matches("x.y.ADS.example.com"));
+        assert!(!hosts.matches("example.com"));
+        assert!(!hosts.matches("localhost"));
+        assert!(simple.matches("www.bad.org"));
+        assert!(!simple.matches("notbad.org"));
+    }
+
+    #[test]
+    fn test_blocklist_hits_and_action() {
+        let blocklists = Blocklists::new(
+            vec![
+                BlockList::parse("a".to_string(), "ads.example.com"),
+                BlockList::parse("b".to_string(), "example.com"),
+            ],
+            "0.0.0.0".parse().unwrap(),
+        );
+
+        assert_eq!(blocklists.check("ads.example.com").unwrap().name, "a");
+        assert_eq!(blocklists.check("www.example.com").unwrap().name, "b");
+        assert_eq!(blocklists.check("cdn.ads.example.com").unwrap().name, "a");
+        assert!(blocklists.check("github.com").is_none());
+        assert_eq!(blocklists.lists()[0].hits(), 2);
+        assert_eq!(blocklists.lists()[1].hits(), 1);
+        assert_eq!(blocklists.action, BlockAction::Sinkhole(Ipv4Addr::UNSPECIFIED));
+        assert_eq!("NXDOMAIN".parse::<BlockAction>(), Ok(BlockAction::NxDomain));
+        assert!("sinkhole".parse::<BlockAction>().is_err());
+    }
+}
@@ -1,10 +1,14 @@
 use anyhow::Result;
 use clap::Parser;
 use rand::random;
-use std::net::{SocketAddr, UdpSocket};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
 use std::time::Duration;
 
+// Groupe multicast DNS (RFC 6762)
+const MDNS_ADDR: &str = "224.0.0.251:5353";
+
 mod dns;
+mod srv;
 use dns::*;
 
 #[derive(Parser)]
@@ -25,6 +29,22 @@ struct Args {
     /// Mode verbeux
     #[arg(short, long)]
     verbose: bool,
+
+    /// Résoudre en IPv6 (enregistrement AAAA) au lieu d'IPv4
+    #[arg(short = '6', long)]
+    ipv6: bool,
+
+    /// Découverte de service (SRV) : interroge _<service>._<proto>.<domaine>
+    #[arg(long)]
+    srv: Option<String>,
+
+    /// Protocole du service pour --srv
+    #[arg(long, default_value = "tcp")]
+    proto: String,
+
+    /// Requête mDNS "one-shot" vers 224.0.0.251:5353 (noms .local, ignore --server)
+    #[arg(long)]
+    mdns: bool,
 }
 
 struct DnsClient {
@@ -46,12 +66,13 @@ impl DnsClient {
         })
     }
 
-    fn resolve(&self, domain: &str) -> Result<Option<std::net::Ipv4Addr>> {
+    // Envoie une requête et renvoie la réponse (None en cas de timeout)
+    fn query(&self, domain: &str, qtype: u16) -> Result<Option<DnsMessage>> {
         // Générer un ID aléatoire pour la requête
         let requete_id = random::<u16>();
 
         // Construire la requête DNS
-        let requete = DnsMessage::new_query(requete_id, domain.to_string());
+        let requete = DnsMessage::new_typed_query(requete_id, domain.to_string(), qtype);
         let requete_bytes = requete.to_bytes();
 
         if self.verbose {
@@ -92,17 +113,7 @@ impl DnsClient {
                     println!("Réponse parsée - {} réponse(s)", reponse.answers.len());
                 }
 
-                // Extraire l'adresse IP de la première réponse A
-                for answer in &reponse.answers {
-                    if let Some(ip) = answer.get_ip() {
-                        if self.verbose {
-                            println!("  📍 Adresse trouvée: {}", ip);
-                        }
-                        return Ok(Some(ip));
-                    }
-                }
-
-                Ok(None)
+                Ok(Some(reponse))
             }
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                 if self.verbose {
@@ -113,14 +124,47 @@ impl DnsClient {
             Err(e) => Err(e.into()),
         }
     }
+
+    fn resolve(&self, domain: &str, qtype: u16) -> Result<Vec<IpAddr>> {
+        let Some(reponse) = self.query(domain, qtype)? else {
+            return Ok(Vec::new());
+        };
+
+        // Extraire toutes les adresses, dans l'ordre donné par le serveur
+        let adresses: Vec<IpAddr> = reponse.answers.iter()
+            .filter_map(|answer| answer.get_addr())
+            .collect();
+        if self.verbose {
+            for ip in &adresses {
+                println!("  📍 Adresse trouvée: {}", ip);
+            }
+        }
+
+        Ok(adresses)
+    }
+
+    // Résout _service._proto.domaine en liste "hôte:port", dans l'ordre RFC 2782
+    fn resolve_srv(&self, name: &str) -> Result<Vec<String>> {
+        let Some(reponse) = self.query(name, TYPE_SRV)? else {
+            return Ok(Vec::new());
+        };
+
+        let records = reponse.answers.iter().filter_map(|answer| answer.get_srv()).collect();
+        let ordre = srv::order_by_rfc2782(records, &mut rand::thread_rng());
+        Ok(srv::host_ports(&ordre))
+    }
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
     // Parser l'adresse du serveur
-    let server_addr: SocketAddr = args.server.parse()
-        .map_err(|_| anyhow::anyhow!("Adresse serveur invalide: {}", args.server))?;
+    let server = if args.mdns { MDNS_ADDR } else { args.server.as_str() };
+    let server_addr: SocketAddr = server.parse()
+        .map_err(|_| anyhow::anyhow!("Adresse serveur invalide: {}", server))?;
+    if args.mdns && !args.domain.ends_with(".local") {
+        println!("⚠️ mDNS ne résout que les noms en .local");
+    }
 
     let timeout = Duration::from_secs(args.timeout);
 
@@ -133,12 +177,29 @@ fn main() -> Result<()> {
     let client = DnsClient::new(server_addr, timeout, args.verbose)?;
 
     // Effectuer la résolution
-    match client.resolve(&args.domain) {
-        Ok(Some(ip)) => {
+    if let Some(service) = &args.srv {
+        let nom = srv::service_name(service, &args.proto, &args.domain);
+        let services = client.resolve_srv(&nom)?;
+        if services.is_empty() {
+            println!("❌ Aucun service trouvé pour '{}'", nom);
+        } else {
+            println!("✅ Serveurs pour {} (ordre RFC 2782):", nom);
+            for host_port in services {
+                println!("   {}", host_port);
+            }
+        }
+        return Ok(());
+    }
+
+    let qtype = if args.ipv6 { TYPE_AAAA } else { TYPE_A };
+    match client.resolve(&args.domain, qtype) {
+        Ok(adresses) if !adresses.is_empty() => {
             println!("✅ Résolution réussie:");
-            println!("   {} -> {}", args.domain, ip);
+            for ip in adresses {
+                println!("   {} -> {}", args.domain, ip);
+            }
         }
-        Ok(None) => {
+        Ok(_) => {
             println!("❌ Aucune adresse trouvée pour '{}'", args.domain);
         }
         Err(e) => {
@@ -1,5 +1,25 @@
 use std::collections::HashMap;
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Types d'enregistrements
+pub const TYPE_A: u16 = 1;
+pub const TYPE_SOA: u16 = 6;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_SRV: u16 = 33;
+
+// Encode un nom de domaine en suite de labels (sans compression)
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for label in name.split('.') {
+        if !label.is_empty() {
+            bytes.push(label.len() as u8);
+            bytes.extend_from_slice(label.as_bytes());
+        }
+    }
+    bytes.push(0); // Fin du nom
+    bytes
+}
 
 #[derive(Debug, Clone)]
 pub struct DnsHeader {
@@ -74,22 +94,17 @@ impl DnsQuestion {
     pub fn new(name: String) -> Self {
         Self {
             name,
-            qtype: 1,    // Enregistrement A
+            qtype: TYPE_A,
             qclass: 1,   // Classe IN
         }
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    pub fn with_type(name: String, qtype: u16) -> Self {
+        Self { qtype, ..Self::new(name) }
+    }
 
-        // Encodage du nom de domaine
-        for label in self.name.split('.') {
-            if !label.is_empty() {
-                bytes.push(label.len() as u8);
-                bytes.extend_from_slice(label.as_bytes());
-            }
-        }
-        bytes.push(0); // Fin du nom
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = encode_name(&self.name);
 
         bytes.extend_from_slice(&self.qtype.to_be_bytes());
         bytes.extend_from_slice(&self.qclass.to_be_bytes());
@@ -138,7 +153,7 @@ impl DnsQuestion {
                 }
 
                 // Calculer l'offset du pointeur (14 bits)
-                let pointer_offset = ((length & 0x3F) as usize) << 8 | (bytes[pos + 1] as usize);
+                let pointer_offset = (length & 0x3F) << 8 | (bytes[pos + 1] as usize);
                 pos = pointer_offset;
                 jumped = true;
                 jumps += 1;
@@ -182,24 +197,77 @@ impl DnsAnswer {
     pub fn new_a_record(name: String, ip: Ipv4Addr, ttl: u32) -> Self {
         Self {
             name,
-            atype: 1, // Enregistrement A
+            atype: TYPE_A,
             class: 1, // Classe IN
             ttl,
             data: ip.octets().to_vec(),
         }
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    pub fn new_aaaa_record(name: String, ip: Ipv6Addr, ttl: u32) -> Self {
+        Self {
+            name,
+            atype: TYPE_AAAA,
+            class: 1, // Classe IN
+            ttl,
+            data: ip.octets().to_vec(),
+        }
+    }
 
-        // Encodage du nom de domaine
-        for label in self.name.split('.') {
-            if !label.is_empty() {
-                bytes.push(label.len() as u8);
-                bytes.extend_from_slice(label.as_bytes());
-            }
+    pub fn new_srv_record(name: String, srv: &SrvRecord, ttl: u32) -> Self {
+        let mut data = Vec::new();
+        data.extend_from_slice(&srv.priority.to_be_bytes());
+        data.extend_from_slice(&srv.weight.to_be_bytes());
+        data.extend_from_slice(&srv.port.to_be_bytes());
+        data.extend_from_slice(&encode_name(&srv.target));
+
+        Self {
+            name,
+            atype: TYPE_SRV,
+            class: 1, // Classe IN
+            ttl,
+            data,
+        }
+    }
+
+    // SOA minimal : serveur "localhost", contact "hostmaster.localhost"
+    pub fn new_soa_record(name: String, serial: u32, ttl: u32) -> Self {
+        let mut data = encode_name("localhost");
+        data.extend_from_slice(&encode_name("hostmaster.localhost"));
+        for valeur in [serial, 3600, 600, 86400, 60] { // serial, refresh, retry, expire, minimum
+            data.extend_from_slice(&valeur.to_be_bytes());
+        }
+
+        Self {
+            name,
+            atype: TYPE_SOA,
+            class: 1, // Classe IN
+            ttl,
+            data,
+        }
+    }
+
+    // Numéro de série d'un enregistrement SOA
+    pub fn get_soa_serial(&self) -> Option<u32> {
+        if self.atype != TYPE_SOA {
+            return None;
+        }
+        let mut offset = 0;
+        DnsQuestion::decode_name(&self.data, &mut offset).ok()?;
+        DnsQuestion::decode_name(&self.data, &mut offset).ok()?;
+        let octets = self.data.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes([octets[0], octets[1], octets[2], octets[3]]))
+    }
+
+    pub fn new_address_record(name: String, ip: IpAddr, ttl: u32) -> Self {
+        match ip {
+            IpAddr::V4(ip) => Self::new_a_record(name, ip, ttl),
+            IpAddr::V6(ip) => Self::new_aaaa_record(name, ip, ttl),
         }
-        bytes.push(0); // Fin du nom
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = encode_name(&self.name);
 
         bytes.extend_from_slice(&self.atype.to_be_bytes());
         bytes.extend_from_slice(&self.class.to_be_bytes());
@@ -238,12 +306,37 @@ impl DnsAnswer {
 
     #[allow(dead_code)]
     pub fn get_ip(&self) -> Option<Ipv4Addr> {
-        if self.atype == 1 && self.data.len() == 4 {
+        if self.atype == TYPE_A && self.data.len() == 4 {
             Some(Ipv4Addr::from([self.data[0], self.data[1], self.data[2], self.data[3]]))
         } else {
             None
         }
     }
+
+    // Décode un enregistrement SRV (la cible ne doit pas être compressée)
+    pub fn get_srv(&self) -> Option<SrvRecord> {
+        if self.atype != TYPE_SRV || self.data.len() < 7 {
+            return None;
+        }
+
+        let mut offset = 6;
+        let target = DnsQuestion::decode_name(&self.data, &mut offset).ok()?;
+        Some(SrvRecord {
+            priority: u16::from_be_bytes([self.data[0], self.data[1]]),
+            weight: u16::from_be_bytes([self.data[2], self.data[3]]),
+            port: u16::from_be_bytes([self.data[4], self.data[5]]),
+            target,
+        })
+    }
+
+    // Adresse IPv4 ou IPv6 selon le type de l'enregistrement
+    pub fn get_addr(&self) -> Option<IpAddr> {
+        match self.atype {
+            TYPE_A => self.get_ip().map(IpAddr::V4),
+            TYPE_AAAA => <[u8; 16]>::try_from(self.data.as_slice()).ok().map(|octets| IpAddr::V6(Ipv6Addr::from(octets))),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -256,9 +349,13 @@ pub struct DnsMessage {
 #[allow(dead_code)]
 impl DnsMessage {
     pub fn new_query(id: u16, domain: String) -> Self {
+        Self::new_typed_query(id, domain, TYPE_A)
+    }
+
+    pub fn new_typed_query(id: u16, domain: String, qtype: u16) -> Self {
         Self {
             header: DnsHeader::new(id),
-            questions: vec![DnsQuestion::new(domain)],
+            questions: vec![DnsQuestion::with_type(domain, qtype)],
             answers: Vec::new(),
         }
     }
@@ -271,6 +368,18 @@ impl DnsMessage {
         }
     }
 
+    // Réponse mDNS non sollicitée ou multicast : ID 0, réponse d'autorité, sans question
+    pub fn new_mdns_response(answers: Vec<DnsAnswer>) -> Self {
+        let mut header = DnsHeader::new_response(0, answers.len() as u16);
+        header.flags = 0x8400;
+        header.question_count = 0;
+        Self {
+            header,
+            questions: Vec::new(),
+            answers,
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
 
@@ -305,33 +414,277 @@ impl DnsMessage {
     }
 }
 
+// Une adresse associée à un nom, avec son poids et son état de santé
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressRecord {
+    pub ip: IpAddr,
+    pub weight: u32,   // 0 = plus de trafic (drain pendant un déploiement)
+    pub healthy: bool, // false = jamais renvoyée
+}
+
+impl AddressRecord {
+    pub fn new(ip: IpAddr) -> Self {
+        Self { ip, weight: 1, healthy: true }
+    }
+
+    fn matches_type(&self, qtype: u16) -> bool {
+        matches!((qtype, self.ip), (TYPE_A, IpAddr::V4(_)) | (TYPE_AAAA, IpAddr::V6(_)))
+    }
+}
+
+// Enregistrement SRV (RFC 2782) : où trouver un service
+#[derive(Debug, Clone, PartialEq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+// Toutes les adresses et services d'un nom, plus le compteur de rotation (round-robin)
+#[derive(Debug, Default)]
+pub struct NameRecords {
+    pub addresses: Vec<AddressRecord>,
+    pub services: Vec<SrvRecord>,
+    rotation: AtomicUsize,
+}
+
+impl Clone for NameRecords {
+    fn clone(&self) -> Self {
+        Self {
+            addresses: self.addresses.clone(),
+            services: self.services.clone(),
+            rotation: AtomicUsize::new(self.rotation.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct DnsDatabase {
-    records: HashMap<String, Ipv4Addr>,
+    records: HashMap<String, NameRecords>,
+    #[allow(dead_code)]
+    pub serial: u32, // numéro de série SOA, incrémenté à chaque modification
 }
 
 #[allow(dead_code)]
 impl DnsDatabase {
     pub fn new() -> Self {
-        let mut records = HashMap::new();
-        records.insert("example.com".to_string(), Ipv4Addr::new(93, 184, 216, 34));
-        records.insert("google.com".to_string(), Ipv4Addr::new(142, 250, 185, 110));
-        records.insert("github.com".to_string(), Ipv4Addr::new(140, 82, 112, 3));
-        records.insert("localhost".to_string(), Ipv4Addr::new(127, 0, 0, 1));
-        records.insert("test.local".to_string(), Ipv4Addr::new(192, 168, 1, 100));
+        let mut database = Self::empty();
+        database.add_record("example.com".to_string(), Ipv4Addr::new(93, 184, 216, 34));
+        database.add_record("google.com".to_string(), Ipv4Addr::new(142, 250, 185, 110));
+        database.add_record("github.com".to_string(), Ipv4Addr::new(140, 82, 112, 3));
+        database.add_record("localhost".to_string(), Ipv4Addr::new(127, 0, 0, 1));
+        database.add_address("localhost".to_string(), AddressRecord::new(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+        database.add_record("test.local".to_string(), Ipv4Addr::new(192, 168, 1, 100));
+        database
+    }
 
-        Self { records }
+    // Base vide, remplie ensuite depuis un fichier de zone
+    pub fn empty() -> Self {
+        Self { records: HashMap::new(), serial: 1 }
     }
 
+    // Première adresse IPv4 en service, sans rotation
     pub fn lookup(&self, domain: &str) -> Option<Ipv4Addr> {
-        self.records.get(domain).copied()
+        self.records.get(domain)?.addresses.iter()
+            .filter(|record| record.healthy)
+            .find_map(|record| match record.ip {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            })
+    }
+
+    pub fn contains(&self, domain: &str) -> bool {
+        self.records.contains_key(domain)
+    }
+
+    // Adresses du type demandé (A ou AAAA), dans un ordre qui tourne à chaque appel.
+    // La première adresse est choisie en proportion des poids ; les adresses
+    // hors service sont ignorées, celles de poids 0 ne passent qu'en dernier.
+    pub fn resolve(&self, domain: &str, qtype: u16) -> Vec<IpAddr> {
+        let Some(name) = self.records.get(domain) else {
+            return Vec::new();
+        };
+
+        let candidates: Vec<&AddressRecord> = name.addresses.iter()
+            .filter(|record| record.healthy && record.matches_type(qtype))
+            .collect();
+        let total: u32 = candidates.iter().map(|record| record.weight).sum();
+        if candidates.is_empty() || total == 0 {
+            return candidates.iter().map(|record| record.ip).collect();
+        }
+
+        let mut tirage = (name.rotation.fetch_add(1, Ordering::Relaxed) % total as usize) as u32;
+        let mut premier = 0;
+        for (index, record) in candidates.iter().enumerate() {
+            if tirage < record.weight {
+                premier = index;
+                break;
+            }
+            tirage -= record.weight;
+        }
+
+        let (actives, drainees): (Vec<&AddressRecord>, Vec<&AddressRecord>) = candidates.iter()
+            .copied()
+            .cycle()
+            .skip(premier)
+            .take(candidates.len())
+            .partition(|record| record.weight > 0);
+        actives.into_iter().chain(drainees).map(|record| record.ip).collect()
     }
 
     #[allow(dead_code)]
     pub fn add_record(&mut self, domain: String, ip: Ipv4Addr) {
-        self.records.insert(domain, ip);
+        self.add_address(domain, AddressRecord::new(IpAddr::V4(ip)));
+    }
+
+    // Ajoute une adresse à un nom, ou remplace celle qui a la même IP
+    pub fn add_address(&mut self, domain: String, record: AddressRecord) {
+        let name = self.records.entry(domain).or_default();
+        match name.addresses.iter_mut().find(|existing| existing.ip == record.ip) {
+            Some(existing) => *existing = record,
+            None => name.addresses.push(record),
+        }
+    }
+
+    // Ajoute un service à un nom de la forme _service._proto.domaine
+    pub fn add_service(&mut self, domain: String, srv: SrvRecord) {
+        let name = self.records.entry(domain).or_default();
+        if !name.services.contains(&srv) {
+            name.services.push(srv);
+        }
+    }
+
+    // Retire une adresse ; le nom disparaît quand il n'a plus d'enregistrement
+    pub fn remove_address(&mut self, domain: &str, ip: IpAddr) -> bool {
+        let Some(name) = self.records.get_mut(domain) else {
+            return false;
+        };
+        let avant = name.addresses.len();
+        name.addresses.retain(|record| record.ip != ip);
+        let retire = name.addresses.len() != avant;
+        self.drop_if_empty(domain);
+        retire
     }
 
-    pub fn all_records(&self) -> &HashMap<String, Ipv4Addr> {
+    pub fn remove_service(&mut self, domain: &str, target: &str, port: u16) -> bool {
+        let Some(name) = self.records.get_mut(domain) else {
+            return false;
+        };
+        let avant = name.services.len();
+        name.services.retain(|srv| srv.target != target || srv.port != port);
+        let retire = name.services.len() != avant;
+        self.drop_if_empty(domain);
+        retire
+    }
+
+    pub fn remove_name(&mut self, domain: &str) -> bool {
+        self.records.remove(domain).is_some()
+    }
+
+    fn drop_if_empty(&mut self, domain: &str) {
+        if self.records.get(domain).is_some_and(|name| name.addresses.is_empty() && name.services.is_empty()) {
+            self.records.remove(domain);
+        }
+    }
+
+    pub fn services(&self, domain: &str) -> &[SrvRecord] {
+        self.records.get(domain).map_or(&[], |name| name.services.as_slice())
+    }
+
+    pub fn all_records(&self) -> &HashMap<String, NameRecords> {
         &self.records
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(ip: &str, weight: u32, healthy: bool) -> AddressRecord {
+        AddressRecord { ip: ip.parse().unwrap(), weight, healthy }
+    }
+
+    #[test]
+    fn test_round_robin_rotates_addresses() {
+        let mut db = DnsDatabase::empty();
+        for ip in ["10.0.0.1", "10.0.0.2", "10.0.0.3"] {
+            db.add_address("web".to_string(), address(ip, 1, true));
+        }
+
+        let premiers: Vec<IpAddr> = (0..3).map(|_| db.resolve("web", TYPE_A)[0]).collect();
+        assert_eq!(premiers, vec![
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)),
+        ]);
+        assert_eq!(db.resolve("web", TYPE_A), vec![
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)),
+        ]);
+    }
+
+    #[test]
+    fn test_weighted_distribution_over_many_queries() {
+        let mut db = DnsDatabase::empty();
+        db.add_address("api".to_string(), address("10.0.0.1", 3, true));
+        db.add_address("api".to_string(), address("10.0.0.2", 1, true));
+        db.add_address("api".to_string(), address("10.0.0.3", 0, true));
+        db.add_address("api".to_string(), address("10.0.0.4", 1, false));
+        db.add_address("api".to_string(), address("2001:db8::1", 1, true));
+
+        let mut compteurs: HashMap<IpAddr, usize> = HashMap::new();
+        for _ in 0..4000 {
+            let adresses = db.resolve("api", TYPE_A);
+            assert_eq!(adresses.len(), 3);
+            assert_eq!(adresses[2], IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+            *compteurs.entry(adresses[0]).or_default() += 1;
+        }
+
+        assert_eq!(compteurs[&IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))], 3000);
+        assert_eq!(compteurs[&IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))], 1000);
+        assert!(!compteurs.contains_key(&IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4))));
+        assert_eq!(db.resolve("api", TYPE_AAAA), vec![IpAddr::V6("2001:db8::1".parse::<Ipv6Addr>().unwrap())]);
+    }
+
+    #[test]
+    fn test_aaaa_answer_roundtrip() {
+        let ip: Ipv6Addr = "2001:db8::42".parse().unwrap();
+        let reponse = DnsMessage::new_response(
+            7,
+            DnsQuestion::with_type("v6.test".to_string(), TYPE_AAAA),
+            vec![DnsAnswer::new_address_record("v6.test".to_string(), IpAddr::V6(ip), 60)],
+        );
+
+        let parsed = DnsMessage::from_bytes(&reponse.to_bytes()).unwrap();
+        assert_eq!(parsed.questions[0].qtype, TYPE_AAAA);
+        assert_eq!(parsed.answers[0].get_addr(), Some(IpAddr::V6(ip)));
+        assert_eq!(parsed.answers[0].get_ip(), None);
+    }
+
+    #[test]
+    fn test_srv_answer_roundtrip() {
+        let srv = SrvRecord { priority: 10, weight: 60, port: 8080, target: "chat1.example.com".to_string() };
+        let reponse = DnsMessage::new_response(
+            1,
+            DnsQuestion::with_type("_chat._tcp.example.com".to_string(), TYPE_SRV),
+            vec![DnsAnswer::new_srv_record("_chat._tcp.example.com".to_string(), &srv, 300)],
+        );
+
+        let parsed = DnsMessage::from_bytes(&reponse.to_bytes()).unwrap();
+        assert_eq!(parsed.answers[0].get_srv(), Some(srv));
+        assert_eq!(parsed.answers[0].get_addr(), None);
+    }
+
+    #[test]
+    fn test_soa_answer_roundtrip() {
+        let answer = DnsAnswer::new_soa_record("web.test".to_string(), 42, 3600);
+        let reponse = DnsMessage::new_response(7, DnsQuestion::with_type("web.test".to_string(), TYPE_SOA), vec![answer]);
+
+        let decode = DnsMessage::from_bytes(&reponse.to_bytes()).unwrap();
+        assert_eq!(decode.answers[0].atype, TYPE_SOA);
+        assert_eq!(decode.answers[0].get_soa_serial(), Some(42));
+        assert_eq!(decode.answers[0].get_addr(), None);
+    }
+}
@@ -0,0 +1,58 @@
+// =============================
+// Transfert vers un résolveur amont (--upstream)
+// Les noms ni bloqués ni connus de la zone locale sont relayés tels quels au
+// résolveur amont, et sa réponse est renvoyée au client sans modification.
+// =============================
+
+use anyhow::{anyhow, Result};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+// Les réponses amont peuvent dépasser 512 octets (EDNS)
+const MAX_REPONSE: usize = 4096;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Forwarder {
+    pub upstream: SocketAddr,
+    pub timeout: Duration,
+}
+
+impl Forwarder {
+    pub fn new(upstream: SocketAddr, timeout: Duration) -> Self {
+        Self { upstream, timeout }
+    }
+
+    // Envoie la requête brute depuis un port éphémère et attend la réponse qui
+    // porte le même ID ; une réponse en retard à une autre requête est ignorée
+    pub fn forward(&self, requete: &[u8]) -> Result<Vec<u8>> {
+        let id = match requete {
+            [a, b, ..] => u16::from_be_bytes([*a, *b]),
+            _ => return Err(anyhow!("Requête trop courte")),
+        };
+
+        let local: SocketAddr = if self.upstream.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse()?;
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(self.upstream)?;
+        socket.send(requete)?;
+
+        let limite = Instant::now() + self.timeout;
+        let mut buffer = [0u8; MAX_REPONSE];
+        loop {
+            let reste = limite.saturating_duration_since(Instant::now());
+            if reste.is_zero() {
+                return Err(anyhow!("Pas de réponse de {} en {} ms", self.upstream, self.timeout.as_millis()));
+            }
+            socket.set_read_timeout(Some(reste))?;
+
+            match socket.recv(&mut buffer) {
+                Ok(size) if size >= 2 && u16::from_be_bytes([buffer[0], buffer[1]]) == id => {
+                    return Ok(buffer[..size].to_vec());
+                }
+                Ok(_) => continue,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+                Err(e) => return Err(anyhow!("Résolveur amont {}: {}", self.upstream, e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// =============================
+// Multicast DNS (RFC 6762) pour les noms en .local
+// Le répondeur écoute sur 224.0.0.251:5353, répond avec les enregistrements
+// locaux, s'annonce au démarrage et envoie des "goodbye" (TTL 0) à l'arrêt.
+// =============================
+
+use anyhow::Result;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::dns::*;
+
+pub const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+pub const MDNS_PORT: u16 = 5353;
+
+const TYPE_ANY: u16 = 255;
+const MDNS_TTL: u32 = 120;
+const LEGACY_TTL: u32 = 10; // RFC 6762 §6.7 : TTL court pour les réponses unicast "legacy"
+const CACHE_FLUSH: u16 = 0x8000; // bit de poids fort de la classe (enregistrement unique)
+const UNICAST_RESPONSE: u16 = 0x8000; // bit "QU" de la classe d'une question
+
+pub fn is_local_name(name: &str) -> bool {
+    name.trim_end_matches('.').to_lowercase().ends_with(".local")
+}
+
+pub struct MdnsResponder {
+    socket: UdpSocket,
+    group: SocketAddrV4,
+    database: DnsDatabase,
+    verbose: bool,
+}
+
+impl MdnsResponder {
+    // Rejoint le groupe multicast sur l'interface donnée (0.0.0.0 = interface par défaut)
+    pub fn bind(port: u16, interface: Ipv4Addr, database: DnsDatabase, verbose: bool) -> Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        // Plusieurs répondeurs mDNS peuvent partager le port 5353 sur la même machine
+        socket.set_reuse_address(true)?;
+        socket.set_reuse_port(true)?;
+        socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into())?;
+        socket.join_multicast_v4(&MDNS_GROUP, &interface)?;
+        socket.set_multicast_if_v4(&interface)?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.set_multicast_ttl_v4(255)?;
+
+        Ok(Self {
+            socket: socket.into(),
+            group: SocketAddrV4::new(MDNS_GROUP, port),
+            database,
+            verbose,
+        })
+    }
+
+    // Réponses pour un nom .local connu, selon le type demandé
+    fn answers_for(&self, name: &str, qtype: u16, ttl: u32, cache_flush: bool) -> Vec<DnsAnswer> {
+        if !is_local_name(name) {
+            return Vec::new();
+        }
+
+        let mut adresses = Vec::new();
+        if qtype == TYPE_A || qtype == TYPE_ANY {
+            adresses.extend(self.database.resolve(name, TYPE_A));
+        }
+        if qtype == TYPE_AAAA || qtype == TYPE_ANY {
+            adresses.extend(self.database.resolve(name, TYPE_AAAA));
+        }
+
+        adresses.into_iter()
+            .map(|ip| {
+                let mut answer = DnsAnswer::new_address_record(name.to_string(), ip, ttl);
+                if cache_flush {
+                    answer.class |= CACHE_FLUSH;
+                }
+                answer
+            })
+            .collect()
+    }
+
+    // Tous les enregistrements .local, pour les annonces et les goodbyes
+    fn all_local_answers(&self, ttl: u32) -> Vec<DnsAnswer> {
+        let mut noms: Vec<&String> = self.database.all_records().keys()
+            .filter(|name| is_local_name(name))
+            .collect();
+        noms.sort();
+
+        noms.into_iter()
+            .flat_map(|name| self.answers_for(name, TYPE_ANY, ttl, true))
+            .collect()
+    }
+
+    fn send_unsolicited(&self, ttl: u32) -> Result<usize> {
+        let answers = self.all_local_answers(ttl);
+        let total = answers.len();
+        if total > 0 {
+            self.socket.send_to(&DnsMessage::new_mdns_response(answers).to_bytes(), self.group)?;
+        }
+        Ok(total)
+    }
+
+    // Annonce les enregistrements locaux sur le groupe multicast
+    pub fn announce(&self) -> Result<usize> {
+        self.send_unsolicited(MDNS_TTL)
+    }
+
+    // Prévient les caches que les enregistrements disparaissent (TTL 0)
+    pub fn goodbye(&self) -> Result<usize> {
+        self.send_unsolicited(0)
+    }
+
+    pub fn handle_packet(&self, bytes: &[u8], from: SocketAddr) -> Result<()> {
+        let Ok(requete) = DnsMessage::from_bytes(bytes) else {
+            return Ok(());
+        };
+
+        // On ignore les réponses des autres répondeurs (y compris nos propres annonces)
+        if requete.header.flags & 0x8000 != 0 {
+            return Ok(());
+        }
+
+        // Requête "one-shot" depuis un port autre que celui du groupe : réponse unicast classique
+        let legacy = from.port() != self.group.port();
+
+        for question in &requete.questions {
+            let answers = if legacy {
+                self.answers_for(&question.name, question.qtype, LEGACY_TTL, false)
+            } else {
+                self.answers_for(&question.name, question.qtype, MDNS_TTL, true)
+            };
+            if answers.is_empty() {
+                continue; // En mDNS, on ne répond jamais "NXDOMAIN"
+            }
+
+            if self.verbose {
+                println!("  📣 mDNS {} -> {} réponse(s) pour {}", question.name, answers.len(), from);
+            }
+
+            if legacy {
+                let mut question = question.clone();
+                question.qclass &= !UNICAST_RESPONSE;
+                let reponse = DnsMessage::new_response(requete.header.id, question, answers);
+                self.socket.send_to(&reponse.to_bytes(), from)?;
+            } else if question.qclass & UNICAST_RESPONSE != 0 {
+                self.socket.send_to(&DnsMessage::new_mdns_response(answers).to_bytes(), from)?;
+            } else {
+                self.socket.send_to(&DnsMessage::new_mdns_response(answers).to_bytes(), self.group)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Boucle principale : annonce, répond jusqu'à ce que `arret` passe à vrai, puis goodbye
+    pub fn run(&self, arret: &AtomicBool) -> Result<()> {
+        // RFC 6762 §8.3 : au moins deux annonces, à une seconde d'intervalle
+        let total = self.announce()?;
+        println!("📣 {} enregistrement(s) .local annoncé(s) sur {}", total, self.group);
+        thread::sleep(Duration::from_secs(1));
+        self.announce()?;
+
+        self.socket.set_read_timeout(Some(Duration::from_millis(500)))?;
+        let mut buffer = [0u8; 9000]; // Les paquets mDNS peuvent dépasser 512 octets
+
+        while !arret.load(Ordering::Relaxed) {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((size, from)) => {
+                    if let Err(e) = self.handle_packet(&buffer[..size], from) {
+                        eprintln!("❌ Erreur mDNS: {}", e);
+                    }
+                }
+                Err(ref e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => {}
+                Err(e) => eprintln!("❌ Erreur de réception: {}", e),
+            }
+        }
+
+        let total = self.goodbye()?;
+        println!("👋 Goodbye envoyé pour {} enregistrement(s)", total);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // Socket "client" qui envoie vers le groupe mDNS par l'interface loopback
+    fn mdns_test_socket() -> UdpSocket {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
+        socket.set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+        socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)).into()).unwrap();
+        let socket: UdpSocket = socket.into();
+        socket.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        socket
+    }
+
+    #[test]
+    fn test_mdns_one_shot_query_on_loopback() {
+        let port = 20000 + (std::process::id() % 20000) as u16;
+        let responder = Arc::new(MdnsResponder::bind(port, Ipv4Addr::LOCALHOST, DnsDatabase::new(), false).unwrap());
+        let arret = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (responder, arret) = (responder.clone(), arret.clone());
+            thread::spawn(move || responder.run(&arret))
+        };
+
+        let client = mdns_test_socket();
+        let group = SocketAddr::from((MDNS_GROUP, port));
+        let mut buffer = [0u8; 512];
+
+        // Nom non .local : pas de réponse, puis vraie question sur test.local
+        client.send_to(&DnsMessage::new_query(1, "example.com".to_string()).to_bytes(), group).unwrap();
+        client.send_to(&DnsMessage::new_query(2, "test.local".to_string()).to_bytes(), group).unwrap();
+        let (size, _) = client.recv_from(&mut buffer).unwrap();
+        let reponse = DnsMessage::from_bytes(&buffer[..size]).unwrap();
+
+        assert_eq!(reponse.header.id, 2);
+        assert_eq!(reponse.answers[0].get_ip(), Some(Ipv4Addr::new(192, 168, 1, 100)));
+        assert_eq!(reponse.answers[0].ttl, 10);
+
+        arret.store(true, Ordering::Relaxed);
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_mdns_announce_and_goodbye() {
+        let port = 40000 + (std::process::id() % 20000) as u16;
+        let ecoute = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
+        ecoute.set_reuse_address(true).unwrap();
+        ecoute.set_reuse_port(true).unwrap();
+        ecoute.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)).into()).unwrap();
+        ecoute.join_multicast_v4(&MDNS_GROUP, &Ipv4Addr::LOCALHOST).unwrap();
+        let ecoute: UdpSocket = ecoute.into();
+        ecoute.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+
+        let responder = MdnsResponder::bind(
+            port,
+            Ipv4Addr::LOCALHOST,
+            crate::zone::parse_zone("imprimante.local A 10.0.0.9\nserveur.com A 10.0.0.1\n").unwrap(),
+            false,
+        ).unwrap();
+
+        let recevoir = || {
+            let mut buffer = [0u8; 512];
+            let (size, _) = ecoute.recv_from(&mut buffer).unwrap();
+            DnsMessage::from_bytes(&buffer[..size]).unwrap()
+        };
+
+        assert_eq!(responder.announce().unwrap(), 1);
+        let annonce = recevoir();
+        assert_eq!(annonce.header.flags, 0x8400);
+        assert_eq!(annonce.answers[0].name, "imprimante.local");
+        assert_eq!(annonce.answers[0].ttl, 120);
+        assert_eq!(annonce.answers[0].class, 0x8001);
+
+        assert_eq!(responder.goodbye().unwrap(), 1);
+        assert_eq!(recevoir().answers[0].ttl, 0);
+    }
+}
@@ -0,0 +1,226 @@
+// =============================
+// Capture pcap des échanges DNS
+// Chaque datagramme UDP est enregistré avec de faux en-têtes Ethernet et IPv4,
+// ce qui suffit pour l'ouvrir dans Wireshark ou tcpdump.
+// Seul l'IPv4 est capturé.
+// =============================
+
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const LINKTYPE_ETHERNET: u32 = 1;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const IP_PROTO_UDP: u8 = 17;
+const ENTETES_LEN: usize = 14 + 20 + 8; // Ethernet + IPv4 + UDP
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedPacket {
+    pub timestamp: Duration, // depuis l'epoch Unix
+    pub src: SocketAddrV4,
+    pub dst: SocketAddrV4,
+    pub payload: Vec<u8>,
+}
+
+pub struct PcapWriter {
+    out: BufWriter<File>,
+}
+
+#[allow(dead_code)]
+impl PcapWriter {
+    pub fn create<P: AsRef<Path>>(chemin: P) -> Result<Self> {
+        let mut out = BufWriter::new(File::create(chemin)?);
+
+        // En-tête global : magic, version 2.4, fuseau, précision, snaplen, type de lien
+        out.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        out.write_all(&2u16.to_le_bytes())?;
+        out.write_all(&4u16.to_le_bytes())?;
+        out.write_all(&0i32.to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&65535u32.to_le_bytes())?;
+        out.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+        out.flush()?;
+
+        Ok(Self { out })
+    }
+
+    // Enregistre un datagramme ; les adresses IPv6 sont ignorées
+    pub fn write_packet(&mut self, src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Result<()> {
+        let (SocketAddr::V4(src), SocketAddr::V4(dst)) = (src, dst) else {
+            return Ok(());
+        };
+
+        let trame = build_frame(src, dst, payload);
+        let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        self.out.write_all(&(maintenant.as_secs() as u32).to_le_bytes())?;
+        self.out.write_all(&maintenant.subsec_micros().to_le_bytes())?;
+        self.out.write_all(&(trame.len() as u32).to_le_bytes())?;
+        self.out.write_all(&(trame.len() as u32).to_le_bytes())?;
+        self.out.write_all(&trame)?;
+        // Flush à chaque paquet : la capture reste lisible si le serveur est tué
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn build_frame(src: SocketAddrV4, dst: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+    let mut trame = Vec::with_capacity(ENTETES_LEN + payload.len());
+
+    // Ethernet : adresses MAC factices
+    trame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x02]);
+    trame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]);
+    trame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+    // IPv4
+    let ip_start = trame.len();
+    trame.push(0x45); // version 4, en-tête de 20 octets
+    trame.push(0);
+    trame.extend_from_slice(&((20 + 8 + payload.len()) as u16).to_be_bytes());
+    trame.extend_from_slice(&[0, 0, 0x40, 0]); // identification, "don't fragment"
+    trame.push(64); // TTL
+    trame.push(IP_PROTO_UDP);
+    trame.extend_from_slice(&[0, 0]); // checksum, calculé plus bas
+    trame.extend_from_slice(&src.ip().octets());
+    trame.extend_from_slice(&dst.ip().octets());
+    let checksum = ipv4_checksum(&trame[ip_start..ip_start + 20]);
+    trame[ip_start + 10..ip_start + 12].copy_from_slice(&checksum.to_be_bytes());
+
+    // UDP (checksum 0 = non calculé, autorisé en IPv4)
+    trame.extend_from_slice(&src.port().to_be_bytes());
+    trame.extend_from_slice(&dst.port().to_be_bytes());
+    trame.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    trame.extend_from_slice(&[0, 0]);
+
+    trame.extend_from_slice(payload);
+    trame
+}
+
+fn ipv4_checksum(entete: &[u8]) -> u16 {
+    let mut somme: u32 = entete
+        .chunks(2)
+        .map(|mot| u16::from_be_bytes([mot[0], *mot.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while somme > 0xffff {
+        somme = (somme & 0xffff) + (somme >> 16);
+    }
+    !(somme as u16)
+}
+
+// Lit tous les datagrammes UDP/IPv4 d'un fichier pcap (les autres trames sont ignorées)
+#[allow(dead_code)]
+pub fn read_pcap<P: AsRef<Path>>(chemin: P) -> Result<Vec<CapturedPacket>> {
+    let mut contenu = Vec::new();
+    File::open(chemin)?.read_to_end(&mut contenu)?;
+
+    if contenu.len() < 24 {
+        return Err(anyhow!("Fichier pcap trop court"));
+    }
+    let magic = u32::from_le_bytes([contenu[0], contenu[1], contenu[2], contenu[3]]);
+    let big_endian = match magic {
+        PCAP_MAGIC => false,
+        m if m.swap_bytes() == PCAP_MAGIC => true,
+        _ => return Err(anyhow!("Ce n'est pas un fichier pcap (magic {:#x})", magic)),
+    };
+    let lire_u32 = |octets: &[u8]| {
+        let octets = [octets[0], octets[1], octets[2], octets[3]];
+        if big_endian { u32::from_be_bytes(octets) } else { u32::from_le_bytes(octets) }
+    };
+    if lire_u32(&contenu[20..24]) != LINKTYPE_ETHERNET {
+        return Err(anyhow!("Seules les captures Ethernet sont supportées"));
+    }
+
+    let mut paquets = Vec::new();
+    let mut offset = 24;
+    while offset + 16 <= contenu.len() {
+        let secondes = lire_u32(&contenu[offset..]);
+        let micros = lire_u32(&contenu[offset + 4..]);
+        let taille = lire_u32(&contenu[offset + 8..]) as usize;
+        if micros >= 1_000_000 {
+            return Err(anyhow!("Horodatage invalide à l'offset {} ({} µs)", offset, micros));
+        }
+        offset += 16;
+
+        if offset + taille > contenu.len() {
+            return Err(anyhow!("Paquet tronqué à l'offset {}", offset));
+        }
+        let trame = &contenu[offset..offset + taille];
+        offset += taille;
+
+        if let Some((src, dst, payload)) = parse_frame(trame) {
+            paquets.push(CapturedPacket {
+                timestamp: Duration::from_secs(secondes as u64) + Duration::from_micros(micros as u64),
+                src,
+                dst,
+                payload: payload.to_vec(),
+            });
+        }
+    }
+
+    Ok(paquets)
+}
+
+fn parse_frame(trame: &[u8]) -> Option<(SocketAddrV4, SocketAddrV4, &[u8])> {
+    if trame.len() < ENTETES_LEN || u16::from_be_bytes([trame[12], trame[13]]) != ETHERTYPE_IPV4 {
+        return None;
+    }
+
+    let ip = &trame[14..];
+    let ihl = ((ip[0] & 0x0f) as usize) * 4;
+    if ip[9] != IP_PROTO_UDP || ip.len() < ihl + 8 {
+        return None;
+    }
+    let src_ip = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+    let dst_ip = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+
+    let udp = &ip[ihl..];
+    let src_port = u16::from_be_bytes([udp[0], udp[1]]);
+    let dst_port = u16::from_be_bytes([udp[2], udp[3]]);
+    let udp_len = (u16::from_be_bytes([udp[4], udp[5]]) as usize).clamp(8, udp.len());
+
+    Some((
+        SocketAddrV4::new(src_ip, src_port),
+        SocketAddrV4::new(dst_ip, dst_port),
+        &udp[8..udp_len],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::DnsMessage;
+    use std::fs;
+
+    #[test]
+    fn test_pcap_roundtrip() {
+        let chemin = std::env::temp_dir().join(format!("tp7_capture_{}.pcap", std::process::id()));
+        let client: SocketAddr = "192.0.2.10:40000".parse().unwrap();
+        let serveur: SocketAddr = "192.0.2.1:8053".parse().unwrap();
+        let requete = DnsMessage::new_query(99, "example.com".to_string()).to_bytes();
+
+        let mut writer = PcapWriter::create(&chemin).unwrap();
+        writer.write_packet(client, serveur, &requete).unwrap();
+        writer.write_packet("[::1]:1".parse().unwrap(), serveur, &requete).unwrap(); // IPv6 ignorée
+        writer.write_packet(serveur, client, b"reponse").unwrap();
+        drop(writer);
+
+        let paquets = read_pcap(&chemin).unwrap();
+        assert_eq!(paquets.len(), 2);
+        assert_eq!(SocketAddr::V4(paquets[0].src), client);
+        assert_eq!(SocketAddr::V4(paquets[0].dst), serveur);
+        assert_eq!(paquets[0].payload, requete);
+        assert_eq!(paquets[1].payload, b"reponse");
+
+        // Microsecondes hors limites : enregistrement rejeté, sans débordement
+        let mut contenu = fs::read(&chemin).unwrap();
+        contenu[28..32].copy_from_slice(&5_000_000u32.to_le_bytes());
+        fs::write(&chemin, contenu).unwrap();
+        assert!(read_pcap(&chemin).is_err());
+
+        fs::remove_file(&chemin).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, SocketAddrV4, UdpSocket};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+mod dns;
+mod pcap;
+use dns::*;
+
+#[derive(Parser)]
+#[command(name = "dns_replay")]
+#[command(about = "Rejoue les requêtes d'une capture pcap et compare les réponses")]
+struct Args {
+    /// Capture pcap (par exemple produite par dns_server --pcap)
+    capture: PathBuf,
+
+    /// Serveur DNS à tester
+    #[arg(short, long, default_value = "127.0.0.1:8053")]
+    server: String,
+
+    /// Timeout par requête en millisecondes
+    #[arg(short, long, default_value = "1000")]
+    timeout: u64,
+
+    /// Affiche aussi les requêtes identiques
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+// Une requête capturée et la réponse enregistrée à l'époque (si elle existe)
+struct Exchange {
+    question: String,
+    query: Vec<u8>,
+    recorded: Option<DnsMessage>,
+}
+
+// Associe chaque requête capturée à sa réponse (même client, même ID)
+fn pair_exchanges(paquets: &[pcap::CapturedPacket]) -> Vec<Exchange> {
+    let mut reponses: HashMap<(SocketAddrV4, u16), DnsMessage> = HashMap::new();
+    for paquet in paquets {
+        if let Ok(message) = DnsMessage::from_bytes(&paquet.payload)
+            && message.header.flags & 0x8000 != 0
+        {
+            reponses.entry((paquet.dst, message.header.id)).or_insert(message);
+        }
+    }
+
+    paquets.iter()
+        .filter_map(|paquet| {
+            let requete = DnsMessage::from_bytes(&paquet.payload).ok()?;
+            if requete.header.flags & 0x8000 != 0 {
+                return None;
+            }
+            let question = requete.questions.first()
+                .map(|q| format!("{} (type {})", q.name, q.qtype))
+                .unwrap_or_default();
+            Some(Exchange {
+                question,
+                query: paquet.payload.clone(),
+                recorded: reponses.remove(&(paquet.src, requete.header.id)),
+            })
+        })
+        .collect()
+}
+
+// Ce que le serveur a répondu à une requête rejouée
+enum Outcome {
+    Answer(DnsMessage),
+    Unreadable(String),
+    Timeout,
+}
+
+// Envoie la requête et attend la réponse du serveur qui porte le même ID.
+// Une réponse arrivée en retard pour une requête précédente est ignorée.
+fn replay_one(socket: &UdpSocket, server_addr: SocketAddr, query: &[u8], timeout: Duration) -> Result<Outcome> {
+    let id = match query {
+        [a, b, ..] => u16::from_be_bytes([*a, *b]),
+        _ => return Ok(Outcome::Unreadable("requête trop courte".to_string())),
+    };
+    socket.send_to(query, server_addr)?;
+
+    let limite = Instant::now() + timeout;
+    let mut buffer = [0u8; 512];
+    loop {
+        let reste = limite.saturating_duration_since(Instant::now());
+        if reste.is_zero() {
+            return Ok(Outcome::Timeout);
+        }
+        socket.set_read_timeout(Some(reste))?;
+
+        match socket.recv_from(&mut buffer) {
+            Ok((size, from)) if from == server_addr && size >= 2 && u16::from_be_bytes([buffer[0], buffer[1]]) == id => {
+                return Ok(match DnsMessage::from_bytes(&buffer[..size]) {
+                    Ok(message) => Outcome::Answer(message),
+                    Err(e) => Outcome::Unreadable(e.to_string()),
+                });
+            }
+            Ok(_) => continue,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn describe_answer(answer: &DnsAnswer) -> String {
+    let valeur = if let Some(ip) = answer.get_addr() {
+        ip.to_string()
+    } else if let Some(srv) = answer.get_srv() {
+        format!("{} {} {}:{}", srv.priority, srv.weight, srv.target, srv.port)
+    } else {
+        answer.data.iter().map(|b| format!("{:02x}", b)).collect()
+    };
+    format!("{} type {} {}", answer.name, answer.atype, valeur)
+}
+
+// Différences entre la réponse enregistrée et la nouvelle.
+// L'ordre des réponses est ignoré : le round-robin le fait tourner volontairement.
+fn compare(recorded: &DnsMessage, actual: &DnsMessage) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    let (rcode_avant, rcode_apres) = (recorded.header.flags & 0x000F, actual.header.flags & 0x000F);
+    if rcode_avant != rcode_apres {
+        differences.push(format!("rcode {} -> {}", rcode_avant, rcode_apres));
+    }
+
+    let mut avant: Vec<String> = recorded.answers.iter().map(describe_answer).collect();
+    let mut apres: Vec<String> = actual.answers.iter().map(describe_answer).collect();
+    avant.sort();
+    apres.sort();
+    for answer in avant.iter().filter(|answer| !apres.contains(answer)) {
+        differences.push(format!("- {}", answer));
+    }
+    for answer in apres.iter().filter(|answer| !avant.contains(answer)) {
+        differences.push(format!("+ {}", answer));
+    }
+
+    differences
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let server_addr: SocketAddr = args.server.parse()
+        .map_err(|_| anyhow!("Adresse serveur invalide: {}", args.server))?;
+    let exchanges = pair_exchanges(&pcap::read_pcap(&args.capture)?);
+
+    println!("🔁 Rejeu de {} requête(s) de {} vers {}", exchanges.len(), args.capture.display(), server_addr);
+    println!();
+
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    let timeout = Duration::from_millis(args.timeout);
+    let (mut identiques, mut differentes, mut sans_reference, mut timeouts) = (0, 0, 0, 0);
+
+    for exchange in &exchanges {
+        let Some(recorded) = &exchange.recorded else {
+            sans_reference += 1;
+            continue;
+        };
+
+        let differences = match replay_one(&socket, server_addr, &exchange.query, timeout)? {
+            Outcome::Answer(actual) => compare(recorded, &actual),
+            Outcome::Unreadable(erreur) => vec![format!("réponse illisible: {}", erreur)],
+            Outcome::Timeout => {
+                println!("⏰ {}: pas de réponse", exchange.question);
+                timeouts += 1;
+                continue;
+            }
+        };
+
+        if differences.is_empty() {
+            identiques += 1;
+            if args.verbose {
+                println!("✅ {}", exchange.question);
+            }
+        } else {
+            differentes += 1;
+            println!("❌ {}", exchange.question);
+            for difference in differences {
+                println!("     {}", difference);
+            }
+        }
+    }
+
+    println!();
+    println!("📊 {} identique(s), {} différente(s), {} timeout(s), {} sans réponse enregistrée",
+             identiques, differentes, timeouts, sans_reference);
+
+    if differentes > 0 || timeouts > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::thread;
+
+    fn paquet(src: &str, dst: &str, message: &DnsMessage) -> pcap::CapturedPacket {
+        pcap::CapturedPacket {
+            timestamp: Duration::ZERO,
+            src: src.parse().unwrap(),
+            dst: dst.parse().unwrap(),
+            payload: message.to_bytes(),
+        }
+    }
+
+    fn reponse(id: u16, ips: &[Ipv4Addr]) -> DnsMessage {
+        let answers = ips.iter().map(|ip| DnsAnswer::new_a_record("web".to_string(), *ip, 60)).collect();
+        DnsMessage::new_response(id, DnsQuestion::new("web".to_string()), answers)
+    }
+
+    #[test]
+    fn test_pair_exchanges_by_client_and_id() {
+        let requete = DnsMessage::new_query(5, "web".to_string());
+        let paquets = vec![
+            paquet("10.0.0.1:4000", "10.0.0.53:53", &requete),
+            paquet("10.0.0.2:4000", "10.0.0.53:53", &requete),
+            paquet("10.0.0.53:53", "10.0.0.1:4000", &reponse(5, &[Ipv4Addr::new(1, 1, 1, 1)])),
+        ];
+
+        let exchanges = pair_exchanges(&paquets);
+        assert_eq!(exchanges.len(), 2);
+        assert!(exchanges[0].recorded.is_some());
+        assert!(exchanges[1].recorded.is_none());
+        assert_eq!(exchanges[0].question, "web (type 1)");
+    }
+
+    #[test]
+    fn test_compare_ignores_answer_order() {
+        let (a, b, c) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 3));
+
+        assert!(compare(&reponse(1, &[a, b]), &reponse(1, &[b, a])).is_empty());
+        assert_eq!(compare(&reponse(1, &[a, b]), &reponse(1, &[a, c])), vec![
+            "- web type 1 10.0.0.2".to_string(),
+            "+ web type 1 10.0.0.3".to_string(),
+        ]);
+
+        let mut nxdomain = reponse(1, &[]);
+        nxdomain.header.flags |= 0x0003;
+        assert_eq!(compare(&reponse(1, &[]), &nxdomain), vec!["rcode 0 -> 3".to_string()]);
+    }
+
+    #[test]
+    fn test_replay_skips_late_replies() {
+        let serveur = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = serveur.local_addr().unwrap();
+        // Répond d'abord à une requête précédente (ID 1), puis à la bonne ;
+        // à la requête 3, une réponse illisible
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            for _ in 0..2 {
+                let (size, client) = serveur.recv_from(&mut buffer).unwrap();
+                let id = DnsMessage::from_bytes(&buffer[..size]).unwrap().header.id;
+                if id == 2 {
+                    serveur.send_to(&reponse(1, &[Ipv4Addr::new(10, 0, 0, 1)]).to_bytes(), client).unwrap();
+                    serveur.send_to(&reponse(2, &[Ipv4Addr::new(10, 0, 0, 2)]).to_bytes(), client).unwrap();
+                } else {
+                    serveur.send_to(&[0, id as u8, 0x81], client).unwrap();
+                }
+            }
+        });
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let timeout = Duration::from_secs(2);
+        let query = |id| DnsMessage::new_query(id, "web".to_string()).to_bytes();
+
+        let Outcome::Answer(actual) = replay_one(&socket, server_addr, &query(2), timeout).unwrap() else {
+            panic!("réponse attendue");
+        };
+        assert_eq!(actual.header.id, 2);
+        assert_eq!(actual.answers[0].get_ip(), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert!(matches!(replay_one(&socket, server_addr, &query(3), timeout).unwrap(), Outcome::Unreadable(_)));
+        assert!(matches!(replay_one(&socket, server_addr, &query(4), Duration::from_millis(50)).unwrap(), Outcome::Timeout));
+    }
+}
@@ -1,10 +1,27 @@
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use clap::Parser;
-use std::net::{SocketAddr, UdpSocket};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1};
+use signal_hook::iterator::Signals;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
+mod admin;
+mod blocklist;
 mod dns;
+mod forward;
+mod mdns;
+mod pcap;
+mod storage;
+mod zone;
+use blocklist::{BlockAction, BlockList, Blocklists};
 use dns::*;
+use forward::Forwarder;
+use storage::{MemoryStore, RecordStore, RedbStore};
 
 #[derive(Parser)]
 #[command(name = "dns_server")]
@@ -21,27 +38,174 @@ struct Args {
     /// Mode verbeux
     #[arg(short, long)]
     verbose: bool,
+
+    /// Fichier de zone (rechargé à la réception de SIGHUP)
+    #[arg(short, long)]
+    zone: Option<PathBuf>,
+
+    /// Liste de blocage (format hosts ou un domaine par ligne), répétable
+    #[arg(short, long)]
+    blocklist: Vec<PathBuf>,
+
+    /// Réponse pour un domaine bloqué: "nxdomain", "0.0.0.0" ou l'IP du sinkhole
+    #[arg(long, default_value = "nxdomain")]
+    block_response: BlockAction,
+
+    /// Résolveur amont pour les noms hors zone (ex: 1.1.1.1:53) ; sans lui, NXDOMAIN
+    #[arg(long)]
+    upstream: Option<SocketAddr>,
+
+    /// Délai d'attente du résolveur amont, en millisecondes (SERVFAIL au-delà)
+    #[arg(long, default_value = "2000")]
+    upstream_timeout: u64,
+
+    /// Mode multicast DNS : répond aux noms .local sur 224.0.0.251:5353 (ignore --port)
+    #[arg(long)]
+    mdns: bool,
+
+    /// Interface (adresse IPv4) sur laquelle rejoindre le groupe mDNS
+    #[arg(long, default_value = "0.0.0.0")]
+    mdns_interface: Ipv4Addr,
+
+    /// Enregistre chaque requête et réponse dans ce fichier pcap
+    #[arg(long)]
+    pcap: Option<PathBuf>,
+
+    /// Adresse de l'API d'administration HTTP (ex: 127.0.0.1:8054)
+    #[arg(long)]
+    admin: Option<SocketAddr>,
+
+    /// Token exigé par l'API d'administration (en-tête "Authorization: Bearer <token>")
+    #[arg(long, env = "DNS_ADMIN_TOKEN", default_value = "", hide_env_values = true)]
+    admin_token: String,
+
+    /// Base persistante des enregistrements (redb) ; sans elle, tout reste en mémoire
+    #[arg(long)]
+    store: Option<PathBuf>,
+}
+
+// Recharge le fichier de zone et remplace la base d'un coup.
+// En cas d'erreur, l'ancienne base reste en place. Le verrou d'écriture est celui
+// de l'API d'administration : une modification en cours n'est jamais écrasée.
+fn reload_zone(database: &ArcSwap<DnsDatabase>, store: &dyn RecordStore, chemin: &Path, ecriture: &Mutex<()>) -> Result<usize> {
+    let _verrou = ecriture.lock().unwrap();
+    let mut nouvelle = zone::load_zone(chemin)?;
+    nouvelle.serial = store.replace_all(&zone::zone_records(&nouvelle))?;
+    let total = nouvelle.all_records().len();
+    database.store(Arc::new(nouvelle));
+    Ok(total)
+}
+
+// Base de départ : le contenu du store s'il en a un, sinon la zone (ou les
+// domaines par défaut) qui sert alors à remplir le store
+fn initial_database(store: &dyn RecordStore, zone: Option<&Path>) -> Result<DnsDatabase> {
+    let records = store.records()?;
+    if !records.is_empty() {
+        return Ok(zone::database_from_records(&records, store.serial()?));
+    }
+
+    let mut database = match zone {
+        Some(chemin) => zone::load_zone(chemin)?,
+        None => DnsDatabase::new(),
+    };
+    database.serial = store.replace_all(&zone::zone_records(&database))?;
+    Ok(database)
+}
+
+// Requêtes transférées en même temps au plus : au-delà, SERVFAIL immédiat
+const MAX_TRANSFERTS: usize = 256;
+
+fn print_blocklist_stats(blocklists: &Blocklists) {
+    println!("📊 Hits des listes de blocage:");
+    for list in blocklists.lists() {
+        println!("   {}: {}", list.name, list.hits());
+    }
 }
 
 struct DnsServer {
     socket: UdpSocket,
-    database: Arc<Mutex<DnsDatabase>>,
+    database: Arc<ArcSwap<DnsDatabase>>,
+    zone: Option<PathBuf>,
+    store: Arc<dyn RecordStore>,
+    ecriture: Arc<Mutex<()>>, // rechargement de la zone et API d'administration
+    blocklists: Arc<Blocklists>,
+    capture: Option<Mutex<pcap::PcapWriter>>,
+    forwarder: Option<Forwarder>,
+    transferts: AtomicUsize,
     verbose: bool,
 }
 
 impl DnsServer {
-    fn new(addr: SocketAddr, verbose: bool) -> Result<Self> {
+    fn new(addr: SocketAddr, zone: Option<PathBuf>, store: Arc<dyn RecordStore>, blocklists: Blocklists, verbose: bool) -> Result<Self> {
         let socket = UdpSocket::bind(addr)?;
-        let database = Arc::new(Mutex::new(DnsDatabase::new()));
+        let initiale = initial_database(store.as_ref(), zone.as_deref())?;
+        let database = Arc::new(ArcSwap::from_pointee(initiale));
 
         Ok(Self {
             socket,
             database,
+            zone,
+            store,
+            ecriture: Arc::new(Mutex::new(())),
+            blocklists: Arc::new(blocklists),
+            capture: None,
+            forwarder: None,
+            transferts: AtomicUsize::new(0),
             verbose,
         })
     }
 
-    fn handle_query(&self, requete: DnsMessage, client_addr: SocketAddr) -> Result<()> {
+    fn enable_capture(&mut self, chemin: &Path) -> Result<()> {
+        self.capture = Some(Mutex::new(pcap::PcapWriter::create(chemin)?));
+        Ok(())
+    }
+
+    // Ajoute un datagramme à la capture pcap, si elle est activée
+    fn capture(&self, src: SocketAddr, dst: SocketAddr, payload: &[u8]) {
+        if let Some(capture) = &self.capture
+            && let Err(e) = capture.lock().unwrap().write_packet(src, dst, payload)
+        {
+            eprintln!("❌ Erreur d'écriture pcap: {}", e);
+        }
+    }
+
+    fn send_response(&self, reponse: &DnsMessage, client_addr: SocketAddr) -> Result<usize> {
+        self.send_bytes(&reponse.to_bytes(), client_addr)
+    }
+
+    fn send_bytes(&self, reponse_bytes: &[u8], client_addr: SocketAddr) -> Result<usize> {
+        self.socket.send_to(reponse_bytes, client_addr)?;
+        self.capture(self.socket.local_addr()?, client_addr, reponse_bytes);
+        Ok(reponse_bytes.len())
+    }
+
+    // Lance un thread qui traite les signaux :
+    // SIGHUP recharge la zone, SIGUSR1 affiche les compteurs des listes de blocage
+    fn spawn_signal_handler(&self) -> Result<()> {
+        let zone = self.zone.clone();
+        let database = self.database.clone();
+        let store = self.store.clone();
+        let ecriture = self.ecriture.clone();
+        let blocklists = self.blocklists.clone();
+        let mut signals = Signals::new([SIGHUP, SIGUSR1])?;
+
+        thread::spawn(move || {
+            for signal in signals.forever() {
+                match (signal, &zone) {
+                    (SIGHUP, Some(chemin)) => match reload_zone(&database, store.as_ref(), chemin, &ecriture) {
+                        Ok(total) => println!("🔄 Zone rechargée depuis {} ({} domaines)", chemin.display(), total),
+                        Err(e) => eprintln!("❌ Rechargement refusé, ancienne zone conservée: {}", e),
+                    },
+                    (SIGHUP, None) => println!("⚠️ SIGHUP ignoré: aucun fichier de zone (--zone)"),
+                    _ => print_blocklist_stats(&blocklists),
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn handle_query(self: &Arc<Self>, requete: DnsMessage, brut: &[u8], client_addr: SocketAddr) -> Result<()> {
         if self.verbose {
             println!("Requête reçue de {} (ID: {})", client_addr, requete.header.id);
         }
@@ -60,16 +224,63 @@ impl DnsServer {
             println!("  🔍 Recherche de '{}'", domaine);
         }
 
-        let database = self.database.lock().unwrap();
-        let reponse = match database.lookup(domaine) {
-            Some(ip) => {
+        if let Some(list) = self.blocklists.check(domaine) {
+            if self.verbose {
+                println!("  🚫 Bloqué par {}: {}", list.name, domaine);
+            }
+            let reponse = match self.blocklists.action {
+                BlockAction::NxDomain => {
+                    let mut reponse = DnsMessage::new_response(requete.header.id, question.clone(), vec![]);
+                    reponse.header.flags |= 0x0003; // NXDOMAIN
+                    reponse
+                }
+                // Le sinkhole est une adresse IPv4 : les autres types reçoivent une réponse vide
+                BlockAction::Sinkhole(ip) => {
+                    let answers = if question.qtype == TYPE_A {
+                        vec![DnsAnswer::new_a_record(domaine.clone(), ip, 60)]
+                    } else {
+                        vec![]
+                    };
+                    DnsMessage::new_response(requete.header.id, question.clone(), answers)
+                }
+            };
+            self.send_response(&reponse, client_addr)?;
+            return Ok(());
+        }
+
+        let database = self.database.load();
+        let reponse = match database.contains(domaine) {
+            true if question.qtype == TYPE_SOA => {
                 if self.verbose {
-                    println!("  ✅ Trouvé: {} -> {}", domaine, ip);
+                    println!("  ✅ SOA {} (serial {})", domaine, database.serial);
                 }
-                let answer = DnsAnswer::new_a_record(domaine.clone(), ip, 3600);
-                DnsMessage::new_response(requete.header.id, question.clone(), vec![answer])
+                let answers = vec![DnsAnswer::new_soa_record(domaine.clone(), database.serial, 3600)];
+                DnsMessage::new_response(requete.header.id, question.clone(), answers)
             }
-            None => {
+            true if question.qtype == TYPE_SRV => {
+                let services = database.services(domaine);
+                if self.verbose {
+                    println!("  ✅ Trouvé: {} -> {:?}", domaine, services);
+                }
+                let answers = services.iter()
+                    .map(|srv| DnsAnswer::new_srv_record(domaine.clone(), srv, 3600))
+                    .collect();
+                DnsMessage::new_response(requete.header.id, question.clone(), answers)
+            }
+            true => {
+                let adresses = database.resolve(domaine, question.qtype);
+                if self.verbose {
+                    println!("  ✅ Trouvé: {} -> {:?}", domaine, adresses);
+                }
+                let answers = adresses.into_iter()
+                    .map(|ip| DnsAnswer::new_address_record(domaine.clone(), ip, 3600))
+                    .collect();
+                DnsMessage::new_response(requete.header.id, question.clone(), answers)
+            }
+            false if self.forwarder.is_some() => {
+                return self.spawn_forward(requete, brut.to_vec(), client_addr);
+            }
+            false => {
                 if self.verbose {
                     println!("  ❌ Non trouvé: {}", domaine);
                 }
@@ -80,31 +291,95 @@ impl DnsServer {
             }
         };
 
-        let reponse_bytes = reponse.to_bytes();
-        self.socket.send_to(&reponse_bytes, client_addr)?;
+        let taille = self.send_response(&reponse, client_addr)?;
 
         if self.verbose {
-            println!("  📤 Réponse envoyée ({} bytes)", reponse_bytes.len());
+            println!("  📤 Réponse envoyée ({} bytes)", taille);
+        }
+
+        Ok(())
+    }
+
+    // Relaie la requête au résolveur amont dans un thread, pour que la boucle de
+    // réception ne reste pas bloquée pendant l'attente de la réponse
+    fn spawn_forward(self: &Arc<Self>, requete: DnsMessage, brut: Vec<u8>, client_addr: SocketAddr) -> Result<()> {
+        if self.transferts.fetch_add(1, Ordering::Relaxed) >= MAX_TRANSFERTS {
+            self.transferts.fetch_sub(1, Ordering::Relaxed);
+            self.send_response(&servfail(&requete), client_addr)?;
+            return Err(anyhow::anyhow!("{} requêtes déjà en attente du résolveur amont, SERVFAIL", MAX_TRANSFERTS));
         }
 
+        let server = Arc::clone(self);
+        thread::spawn(move || {
+            if let Err(e) = server.forward(&requete, &brut, client_addr) {
+                eprintln!("❌ Erreur lors du traitement: {}", e);
+            }
+            server.transferts.fetch_sub(1, Ordering::Relaxed);
+        });
         Ok(())
     }
 
-    fn run(&self) -> Result<()> {
+    // Renvoie au client la réponse du résolveur amont, ou SERVFAIL s'il ne répond pas
+    fn forward(&self, requete: &DnsMessage, brut: &[u8], client_addr: SocketAddr) -> Result<()> {
+        let Some(forwarder) = &self.forwarder else {
+            return Ok(());
+        };
+        if self.verbose {
+            println!("  ↪️ Transfert de '{}' à {}", requete.questions[0].name, forwarder.upstream);
+        }
+
+        match forwarder.forward(brut) {
+            Ok(reponse) => {
+                let taille = self.send_bytes(&reponse, client_addr)?;
+                if self.verbose {
+                    println!("  📤 Réponse amont relayée à {} ({} bytes)", client_addr, taille);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                self.send_response(&servfail(requete), client_addr)?;
+                Err(e)
+            }
+        }
+    }
+
+    fn run(self: Arc<Self>) -> Result<()> {
+        self.spawn_signal_handler()?;
+
         println!("🚀 Serveur DNS démarré sur {}", self.socket.local_addr()?);
-        println!("📋 Domaines configurés:");
+        if let Some(chemin) = &self.zone {
+            println!("📂 Zone: {} (kill -HUP {} pour recharger)", chemin.display(), std::process::id());
+        }
+        for list in self.blocklists.lists() {
+            println!("🚫 Liste de blocage: {} ({} domaines)", list.name, list.len());
+        }
+        if let Some(forwarder) = &self.forwarder {
+            println!("↪️ Noms hors zone transférés à {} (timeout {} ms)", forwarder.upstream, forwarder.timeout.as_millis());
+        }
+        println!("📋 Domaines configurés (serial SOA {}):", self.database.load().serial);
 
-        let database = self.database.lock().unwrap();
+        let database = self.database.load();
         let mut domains: Vec<_> = database.all_records().iter().collect();
         domains.sort_by_key(|(domain, _)| *domain);
 
-        for (domain, ip) in domains {
-            println!("   {} -> {}", domain, ip);
+        for (domain, name) in domains {
+            for record in &name.addresses {
+                let etat = if record.healthy { "" } else { " (hors service)" };
+                println!("   {} -> {} (poids {}){}", domain, record.ip, record.weight, etat);
+            }
+            for srv in &name.services {
+                println!("   {} -> SRV {} {} {}:{}", domain, srv.priority, srv.weight, srv.target, srv.port);
+            }
         }
         drop(database);
 
         println!("\n⏳ En attente de requêtes...\n");
 
+        self.serve()
+    }
+
+    // Boucle de réception des requêtes
+    fn serve(self: &Arc<Self>) -> Result<()> {
         let mut buffer = [0u8; 512];
 
         loop {
@@ -113,10 +388,11 @@ impl DnsServer {
                     if self.verbose {
                         println!("📨 Paquet reçu de {} ({} bytes)", client_addr, size);
                     }
+                    self.capture(client_addr, self.socket.local_addr()?, &buffer[..size]);
 
                     match DnsMessage::from_bytes(&buffer[..size]) {
                         Ok(requete) => {
-                            if let Err(e) = self.handle_query(requete, client_addr) {
+                            if let Err(e) = self.handle_query(requete, &buffer[..size], client_addr) {
                                 eprintln!("❌ Erreur lors du traitement: {}", e);
                             }
                         }
@@ -135,9 +411,39 @@ impl DnsServer {
     }
 }
 
+// Réponse vide avec le code SERVFAIL (échec du serveur)
+fn servfail(requete: &DnsMessage) -> DnsMessage {
+    let mut reponse = DnsMessage::new_response(requete.header.id, requete.questions[0].clone(), vec![]);
+    reponse.header.flags |= 0x0002; // SERVFAIL
+    reponse
+}
+
+fn run_mdns(args: &Args) -> Result<()> {
+    let database = match &args.zone {
+        Some(chemin) => zone::load_zone(chemin)?,
+        None => DnsDatabase::new(),
+    };
+
+    println!("🔧 Répondeur mDNS sur {}:{} (interface {})", mdns::MDNS_GROUP, mdns::MDNS_PORT, args.mdns_interface);
+    println!();
+
+    let responder = mdns::MdnsResponder::bind(mdns::MDNS_PORT, args.mdns_interface, database, args.verbose)?;
+
+    // Ctrl+C ou SIGTERM : on sort proprement de la boucle pour envoyer les goodbyes
+    let arret = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGINT, arret.clone())?;
+    signal_hook::flag::register(SIGTERM, arret.clone())?;
+
+    responder.run(&arret)
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    if args.mdns {
+        return run_mdns(&args);
+    }
+
     let addr = format!("{}:{}", args.address, args.port);
     let socket_addr: SocketAddr = addr.parse()
         .map_err(|_| anyhow::anyhow!("Adresse invalide: {}", addr))?;
@@ -147,13 +453,75 @@ fn main() -> Result<()> {
     println!("   Mode verbeux: {}", args.verbose);
     println!();
 
-    let server = DnsServer::new(socket_addr, args.verbose)?;
-    server.run()
+    let mut lists = Vec::new();
+    for chemin in &args.blocklist {
+        lists.push(BlockList::load(chemin)?);
+    }
+    let blocklists = Blocklists::new(lists, args.block_response);
+
+    let store: Arc<dyn RecordStore> = match &args.store {
+        Some(chemin) => {
+            println!("💾 Enregistrements persistés dans {}", chemin.display());
+            Arc::new(RedbStore::open(chemin)?)
+        }
+        None => Arc::new(MemoryStore::new()),
+    };
+
+    let mut server = DnsServer::new(socket_addr, args.zone.clone(), store, blocklists, args.verbose)?;
+    if let Some(chemin) = &args.pcap {
+        server.enable_capture(chemin)?;
+        println!("🎥 Capture pcap dans {}", chemin.display());
+    }
+    if let Some(upstream) = args.upstream {
+        server.forwarder = Some(Forwarder::new(upstream, Duration::from_millis(args.upstream_timeout)));
+    }
+    if let Some(addr) = args.admin {
+        let api = admin::AdminApi::bind(addr, args.admin_token.clone(), server.database.clone(), server.store.clone(), args.zone.clone(), server.ecriture.clone())?;
+        println!("🛠️  API d'administration sur http://{}", api.local_addr()?);
+        if args.zone.is_none() && args.store.is_none() {
+            println!("⚠️ Pas de fichier de zone (--zone) ni de base (--store) : les modifications seront perdues au redémarrage");
+        }
+        api.spawn();
+    }
+    Arc::new(server).run()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    // Serveur de test sur un port libre, servi dans un thread, avec la zone donnée
+    fn start_server(zone: &str, configure: impl FnOnce(&mut DnsServer)) -> SocketAddr {
+        let blocklists = Blocklists::new(vec![], BlockAction::NxDomain);
+        let mut server = DnsServer::new("127.0.0.1:0".parse().unwrap(), None, Arc::new(MemoryStore::new()), blocklists, false).unwrap();
+        server.database.store(Arc::new(zone::parse_zone(zone).unwrap()));
+        configure(&mut server);
+
+        let addr = server.socket.local_addr().unwrap();
+        let server = Arc::new(server);
+        thread::spawn(move || server.serve());
+        addr
+    }
+
+    // Envoie une question au serveur et renvoie sa réponse
+    fn query(server: SocketAddr, name: &str, qtype: u16) -> DnsMessage {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let id = rand::random();
+        socket.send_to(&DnsMessage::new_typed_query(id, name.to_string(), qtype).to_bytes(), server).unwrap();
+
+        let mut buffer = [0u8; 512];
+        let (size, _) = socket.recv_from(&mut buffer).unwrap();
+        let reponse = DnsMessage::from_bytes(&buffer[..size]).unwrap();
+        assert_eq!(reponse.header.id, id);
+        reponse
+    }
+
+    fn rcode(reponse: &DnsMessage) -> u16 {
+        reponse.header.flags & 0x000F
+    }
 
     #[test]
     fn test_dns_database() {
@@ -174,4 +542,94 @@ mod tests {
         assert_eq!(answer.name, "test.com");
         assert_eq!(answer.get_ip(), Some(Ipv4Addr::new(192, 168, 1, 1)));
     }
+
+    #[test]
+    fn test_reload_zone_keeps_old_data_on_error() {
+        let chemin = std::env::temp_dir().join(format!("tp7_zone_{}.txt", std::process::id()));
+        let database = ArcSwap::from_pointee(DnsDatabase::new());
+        let store = MemoryStore::new();
+
+        fs::write(&chemin, "reload.test A 10.1.2.3\n").unwrap();
+        assert_eq!(reload_zone(&database, &store, &chemin, &Mutex::new(())).unwrap(), 1);
+        assert_eq!(database.load().lookup("reload.test"), Some(Ipv4Addr::new(10, 1, 2, 3)));
+        assert_eq!(database.load().lookup("example.com"), None);
+        assert_eq!(database.load().serial, 1);
+
+        fs::write(&chemin, "reload.test A 999.0.0.1\n").unwrap();
+        assert!(reload_zone(&database, &store, &chemin, &Mutex::new(())).is_err());
+        assert_eq!(database.load().lookup("reload.test"), Some(Ipv4Addr::new(10, 1, 2, 3)));
+        assert_eq!(store.serial().unwrap(), 1);
+
+        fs::remove_file(&chemin).unwrap();
+    }
+
+    #[test]
+    fn test_blocklist_in_front_of_upstream() {
+        // Faux résolveur amont qui répond 10.9.9.9 à tout
+        let amont = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let amont_addr = amont.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            while let Ok((size, client)) = amont.recv_from(&mut buffer) {
+                let requete = DnsMessage::from_bytes(&buffer[..size]).unwrap();
+                let question = requete.questions[0].clone();
+                let answers = vec![DnsAnswer::new_a_record(question.name.clone(), Ipv4Addr::new(10, 9, 9, 9), 60)];
+                amont.send_to(&DnsMessage::new_response(requete.header.id, question, answers).to_bytes(), client).unwrap();
+            }
+        });
+
+        let serveur = start_server("local.test A 10.0.0.1\n", |server| {
+            server.blocklists = Arc::new(Blocklists::new(vec![BlockList::parse("pubs".to_string(), "ads.example.com")], BlockAction::NxDomain));
+            server.forwarder = Some(Forwarder::new(amont_addr, Duration::from_secs(1)));
+        });
+
+        assert_eq!(query(serveur, "local.test", TYPE_A).answers[0].get_ip(), Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(query(serveur, "www.github.com", TYPE_A).answers[0].get_ip(), Some(Ipv4Addr::new(10, 9, 9, 9)));
+        let bloque = query(serveur, "x.ads.example.com", TYPE_A);
+        assert_eq!(rcode(&bloque), 3);
+        assert!(bloque.answers.is_empty());
+
+        // Résolveur amont muet : SERVFAIL une fois le délai écoulé
+        let muet = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let muet_addr = muet.local_addr().unwrap();
+        let serveur = start_server("", |server| server.forwarder = Some(Forwarder::new(muet_addr, Duration::from_millis(100))));
+        assert_eq!(rcode(&query(serveur, "www.github.com", TYPE_A)), 2);
+    }
+
+    #[test]
+    fn test_round_robin_and_aaaa_answers() {
+        let serveur = start_server("web A 10.0.0.1\nweb A 10.0.0.2\nweb A 10.0.0.3 down\nweb AAAA 2001:db8::1\n", |_| {});
+
+        let premiere = query(serveur, "web", TYPE_A);
+        let seconde = query(serveur, "web", TYPE_A);
+        assert_eq!(premiere.answers.len(), 2);
+        assert_ne!(premiere.answers[0].get_ip(), seconde.answers[0].get_ip());
+
+        let v6 = query(serveur, "web", TYPE_AAAA);
+        assert_eq!(v6.answers.len(), 1);
+        assert_eq!(v6.answers[0].get_addr(), Some(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))));
+        assert_eq!(rcode(&query(serveur, "absent.test", TYPE_A)), 3);
+    }
+
+    #[test]
+    fn test_srv_answers() {
+        let serveur = start_server("_chat._tcp.example.com SRV 10 60 8080 chat1.example.com\n_chat._tcp.example.com SRV 20 0 8080 secours.example.com\n", |_| {});
+
+        let reponse = query(serveur, "_chat._tcp.example.com", TYPE_SRV);
+        let cibles: Vec<String> = reponse.answers.iter().filter_map(|answer| answer.get_srv()).map(|srv| srv.target).collect();
+        assert_eq!(cibles, vec!["chat1.example.com", "secours.example.com"]);
+    }
+
+    #[test]
+    fn test_store_contents_and_soa_serial_served() {
+        let store = MemoryStore::new();
+        store.replace_all(&[zone::ZoneRecord::parse_line("web.test A 10.0.0.1", 1).unwrap()]).unwrap();
+        store.apply(&storage::Change::Insert(zone::ZoneRecord::parse_line("api.test A 10.0.0.2", 1).unwrap())).unwrap();
+
+        // Le store a déjà du contenu : c'est lui qui est servi, avec son serial
+        let serveur = start_server("", |server| server.database.store(Arc::new(initial_database(&store, None).unwrap())));
+        assert_eq!(query(serveur, "web.test", TYPE_SOA).answers[0].get_soa_serial(), Some(2));
+        assert_eq!(query(serveur, "api.test", TYPE_A).answers[0].get_ip(), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(rcode(&query(serveur, "example.com", TYPE_SOA)), 3);
+    }
 }
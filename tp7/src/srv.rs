@@ -0,0 +1,100 @@
+// =============================
+// Découverte de services via les enregistrements SRV (RFC 2782)
+// Ordre de contact : priorité croissante, puis tirage pondéré par le poids
+// à l'intérieur de chaque priorité.
+// =============================
+
+use rand::Rng;
+
+use crate::dns::SrvRecord;
+
+// Nom à interroger pour un service : _service._proto.domaine
+pub fn service_name(service: &str, proto: &str, domain: &str) -> String {
+    format!(
+        "_{}._{}.{}",
+        service.trim_start_matches('_'),
+        proto.trim_start_matches('_'),
+        domain
+    )
+}
+
+// Trie les enregistrements dans l'ordre où il faut essayer les serveurs
+pub fn order_by_rfc2782<R: Rng>(mut records: Vec<SrvRecord>, rng: &mut R) -> Vec<SrvRecord> {
+    // Une seule cible "." : le service n'est pas disponible pour ce domaine
+    if records.len() == 1 && records[0].target.is_empty() {
+        return Vec::new();
+    }
+
+    // Les poids 0 passent en tête de leur groupe, comme le demande la RFC
+    records.sort_by_key(|record| (record.priority, record.weight != 0));
+
+    let mut ordre = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priorite = records[0].priority;
+        let fin = records.iter().position(|record| record.priority != priorite).unwrap_or(records.len());
+        let mut groupe: Vec<SrvRecord> = records.drain(..fin).collect();
+
+        while !groupe.is_empty() {
+            let total: u32 = groupe.iter().map(|record| record.weight as u32).sum();
+            let tirage = rng.gen_range(0..=total);
+
+            let mut cumul = 0;
+            let index = groupe.iter()
+                .position(|record| {
+                    cumul += record.weight as u32;
+                    cumul >= tirage
+                })
+                .unwrap_or(0);
+            ordre.push(groupe.remove(index));
+        }
+    }
+
+    ordre
+}
+
+// Liste "hôte:port" prête à être utilisée pour se connecter
+pub fn host_ports(records: &[SrvRecord]) -> Vec<String> {
+    records.iter()
+        .map(|record| format!("{}:{}", record.target, record.port))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn srv(priority: u16, weight: u16, target: &str) -> SrvRecord {
+        SrvRecord { priority, weight, port: 8080, target: target.to_string() }
+    }
+
+    #[test]
+    fn test_srv_ordering_priority_then_weight() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut premiers = 0;
+
+        for _ in 0..1000 {
+            let ordre = order_by_rfc2782(
+                vec![srv(20, 0, "secours"), srv(10, 90, "lourd"), srv(10, 10, "leger")],
+                &mut rng,
+            );
+            assert_eq!(ordre.len(), 3);
+            assert_eq!(ordre[2].target, "secours");
+            if ordre[0].target == "lourd" {
+                premiers += 1;
+            }
+        }
+
+        // ~90% des tirages doivent commencer par le serveur de poids 90
+        assert!((850..=950).contains(&premiers), "{}", premiers);
+    }
+
+    #[test]
+    fn test_srv_helpers() {
+        assert_eq!(service_name("chat", "tcp", "example.com"), "_chat._tcp.example.com");
+        assert_eq!(service_name("_chat", "_tcp", "example.com"), "_chat._tcp.example.com");
+        assert!(order_by_rfc2782(vec![srv(0, 0, "")], &mut StdRng::seed_from_u64(1)).is_empty());
+        assert_eq!(host_ports(&[srv(0, 0, "chat1.example.com")]), vec!["chat1.example.com:8080"]);
+    }
+}
@@ -0,0 +1,238 @@
+// =============================
+// Stockage des enregistrements
+// Le trait RecordStore a deux implémentations :
+//   - MemoryStore : tout en mémoire, perdu au redémarrage
+//   - RedbStore   : base clé/valeur embarquée (redb), qui survit aux redémarrages
+// Chaque modification incrémente le numéro de série SOA de la zone.
+// =============================
+
+use anyhow::{anyhow, Result};
+use redb::{Database, ReadableTable, TableDefinition};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::dns::DnsDatabase;
+use crate::zone::ZoneRecord;
+
+// Clé : identité de l'enregistrement ; valeur : l'enregistrement en JSON
+const RECORDS: TableDefinition<&str, &str> = TableDefinition::new("records");
+const META: TableDefinition<&str, u32> = TableDefinition::new("meta");
+const SERIAL_KEY: &str = "soa_serial";
+
+// Une modification de la zone
+#[derive(Debug, Clone)]
+pub enum Change {
+    Insert(ZoneRecord),
+    Update(ZoneRecord),
+    Remove(ZoneRecord),
+    RemoveName(String),
+}
+
+impl Change {
+    // Applique la modification à une base en mémoire ; faux si la cible n'existe pas
+    pub fn apply_to(&self, database: &mut DnsDatabase) -> bool {
+        match self {
+            Change::Insert(record) => {
+                record.insert_into(database);
+                true
+            }
+            Change::Update(record) => {
+                let existe = record.remove_from(database);
+                if existe {
+                    record.insert_into(database);
+                }
+                existe
+            }
+            Change::Remove(record) => record.remove_from(database),
+            Change::RemoveName(name) => database.remove_name(name),
+        }
+    }
+
+    // Applique la modification à une table identité -> enregistrement
+    fn apply_to_map(&self, records: &mut BTreeMap<String, ZoneRecord>) {
+        match self {
+            Change::Insert(record) | Change::Update(record) => {
+                records.insert(record.key(), record.clone());
+            }
+            Change::Remove(record) => {
+                records.remove(&record.key());
+            }
+            Change::RemoveName(name) => records.retain(|_, record| record.name() != name),
+        }
+    }
+}
+
+// Numéro de série suivant (arithmétique modulo 2^32 de la RFC 1982, 0 évité)
+fn next_serial(serial: u32) -> u32 {
+    serial.wrapping_add(1).max(1)
+}
+
+pub trait RecordStore: Send + Sync {
+    fn records(&self) -> Result<Vec<ZoneRecord>>;
+    fn serial(&self) -> Result<u32>;
+    // Enregistre la modification et renvoie le nouveau numéro de série
+    fn apply(&self, change: &Change) -> Result<u32>;
+    // Remplace tout le contenu (rechargement du fichier de zone)
+    fn replace_all(&self, records: &[ZoneRecord]) -> Result<u32>;
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    state: Mutex<(BTreeMap<String, ZoneRecord>, u32)>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RecordStore for MemoryStore {
+    fn records(&self) -> Result<Vec<ZoneRecord>> {
+        Ok(self.state.lock().unwrap().0.values().cloned().collect())
+    }
+
+    fn serial(&self) -> Result<u32> {
+        Ok(self.state.lock().unwrap().1)
+    }
+
+    fn apply(&self, change: &Change) -> Result<u32> {
+        let mut state = self.state.lock().unwrap();
+        change.apply_to_map(&mut state.0);
+        state.1 = next_serial(state.1);
+        Ok(state.1)
+    }
+
+    fn replace_all(&self, records: &[ZoneRecord]) -> Result<u32> {
+        let mut state = self.state.lock().unwrap();
+        state.0 = records.iter().map(|record| (record.key(), record.clone())).collect();
+        state.1 = next_serial(state.1);
+        Ok(state.1)
+    }
+}
+
+pub struct RedbStore {
+    db: Database,
+}
+
+impl RedbStore {
+    pub fn open<P: AsRef<Path>>(chemin: P) -> Result<Self> {
+        let chemin = chemin.as_ref();
+        let db = Database::create(chemin)
+            .map_err(|e| anyhow!("Impossible d'ouvrir {}: {}", chemin.display(), e))?;
+
+        // Crée les tables dès l'ouverture pour que les lectures n'échouent jamais
+        let tx = db.begin_write()?;
+        tx.open_table(RECORDS)?;
+        tx.open_table(META)?;
+        tx.commit()?;
+
+        Ok(Self { db })
+    }
+
+    // Exécute `modifier` sur les enregistrements et incrémente le serial, en une transaction
+    fn write<F>(&self, modifier: F) -> Result<u32>
+    where
+        F: FnOnce(&mut BTreeMap<String, ZoneRecord>),
+    {
+        let tx = self.db.begin_write()?;
+        let serial = {
+            let mut table = tx.open_table(RECORDS)?;
+            let avant = read_records(&table)?;
+            let mut records = avant.clone();
+            modifier(&mut records);
+
+            for cle in avant.keys().filter(|cle| !records.contains_key(*cle)) {
+                table.remove(cle.as_str())?;
+            }
+            for (cle, record) in &records {
+                if avant.get(cle) != Some(record) {
+                    table.insert(cle.as_str(), serde_json::to_string(record)?.as_str())?;
+                }
+            }
+
+            let mut meta = tx.open_table(META)?;
+            let serial = next_serial(meta.get(SERIAL_KEY)?.map(|v| v.value()).unwrap_or(0));
+            meta.insert(SERIAL_KEY, serial)?;
+            serial
+        };
+        tx.commit()?;
+        Ok(serial)
+    }
+
+    fn load_map(&self) -> Result<BTreeMap<String, ZoneRecord>> {
+        let tx = self.db.begin_read()?;
+        read_records(&tx.open_table(RECORDS)?)
+    }
+}
+
+fn read_records<T: ReadableTable<&'static str, &'static str>>(table: &T) -> Result<BTreeMap<String, ZoneRecord>> {
+    let mut records = BTreeMap::new();
+    for entree in table.iter()? {
+        let (cle, valeur) = entree?;
+        records.insert(cle.value().to_string(), serde_json::from_str(valeur.value())?);
+    }
+    Ok(records)
+}
+
+impl RecordStore for RedbStore {
+    fn records(&self) -> Result<Vec<ZoneRecord>> {
+        Ok(self.load_map()?.into_values().collect())
+    }
+
+    fn serial(&self) -> Result<u32> {
+        let tx = self.db.begin_read()?;
+        let meta = tx.open_table(META)?;
+        Ok(meta.get(SERIAL_KEY)?.map(|v| v.value()).unwrap_or(0))
+    }
+
+    fn apply(&self, change: &Change) -> Result<u32> {
+        self.write(|records| change.apply_to_map(records))
+    }
+
+    fn replace_all(&self, nouveaux: &[ZoneRecord]) -> Result<u32> {
+        self.write(|records| {
+            *records = nouveaux.iter().map(|record| (record.key(), record.clone())).collect();
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::TYPE_AAAA;
+    use crate::zone;
+    use std::fs;
+    use std::net::{IpAddr, Ipv6Addr};
+
+    #[test]
+    fn test_redb_store_persists_and_bumps_serial() {
+        let chemin = std::env::temp_dir().join(format!("tp7_store_{}.redb", std::process::id()));
+        let _ = fs::remove_file(&chemin);
+        let web = zone::ZoneRecord::parse_line("web.test A 10.0.0.1", 1).unwrap();
+        let api = zone::ZoneRecord::parse_line("api.test AAAA ::1", 2).unwrap();
+
+        {
+            let store = RedbStore::open(&chemin).unwrap();
+            assert_eq!(store.serial().unwrap(), 0);
+            assert_eq!(store.replace_all(std::slice::from_ref(&web)).unwrap(), 1);
+            assert_eq!(store.apply(&Change::Insert(api.clone())).unwrap(), 2);
+            assert_eq!(store.apply(&Change::Remove(web.clone())).unwrap(), 3);
+        }
+
+        // Réouverture : le contenu et le serial ont survécu, et la base est reconstruite depuis le store
+        let store = RedbStore::open(&chemin).unwrap();
+        assert_eq!(store.records().unwrap(), vec![api.clone()]);
+        let database = zone::database_from_records(&store.records().unwrap(), store.serial().unwrap());
+        assert_eq!(database.serial, 3);
+        assert_eq!(database.resolve("api.test", TYPE_AAAA), vec![IpAddr::V6(Ipv6Addr::LOCALHOST)]);
+        assert!(!database.contains("web.test"));
+
+        assert_eq!(store.apply(&Change::RemoveName("api.test".to_string())).unwrap(), 4);
+        assert!(store.records().unwrap().is_empty());
+
+        drop(store);
+        fs::remove_file(&chemin).unwrap();
+    }
+}
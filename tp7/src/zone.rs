@@ -0,0 +1,309 @@
+// =============================
+// Fichier de zone
+// Une ligne par enregistrement : <nom> <A|AAAA> <adresse> [weight=N] [down]
+//                              ou <nom> SRV <priorité> <poids> <port> <cible>
+// Un nom peut avoir plusieurs lignes (plusieurs adresses).
+// Les lignes vides et les commentaires (#) sont ignorés.
+// =============================
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+use crate::dns::{AddressRecord, DnsDatabase, SrvRecord};
+
+fn poids_par_defaut() -> u32 {
+    1
+}
+
+fn en_service() -> bool {
+    true
+}
+
+// Un enregistrement de zone, tel qu'il apparaît dans le fichier (une ligne)
+// ou dans l'API d'administration (JSON)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ZoneRecord {
+    A {
+        name: String,
+        address: Ipv4Addr,
+        #[serde(default = "poids_par_defaut")]
+        weight: u32,
+        #[serde(default = "en_service")]
+        healthy: bool,
+    },
+    #[serde(rename = "AAAA")]
+    Aaaa {
+        name: String,
+        address: Ipv6Addr,
+        #[serde(default = "poids_par_defaut")]
+        weight: u32,
+        #[serde(default = "en_service")]
+        healthy: bool,
+    },
+    #[serde(rename = "SRV")]
+    Srv {
+        name: String,
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+}
+
+#[allow(dead_code)]
+impl ZoneRecord {
+    // Identité de l'enregistrement : deux enregistrements de même clé se remplacent
+    pub fn key(&self) -> String {
+        match self {
+            ZoneRecord::A { name, address, .. } => format!("{} A {}", name, address),
+            ZoneRecord::Aaaa { name, address, .. } => format!("{} AAAA {}", name, address),
+            ZoneRecord::Srv { name, port, target, .. } => format!("{} SRV {}:{}", name, target, port),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ZoneRecord::A { name, .. } | ZoneRecord::Aaaa { name, .. } | ZoneRecord::Srv { name, .. } => name,
+        }
+    }
+
+    // Parse une ligne (sans commentaire) du fichier de zone
+    pub fn parse_line(ligne: &str, numero: usize) -> Result<Self> {
+        let champs: Vec<&str> = ligne.split_whitespace().collect();
+        if champs.len() < 3 {
+            return Err(anyhow!("Ligne {}: format attendu '<nom> <type> <adresse> [options]'", numero));
+        }
+
+        let name = champs[0].trim_end_matches('.').to_string();
+        if champs[1].eq_ignore_ascii_case("SRV") {
+            return parse_srv(name, &champs[2..], numero);
+        }
+
+        let ip: IpAddr = match champs[1].to_uppercase().as_str() {
+            "A" => champs[2]
+                .parse::<Ipv4Addr>()
+                .map(IpAddr::V4)
+                .map_err(|_| anyhow!("Ligne {}: adresse IPv4 invalide '{}'", numero, champs[2]))?,
+            "AAAA" => champs[2]
+                .parse::<Ipv6Addr>()
+                .map(IpAddr::V6)
+                .map_err(|_| anyhow!("Ligne {}: adresse IPv6 invalide '{}'", numero, champs[2]))?,
+            autre => {
+                return Err(anyhow!("Ligne {}: type d'enregistrement inconnu '{}'", numero, autre));
+            }
+        };
+
+        let mut record = AddressRecord::new(ip);
+        for option in &champs[3..] {
+            match option.split_once('=') {
+                Some(("weight", poids)) => {
+                    record.weight = poids
+                        .parse()
+                        .map_err(|_| anyhow!("Ligne {}: poids invalide '{}'", numero, poids))?;
+                }
+                None if *option == "down" => record.healthy = false,
+                _ => return Err(anyhow!("Ligne {}: option inconnue '{}'", numero, option)),
+            }
+        }
+        Ok(Self::from_address(name, &record))
+    }
+
+    pub fn to_line(&self) -> String {
+        match self {
+            ZoneRecord::A { name, address, weight, healthy } => address_line(name, "A", address, *weight, *healthy),
+            ZoneRecord::Aaaa { name, address, weight, healthy } => address_line(name, "AAAA", address, *weight, *healthy),
+            ZoneRecord::Srv { name, priority, weight, port, target } => {
+                format!("{} SRV {} {} {} {}", name, priority, weight, port, if target.is_empty() { "." } else { target })
+            }
+        }
+    }
+
+    fn from_address(name: String, record: &AddressRecord) -> Self {
+        match record.ip {
+            IpAddr::V4(address) => ZoneRecord::A { name, address, weight: record.weight, healthy: record.healthy },
+            IpAddr::V6(address) => ZoneRecord::Aaaa { name, address, weight: record.weight, healthy: record.healthy },
+        }
+    }
+
+    // Ajoute (ou remplace) l'enregistrement dans la base
+    pub fn insert_into(&self, database: &mut DnsDatabase) {
+        match self.clone() {
+            ZoneRecord::A { name, address, weight, healthy } => {
+                database.add_address(name, AddressRecord { ip: IpAddr::V4(address), weight, healthy });
+            }
+            ZoneRecord::Aaaa { name, address, weight, healthy } => {
+                database.add_address(name, AddressRecord { ip: IpAddr::V6(address), weight, healthy });
+            }
+            ZoneRecord::Srv { name, priority, weight, port, target } => {
+                database.add_service(name, SrvRecord { priority, weight, port, target });
+            }
+        }
+    }
+
+    // Retire l'enregistrement de la base (identifié par son adresse, ou sa cible et son port)
+    pub fn remove_from(&self, database: &mut DnsDatabase) -> bool {
+        match self {
+            ZoneRecord::A { name, address, .. } => database.remove_address(name, IpAddr::V4(*address)),
+            ZoneRecord::Aaaa { name, address, .. } => database.remove_address(name, IpAddr::V6(*address)),
+            ZoneRecord::Srv { name, port, target, .. } => database.remove_service(name, target, *port),
+        }
+    }
+}
+
+fn address_line(name: &str, rtype: &str, address: &dyn std::fmt::Display, weight: u32, healthy: bool) -> String {
+    let mut ligne = format!("{} {} {}", name, rtype, address);
+    if weight != 1 {
+        ligne.push_str(&format!(" weight={}", weight));
+    }
+    if !healthy {
+        ligne.push_str(" down");
+    }
+    ligne
+}
+
+// <priorité> <poids> <port> <cible>
+fn parse_srv(name: String, champs: &[&str], ligne: usize) -> Result<ZoneRecord> {
+    let [priority, weight, port, target] = champs else {
+        return Err(anyhow!("Ligne {}: format attendu '<nom> SRV <priorité> <poids> <port> <cible>'", ligne));
+    };
+    let nombre = |valeur: &str| {
+        valeur.parse::<u16>().map_err(|_| anyhow!("Ligne {}: nombre invalide '{}'", ligne, valeur))
+    };
+
+    Ok(ZoneRecord::Srv {
+        name,
+        priority: nombre(priority)?,
+        weight: nombre(weight)?,
+        port: nombre(port)?,
+        target: target.trim_end_matches('.').to_string(),
+    })
+}
+
+// Construit une base DNS à partir du contenu d'un fichier de zone
+pub fn parse_zone(contenu: &str) -> Result<DnsDatabase> {
+    let mut database = DnsDatabase::empty();
+
+    for (numero, ligne) in contenu.lines().enumerate() {
+        let ligne = ligne.split('#').next().unwrap_or("").trim();
+        if ligne.is_empty() {
+            continue;
+        }
+        ZoneRecord::parse_line(ligne, numero + 1)?.insert_into(&mut database);
+    }
+
+    Ok(database)
+}
+
+// Construit une base à partir d'enregistrements (ceux d'un RecordStore par exemple)
+#[allow(dead_code)]
+pub fn database_from_records(records: &[ZoneRecord], serial: u32) -> DnsDatabase {
+    let mut database = DnsDatabase::empty();
+    for record in records {
+        record.insert_into(&mut database);
+    }
+    database.serial = serial;
+    database
+}
+
+// Tous les enregistrements de la base, triés par nom
+#[allow(dead_code)]
+pub fn zone_records(database: &DnsDatabase) -> Vec<ZoneRecord> {
+    let mut noms: Vec<&String> = database.all_records().keys().collect();
+    noms.sort();
+
+    let mut records = Vec::new();
+    for nom in noms {
+        let name = &database.all_records()[nom];
+        for address in &name.addresses {
+            records.push(ZoneRecord::from_address(nom.clone(), address));
+        }
+        for srv in &name.services {
+            records.push(ZoneRecord::Srv {
+                name: nom.clone(),
+                priority: srv.priority,
+                weight: srv.weight,
+                port: srv.port,
+                target: srv.target.clone(),
+            });
+        }
+    }
+    records
+}
+
+// Nouvelle version du fichier de zone, écrite à côté de l'ancienne (.tmp) puis
+// renommée par commit() : un lecteur (ou un SIGHUP) ne voit jamais un fichier à
+// moitié écrit. Si elle n'est pas mise en place, le fichier temporaire est supprimé.
+pub struct PendingZone {
+    temporaire: PathBuf,
+    chemin: PathBuf,
+    en_place: bool,
+}
+
+#[allow(dead_code)]
+impl PendingZone {
+    pub fn write<P: AsRef<Path>>(chemin: P, database: &DnsDatabase) -> Result<Self> {
+        let chemin = chemin.as_ref().to_path_buf();
+        let mut contenu = String::from("# Fichier généré par dns_server (API d'administration)\n");
+        for record in zone_records(database) {
+            contenu.push_str(&record.to_line());
+            contenu.push('\n');
+        }
+
+        let temporaire = chemin.with_extension("tmp");
+        fs::write(&temporaire, contenu)
+            .map_err(|e| anyhow!("Impossible d'écrire {}: {}", temporaire.display(), e))?;
+        Ok(Self { temporaire, chemin, en_place: false })
+    }
+
+    // Remplace l'ancien fichier par le nouveau (renommage atomique)
+    pub fn commit(mut self) -> Result<()> {
+        fs::rename(&self.temporaire, &self.chemin)
+            .map_err(|e| anyhow!("Impossible de remplacer {}: {}", self.chemin.display(), e))?;
+        self.en_place = true;
+        Ok(())
+    }
+}
+
+impl Drop for PendingZone {
+    fn drop(&mut self) {
+        if !self.en_place {
+            let _ = fs::remove_file(&self.temporaire);
+        }
+    }
+}
+
+// Lit et parse un fichier de zone
+pub fn load_zone<P: AsRef<Path>>(chemin: P) -> Result<DnsDatabase> {
+    let chemin = chemin.as_ref();
+    let contenu = fs::read_to_string(chemin)
+        .map_err(|e| anyhow!("Impossible de lire {}: {}", chemin.display(), e))?;
+    parse_zone(&contenu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_parsing() {
+        let db = parse_zone("# commentaire\nexample.org. A 10.0.0.1\n\nfoo.local A 10.0.0.2 # fin\n").unwrap();
+
+        assert_eq!(db.lookup("example.org"), Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(db.lookup("foo.local"), Some(Ipv4Addr::new(10, 0, 0, 2)));
+        assert!(parse_zone("example.org A pas-une-ip").is_err());
+        assert!(parse_zone("example.org MX 10.0.0.1").is_err());
+    }
+
+    #[test]
+    fn test_srv_zone_line() {
+        let db = parse_zone("_chat._tcp.example.com SRV 10 60 8080 chat1.example.com.\n").unwrap();
+        let srv = &db.services("_chat._tcp.example.com")[0];
+        assert_eq!((srv.priority, srv.weight, srv.port), (10, 60, 8080));
+        assert_eq!(srv.target, "chat1.example.com");
+        assert!(parse_zone("_chat._tcp.example.com SRV 10 60 chat1.example.com").is_err());
+    }
+}